@@ -0,0 +1,27 @@
+use std::hint::black_box;
+
+use bookbinding::{arrange_pages_with, plan_signatures, Pages, SignatureParams};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn arrange_pages(c: &mut Criterion) {
+    let params = SignatureParams::default();
+    let mut group = c.benchmark_group("arrange_pages");
+    for num_pages in [100, 1000, 10000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_pages),
+            &num_pages,
+            |b, &num_pages| {
+                b.iter(|| {
+                    let plan = plan_signatures(Pages(num_pages).sheets_needed(), params);
+                    let mut order = vec![0; num_pages];
+                    arrange_pages_with(&plan, |src, dest| order[dest] = src);
+                    black_box(order)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, arrange_pages);
+criterion_main!(benches);
@@ -0,0 +1,222 @@
+//! The `analyze` command, which describes the pages of a document and suggests how to impose it,
+//! without changing it.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use bookbinding::{Pages, MAX_FOLDABLE_SHEETS};
+use log::info;
+use lopdf::Document;
+
+use crate::{
+    length::POINTS_PER_MM,
+    pdf::{self, PlacedBox},
+};
+
+/// Describes the pages of a PDF, and suggests options to impose it with.
+#[derive(Debug, clap::Args)]
+pub struct AnalyzeArgs {
+    /// Path to the PDF to analyze
+    pub input: PathBuf,
+}
+
+/// Common sizes of paper to print on, in points, as portrait.
+const SHEET_SIZES: [(&str, (f32, f32)); 4] = [
+    ("A4", (595.0, 842.0)),
+    ("Letter", (612.0, 792.0)),
+    ("A3", (842.0, 1191.0)),
+    ("Tabloid", (792.0, 1224.0)),
+];
+
+/// Prints the analysis of the input.
+pub fn run(args: &AnalyzeArgs) -> color_eyre::Result<()> {
+    let document = Document::load(&args.input)?;
+    let num_pages = document.page_iter().size_hint().0;
+    info!("Number of pages: {num_pages}");
+    let sizes = page_sizes(&document);
+    info!("Page sizes:");
+    for &((width, height), count) in &sizes {
+        info!(
+            "  {} x {} mm ({width} x {height} pt), {}: {count} pages",
+            mm(width),
+            mm(height),
+            orientation((width, height))
+        );
+    }
+    if sizes.len() > 1 {
+        info!("The pages are not all the same size, so the sheets below are for the most common.");
+    }
+    let num_sheets = Pages(num_pages).sheets_needed().0;
+    info!("Sheets needed: {num_sheets}, folded once with 4 pages on each");
+    let (signature_size, reason) = suggest_signature_size(num_sheets);
+    info!("Suggested: --signature-size {signature_size}");
+    info!("  {reason}");
+    if let Some(&((width, height), _)) = sizes.first() {
+        for line in sheet_lines((width as f32, height as f32)) {
+            info!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// The distinct sizes of the pages, as they are displayed (taking their rotation into account) and
+/// rounded to whole points, with the number of pages of each size, most common first.
+pub fn page_sizes(document: &Document) -> Vec<((u32, u32), usize)> {
+    let mut counts = BTreeMap::new();
+    for page_id in document.page_iter() {
+        let Some([x0, y0, x1, y1]) = pdf::placed_box(document, page_id, PlacedBox::Crop) else {
+            continue;
+        };
+        let (width, height) = (
+            (x1 - x0).abs().round() as u32,
+            (y1 - y0).abs().round() as u32,
+        );
+        let size = match pdf::rotation(document, page_id) {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        };
+        *counts.entry(size).or_insert(0) += 1;
+    }
+    let mut sizes = counts.into_iter().collect::<Vec<_>>();
+    sizes.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    sizes
+}
+
+/// Whether a page of this size is portrait, landscape, or square.
+fn orientation((width, height): (u32, u32)) -> &'static str {
+    match width.cmp(&height) {
+        std::cmp::Ordering::Less => "portrait",
+        std::cmp::Ordering::Equal => "square",
+        std::cmp::Ordering::Greater => "landscape",
+    }
+}
+
+/// Converts a length in points to whole millimetres.
+fn mm(points: u32) -> u32 {
+    (points as f32 / POINTS_PER_MM).round() as u32
+}
+
+/// Suggests a signature size for a book with this many sheets, with the reason for it.
+///
+/// A book which fits in a single foldable signature is best kept as one. Otherwise, sizes from 4
+/// sheets up to the most which can be folded are considered, preferring one which splits the sheets
+/// evenly, then one which leaves the fullest last signature, and then the one closest to the
+/// default of 6 sheets.
+pub fn suggest_signature_size(num_sheets: usize) -> (usize, String) {
+    if num_sheets <= MAX_FOLDABLE_SHEETS {
+        return (
+            0,
+            format!(
+                "all {num_sheets} sheets fit in a single signature, which can be folded as long \
+                 as it has at most {MAX_FOLDABLE_SHEETS} sheets"
+            ),
+        );
+    }
+    let default = bookbinding::SignatureParams::default().signature_size;
+    let size = (4..=MAX_FOLDABLE_SHEETS)
+        .min_by_key(|&size| {
+            let remainder = num_sheets % size;
+            // a remainder of 0 is a full last signature
+            let missing = if remainder == 0 { 0 } else { size - remainder };
+            (missing, size.abs_diff(default))
+        })
+        .unwrap_or(default);
+    let (count, remainder) = (num_sheets / size, num_sheets % size);
+    let reason = if remainder == 0 {
+        format!("{num_sheets} sheets split evenly into {count} signatures of {size} sheets")
+    } else {
+        format!(
+            "{num_sheets} sheets make {count} signatures of {size} sheets and a last signature of \
+             {remainder}, which is the fullest last signature of any size from 4 to \
+             {MAX_FOLDABLE_SHEETS} sheets"
+        )
+    };
+    (size, reason)
+}
+
+/// Describes how pages of this size fit on each of the common sheet sizes: whether a spread of two
+/// pages fits for folding, and how many pages fit in a `--grid` for cutting apart.
+fn sheet_lines((width, height): (f32, f32)) -> Vec<String> {
+    let mut lines = vec!["Common sheet sizes:".to_owned()];
+    for (name, sheet) in SHEET_SIZES {
+        let spread = if fits((2.0 * width, height), sheet) {
+            "a two-page spread fits for folding"
+        } else {
+            "a two-page spread does not fit"
+        };
+        let line = match grid_fit((width, height), sheet) {
+            Some((columns, rows)) => format!(
+                "  {name}: {spread}; {} pages fit with --grid {columns}x{rows}",
+                columns * rows
+            ),
+            None => format!("  {name}: {spread}; the pages do not fit for cutting"),
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+/// Whether a rectangle fits on a sheet, either way round.
+fn fits((width, height): (f32, f32), (sheet_width, sheet_height): (f32, f32)) -> bool {
+    (width <= sheet_width && height <= sheet_height)
+        || (width <= sheet_height && height <= sheet_width)
+}
+
+/// The grid with the most pages of this size which fits on a sheet, either way round, or `None` if
+/// not even one page fits. The margins around the grid for its cut marks are taken into account.
+fn grid_fit((width, height): (f32, f32), sheet: (f32, f32)) -> Option<(usize, usize)> {
+    let margin = 2.0 * bookbinding::MARK_MARGIN;
+    [sheet, (sheet.1, sheet.0)]
+        .into_iter()
+        .map(|(sheet_width, sheet_height)| {
+            let columns = ((sheet_width - margin) / width).floor().max(0.0) as usize;
+            let rows = ((sheet_height - margin) / height).floor().max(0.0) as usize;
+            (columns, rows)
+        })
+        .filter(|&(columns, rows)| columns > 0 && rows > 0)
+        .max_by_key(|&(columns, rows)| columns * rows)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::testing;
+
+    #[test]
+    pub fn page_sizes() {
+        let mut document = testing::document_with_boxes(&[
+            [0.0, 0.0, 420.0, 595.0],
+            [0.0, 0.0, 595.0, 842.0],
+            [0.0, 0.0, 420.2, 594.8],
+            [0.0, 0.0, 595.0, 420.0],
+        ]);
+        // a landscape page turned upright
+        let page_id = document.page_iter().nth(3).unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Rotate", 90);
+        assert_eq!(
+            super::page_sizes(&document),
+            [((420, 595), 3), ((595, 842), 1)]
+        );
+    }
+
+    #[test_case(5 => 0)]
+    #[test_case(8 => 0)]
+    #[test_case(10 => 5)]
+    #[test_case(12 => 6)]
+    #[test_case(13 => 7)]
+    #[test_case(24 => 6)]
+    #[test_case(29 => 6)]
+    pub fn suggest_signature_size(num_sheets: usize) -> usize {
+        super::suggest_signature_size(num_sheets).0
+    }
+
+    #[test_case((420.0, 595.0), (595.0, 842.0) => Some((1, 1)))]
+    #[test_case((180.0, 252.0), (842.0, 595.0) => Some((3, 3)))]
+    #[test_case((900.0, 900.0), (595.0, 842.0) => None)]
+    fn grid_fit(page: (f32, f32), sheet: (f32, f32)) -> Option<(usize, usize)> {
+        super::grid_fit(page, sheet)
+    }
+}
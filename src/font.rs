@@ -0,0 +1,428 @@
+//! Reading and subsetting TrueType fonts, for embedding the bundled font in text drawn by the tool
+//! (see [`crate::pdf::embed_fonts`]).
+//!
+//! Subsetting keeps the glyph ids of the original font, and empties the outlines of the glyphs
+//! which are not needed, so that nothing which refers to the glyphs has to be renumbered. Only
+//! the tables which a PDF viewer uses to draw the glyphs are kept.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use color_eyre::eyre::{bail, eyre};
+
+/// DejaVu Sans, with the outlines of the glyphs for `WinAnsiEncoding`, which are all the characters
+/// that text drawn by the tool can use (see [`crate::pdf::text_operations`]). See `fonts/LICENSE`
+/// for its license.
+pub const DEJAVU_SANS: &[u8] = include_bytes!("../fonts/DejaVuSans-WinAnsi.ttf");
+
+/// The tables kept in a subset. The other tables are only used for laying out text, which the
+/// PDF does itself, or are not needed to draw the glyphs.
+const SUBSET_TABLES: [&[u8; 4]; 13] = [
+    b"OS/2", b"cmap", b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp",
+    b"name", b"post", b"prep",
+];
+
+/// Flags of a component of a composite glyph.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// The metrics of a font for its PDF font descriptor, in thousandths of an em.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    pub bbox: [f32; 4],
+    pub ascent: f32,
+    pub descent: f32,
+    pub cap_height: f32,
+}
+
+/// A TrueType font, read from its tables.
+pub struct Font<'a> {
+    tables: BTreeMap<[u8; 4], &'a [u8]>,
+}
+
+impl<'a> Font<'a> {
+    /// Reads the table directory of a font.
+    pub fn parse(data: &'a [u8]) -> color_eyre::Result<Self> {
+        let num_tables = read_u16(data, 4)?;
+        let mut tables = BTreeMap::new();
+        for index in 0..usize::from(num_tables) {
+            let record = 12 + 16 * index;
+            let tag = data
+                .get(record..record + 4)
+                .and_then(|tag| <[u8; 4]>::try_from(tag).ok())
+                .ok_or_else(|| eyre!("the font's table directory is truncated"))?;
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            let table = data
+                .get(offset..offset + length)
+                .ok_or_else(|| eyre!("the font's {} table is truncated", tag_name(&tag)))?;
+            tables.insert(tag, table);
+        }
+        Ok(Font { tables })
+    }
+
+    /// The table with the given tag.
+    fn table(&self, tag: &[u8; 4]) -> color_eyre::Result<&'a [u8]> {
+        self.tables
+            .get(tag)
+            .copied()
+            .ok_or_else(|| eyre!("the font has no {} table", tag_name(tag)))
+    }
+
+    /// The number of font units in an em.
+    pub fn units_per_em(&self) -> color_eyre::Result<u16> {
+        read_u16(self.table(b"head")?, 18)
+    }
+
+    /// The number of glyphs in the font.
+    fn num_glyphs(&self) -> color_eyre::Result<u16> {
+        read_u16(self.table(b"maxp")?, 4)
+    }
+
+    /// The id of the glyph for a character, from the Unicode BMP subtable of the character map,
+    /// or 0 (the missing glyph) if the font has no glyph for it.
+    pub fn glyph_id(&self, c: char) -> color_eyre::Result<u16> {
+        let Ok(code) = u16::try_from(u32::from(c)) else {
+            return Ok(0);
+        };
+        let cmap = self.table(b"cmap")?;
+        let num_subtables = read_u16(cmap, 2)?;
+        let mut subtable = None;
+        for index in 0..usize::from(num_subtables) {
+            let record = 4 + 8 * index;
+            if (read_u16(cmap, record)?, read_u16(cmap, record + 2)?) == (3, 1) {
+                subtable = Some(read_u32(cmap, record + 4)? as usize);
+            }
+        }
+        let subtable = subtable.ok_or_else(|| eyre!("the font has no Unicode character map"))?;
+        if read_u16(cmap, subtable)? != 4 {
+            bail!("the font's Unicode character map is not format 4");
+        }
+        let seg_count = usize::from(read_u16(cmap, subtable + 6)? / 2);
+        let end_codes = subtable + 14;
+        let start_codes = end_codes + 2 * seg_count + 2;
+        let id_deltas = start_codes + 2 * seg_count;
+        let id_range_offsets = id_deltas + 2 * seg_count;
+        for segment in 0..seg_count {
+            if read_u16(cmap, end_codes + 2 * segment)? < code {
+                continue;
+            }
+            let start = read_u16(cmap, start_codes + 2 * segment)?;
+            if start > code {
+                return Ok(0);
+            }
+            let delta = read_u16(cmap, id_deltas + 2 * segment)?;
+            let range_offset_at = id_range_offsets + 2 * segment;
+            let range_offset = read_u16(cmap, range_offset_at)?;
+            if range_offset == 0 {
+                return Ok(code.wrapping_add(delta));
+            }
+            // the range offset is relative to its own position in the subtable
+            let glyph_at =
+                range_offset_at + usize::from(range_offset) + 2 * usize::from(code - start);
+            return match read_u16(cmap, glyph_at)? {
+                0 => Ok(0),
+                glyph => Ok(glyph.wrapping_add(delta)),
+            };
+        }
+        Ok(0)
+    }
+
+    /// The advance width of a glyph, in font units.
+    pub fn advance(&self, glyph: u16) -> color_eyre::Result<u16> {
+        let num_metrics = read_u16(self.table(b"hhea")?, 34)?;
+        // glyphs after the last metric have the same advance as it
+        let index = glyph.min(num_metrics.saturating_sub(1));
+        read_u16(self.table(b"hmtx")?, 4 * usize::from(index))
+    }
+
+    /// The metrics of the font, for its font descriptor.
+    pub fn metrics(&self) -> color_eyre::Result<Metrics> {
+        let scale = 1000.0 / f32::from(self.units_per_em()?);
+        let head = self.table(b"head")?;
+        let hhea = self.table(b"hhea")?;
+        let mut bbox = [0.0; 4];
+        for (index, value) in bbox.iter_mut().enumerate() {
+            *value = f32::from(read_i16(head, 36 + 2 * index)?) * scale;
+        }
+        let ascent = f32::from(read_i16(hhea, 4)?) * scale;
+        // the cap height is only in version 2 and later of the OS/2 table
+        let cap_height = match self.table(b"OS/2") {
+            Ok(os2) if read_u16(os2, 0)? >= 2 => f32::from(read_i16(os2, 88)?) * scale,
+            _ => ascent,
+        };
+        Ok(Metrics {
+            bbox,
+            ascent,
+            descent: f32::from(read_i16(hhea, 6)?) * scale,
+            cap_height,
+        })
+    }
+
+    /// The outline of a glyph, which is empty for a glyph with no outline, such as a space.
+    fn glyph(&self, glyph: u16) -> color_eyre::Result<&'a [u8]> {
+        let loca = self.table(b"loca")?;
+        let glyph = usize::from(glyph);
+        let (start, end) = match read_i16(self.table(b"head")?, 50)? {
+            // short offsets are stored divided by 2
+            0 => (
+                2 * usize::from(read_u16(loca, 2 * glyph)?),
+                2 * usize::from(read_u16(loca, 2 * glyph + 2)?),
+            ),
+            _ => (
+                read_u32(loca, 4 * glyph)? as usize,
+                read_u32(loca, 4 * glyph + 4)? as usize,
+            ),
+        };
+        self.table(b"glyf")?
+            .get(start..end)
+            .ok_or_else(|| eyre!("the outline of glyph {glyph} is truncated"))
+    }
+
+    /// The glyphs which a composite glyph is made of, or none for a simple glyph.
+    fn components(&self, glyph: u16) -> color_eyre::Result<Vec<u16>> {
+        let outline = self.glyph(glyph)?;
+        if outline.is_empty() || read_i16(outline, 0)? >= 0 {
+            return Ok(Vec::new());
+        }
+        let mut components = Vec::new();
+        let mut offset = 10;
+        loop {
+            let flags = read_u16(outline, offset)?;
+            components.push(read_u16(outline, offset + 2)?);
+            offset += 4;
+            offset += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                4
+            } else {
+                2
+            };
+            if flags & WE_HAVE_A_SCALE != 0 {
+                offset += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                offset += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                offset += 8;
+            }
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(components);
+            }
+        }
+    }
+
+    /// A font with only the outlines of the glyphs for the given characters (and of the missing
+    /// glyph), and a character map of only those characters.
+    pub fn subset(&self, chars: &BTreeSet<char>) -> color_eyre::Result<Vec<u8>> {
+        let mut map = BTreeMap::new();
+        for &c in chars {
+            if let (Ok(code), glyph @ 1..) = (u16::try_from(u32::from(c)), self.glyph_id(c)?) {
+                map.insert(code, glyph);
+            }
+        }
+        // the glyphs to keep, including the components of composite glyphs
+        let mut kept = BTreeSet::from([0]);
+        let mut pending = map.values().copied().collect::<Vec<_>>();
+        while let Some(glyph) = pending.pop() {
+            if kept.insert(glyph) {
+                pending.extend(self.components(glyph)?);
+            }
+        }
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in 0..self.num_glyphs()? {
+            loca.extend(u32::try_from(glyf.len())?.to_be_bytes());
+            if kept.contains(&glyph) {
+                glyf.extend(self.glyph(glyph)?);
+                // each outline starts on a 4 byte boundary
+                glyf.resize(glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        loca.extend(u32::try_from(glyf.len())?.to_be_bytes());
+
+        let mut head = self.table(b"head")?.to_vec();
+        if head.len() < 54 {
+            bail!("the font's head table is truncated");
+        }
+        // the checksum adjustment is worked out again for the subset
+        head[8..12].fill(0);
+        // long offsets in the `loca` table
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        // post table version 3, with no glyph names
+        let mut post = self
+            .table(b"post")?
+            .get(..32)
+            .ok_or_else(|| eyre!("the font's post table is truncated"))?
+            .to_vec();
+        post[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+
+        let mut tables = BTreeMap::new();
+        for tag in SUBSET_TABLES {
+            if let Some(&table) = self.tables.get(tag) {
+                tables.insert(*tag, table.to_vec());
+            }
+        }
+        tables.insert(*b"cmap", unicode_cmap(&map));
+        tables.insert(*b"glyf", glyf);
+        tables.insert(*b"loca", loca);
+        tables.insert(*b"head", head);
+        tables.insert(*b"post", post);
+        Ok(font_file(tables))
+    }
+}
+
+/// A character map with a single Unicode BMP subtable, in format 4, which maps each character to
+/// its glyph with a segment of its own.
+fn unicode_cmap(map: &BTreeMap<u16, u16>) -> Vec<u8> {
+    // each character, and the segment for 0xFFFF which must end the subtable
+    let segments = map
+        .iter()
+        .map(|(&code, &glyph)| (code, glyph.wrapping_sub(code)))
+        .chain([(0xFFFF, 1)])
+        .collect::<Vec<_>>();
+    let seg_count = segments.len() as u16;
+    let entry_selector = 15 - seg_count.leading_zeros() as u16;
+    let search_range = 2 << entry_selector;
+    let mut subtable = Vec::new();
+    for value in [
+        4,
+        16 + 8 * seg_count,
+        0,
+        2 * seg_count,
+        search_range,
+        entry_selector,
+        2 * seg_count - search_range,
+    ] {
+        subtable.extend(value.to_be_bytes());
+    }
+    subtable.extend(segments.iter().flat_map(|&(code, _)| code.to_be_bytes()));
+    // reserved padding
+    subtable.extend([0, 0]);
+    subtable.extend(segments.iter().flat_map(|&(code, _)| code.to_be_bytes()));
+    subtable.extend(segments.iter().flat_map(|&(_, delta)| delta.to_be_bytes()));
+    subtable.extend(segments.iter().flat_map(|_| [0, 0]));
+
+    let mut cmap = Vec::new();
+    for value in [0u16, 1, 3, 1] {
+        cmap.extend(value.to_be_bytes());
+    }
+    cmap.extend(12u32.to_be_bytes());
+    cmap.extend(subtable);
+    cmap
+}
+
+/// Assembles the tables of a font into a font file.
+fn font_file(tables: BTreeMap<[u8; 4], Vec<u8>>) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+    let mut font = Vec::new();
+    font.extend(0x0001_0000u32.to_be_bytes());
+    for value in [
+        num_tables,
+        search_range,
+        entry_selector,
+        16 * num_tables - search_range,
+    ] {
+        font.extend(value.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, table) in &tables {
+        font.extend(tag);
+        font.extend(checksum(table).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        offset += table.len().next_multiple_of(4);
+    }
+    for table in tables.values() {
+        font.extend(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    if let Some(head_offset) = head_offset {
+        // the adjustment makes the checksum of the whole font a fixed value
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+/// The checksum of a table, or of a whole font: the sum of its 32-bit words, padded with zeros.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|word| {
+            let mut bytes = [0; 4];
+            bytes[..word.len()].copy_from_slice(word);
+            u32::from_be_bytes(bytes)
+        })
+        .fold(0, u32::wrapping_add)
+}
+
+/// The tag of a table, for error messages.
+fn tag_name(tag: &[u8; 4]) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_owned()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> color_eyre::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| eyre!("the font is truncated"))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> color_eyre::Result<i16> {
+    Ok(read_u16(data, offset)? as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> color_eyre::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| eyre!("the font is truncated"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::{Font, DEJAVU_SANS};
+
+    #[test]
+    fn bundled_font() {
+        let font = Font::parse(DEJAVU_SANS).unwrap();
+        assert_eq!(font.units_per_em().unwrap(), 2048);
+        let a = font.glyph_id('a').unwrap();
+        assert_ne!(a, 0);
+        assert!(!font.glyph(a).unwrap().is_empty());
+        assert_eq!(font.glyph_id('\u{4e00}').unwrap(), 0);
+        let metrics = font.metrics().unwrap();
+        assert!(metrics.ascent > 0.0 && metrics.descent < 0.0);
+    }
+
+    #[test]
+    fn subset() {
+        let font = Font::parse(DEJAVU_SANS).unwrap();
+        // an accented letter is a composite of the letter and the accent
+        let chars = BTreeSet::from(['A', 'é', ' ']);
+        let data = font.subset(&chars).unwrap();
+        assert!(data.len() < DEJAVU_SANS.len());
+        assert_eq!(super::checksum(&data), 0xB1B0_AFBA);
+        let subset = Font::parse(&data).unwrap();
+        for c in ['A', 'é', ' ', 'B'] {
+            let glyph = font.glyph_id(c).unwrap();
+            let expected = if chars.contains(&c) { glyph } else { 0 };
+            assert_eq!(subset.glyph_id(c).unwrap(), expected, "{c:?}");
+            assert_eq!(subset.advance(glyph).unwrap(), font.advance(glyph).unwrap());
+        }
+        let accented = font.glyph_id('é').unwrap();
+        let components = font.components(accented).unwrap();
+        assert!(!components.is_empty());
+        for glyph in components.into_iter().chain([accented]) {
+            assert_eq!(subset.glyph(glyph).unwrap(), font.glyph(glyph).unwrap());
+        }
+        let b = font.glyph_id('B').unwrap();
+        assert!(subset.glyph(b).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Expansion of wildcards in the name of the input, so that several files can be imposed as one
+//! document, such as the chapters of a book.
+
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::bail;
+
+/// Whether a path has wildcards in its file name which should be expanded.
+pub fn is_pattern(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(['*', '?']))
+        && !path.exists()
+}
+
+/// The files matching a pattern, in natural order (see [`natural_cmp`]). Only the file name can
+/// contain wildcards: `*` matches any number of characters, and `?` matches one character. Files
+/// starting with `.` are only matched by patterns which also start with `.`. It is an error if no
+/// files match.
+pub fn expand(pattern: &Path) -> color_eyre::Result<Vec<PathBuf>> {
+    let directory = pattern.parent().unwrap_or(Path::new(""));
+    if directory.to_string_lossy().contains(['*', '?']) {
+        bail!(
+            "wildcards are only supported in the file name, not in the directory of {}",
+            pattern.display()
+        );
+    }
+    let name_pattern = pattern
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let listed = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(listed)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if matches(&name_pattern, &name) && entry.file_type()?.is_file() {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        bail!("no files match {}", pattern.display());
+    }
+    names.sort_by(|a, b| natural_cmp(a, b));
+    Ok(names.iter().map(|name| directory.join(name)).collect())
+}
+
+/// Whether a file name matches a pattern with `*` and `?` wildcards.
+fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // the positions to go back to when a match after a `*` fails
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // let the `*` match one more character
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    p = star + 1;
+                    n = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Compares file names in natural order, where runs of digits are compared as numbers, so that
+/// `ch2.pdf` comes before `ch10.pdf`. Numbers which are equal but written differently (such as
+/// `02` and `2`) are ordered by their digits, so that the order is still total.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (digits_a, rest_a) = split_digits(a);
+            let (digits_b, rest_b) = split_digits(b);
+            let (trimmed_a, trimmed_b) = (
+                digits_a.trim_start_matches('0'),
+                digits_b.trim_start_matches('0'),
+            );
+            let order = trimmed_a
+                .len()
+                .cmp(&trimmed_b.len())
+                .then_with(|| trimmed_a.cmp(trimmed_b))
+                .then_with(|| digits_a.cmp(digits_b));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (rest_a, rest_b);
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+        }
+    }
+}
+
+/// Splits a string into its leading ASCII digits and the rest.
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use test_case::test_case;
+
+    #[test_case("*.pdf", "ch1.pdf" => true)]
+    #[test_case("*.pdf", "ch1.pdf.bak" => false)]
+    #[test_case("ch?.pdf", "ch1.pdf" => true)]
+    #[test_case("ch?.pdf", "ch10.pdf" => false)]
+    #[test_case("ch*1*.pdf", "ch2-1-b.pdf" => true)]
+    #[test_case("*", ".hidden.pdf" => false)]
+    #[test_case(".*", ".hidden.pdf" => true)]
+    #[test_case("ch**", "ch" => true)]
+    fn matches(pattern: &str, name: &str) -> bool {
+        super::matches(pattern, name)
+    }
+
+    #[test_case("ch2.pdf", "ch10.pdf" => Ordering::Less)]
+    #[test_case("ch10.pdf", "ch9.pdf" => Ordering::Greater)]
+    #[test_case("ch02.pdf", "ch2.pdf" => Ordering::Less)]
+    #[test_case("ch2.pdf", "ch2.pdf" => Ordering::Equal)]
+    #[test_case("ch2.pdf", "ch2a.pdf" => Ordering::Less)]
+    #[test_case("appendix.pdf", "ch1.pdf" => Ordering::Less)]
+    fn natural_cmp(a: &str, b: &str) -> Ordering {
+        super::natural_cmp(a, b)
+    }
+
+    #[test]
+    fn expand() {
+        let directory =
+            std::env::temp_dir().join(format!("bookbinding-glob-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for name in ["ch10.pdf", "ch2.pdf", "ch1.pdf", "notes.txt"] {
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+        let expanded = super::expand(&directory.join("ch*.pdf"));
+        let missing = super::expand(&directory.join("*.ps"));
+        std::fs::remove_dir_all(&directory).unwrap();
+        let names = expanded
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["ch1.pdf", "ch2.pdf", "ch10.pdf"]);
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,632 @@
+//! Imposition of pages in a grid on each sheet, for items which are cut apart rather than folded,
+//! such as cards and bookmarks.
+
+use std::str::FromStr;
+
+use bookbinding::{edge_marks, mark_operations, MARK_MARGIN, MARK_OFFSET, MARK_WIDTH};
+use color_eyre::eyre::eyre;
+use lopdf::{content::Operation, Content, Dictionary, Document, Object, Stream};
+
+use crate::{
+    pdf::{self, PlacedBox},
+    transform::PageTransform,
+};
+
+/// Radius of the circle of each registration mark. Its cross extends `MARK_OFFSET` further.
+const REGISTRATION_RADIUS: f32 = 3.0;
+/// Width and height of each patch of the color bar.
+const PATCH_SIZE: f32 = 10.0;
+/// The colors of the patches of the color bar, in CMYK: steps of 25% of each process color, then
+/// each overprint of two colors and of all three.
+const COLOR_BAR: [[f32; 4]; 20] = [
+    [0.25, 0.0, 0.0, 0.0],
+    [0.5, 0.0, 0.0, 0.0],
+    [0.75, 0.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 0.25, 0.0, 0.0],
+    [0.0, 0.5, 0.0, 0.0],
+    [0.0, 0.75, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 0.25, 0.0],
+    [0.0, 0.0, 0.5, 0.0],
+    [0.0, 0.0, 0.75, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 0.25],
+    [0.0, 0.0, 0.0, 0.5],
+    [0.0, 0.0, 0.0, 0.75],
+    [0.0, 0.0, 0.0, 1.0],
+    [1.0, 1.0, 0.0, 0.0],
+    [1.0, 0.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0, 0.0],
+    [1.0, 1.0, 1.0, 0.0],
+];
+/// Name of the registration color space in the resources of each sheet.
+const REGISTRATION: &str = "Registration";
+
+/// Marks for printing on a press, added to the margins of each sheet along with the cut marks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PressMarks {
+    /// Registration marks in each corner, drawn on every plate, for lining up the plates.
+    pub registration: bool,
+    /// A bar of CMYK color patches along the bottom of the sheet, below the cut marks, for
+    /// checking the ink.
+    pub color_bar: bool,
+}
+
+/// The number of columns and rows of pages on each sheet, written as `<columns>x<rows>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error =
+            || format!("invalid grid {value:?}, expected `<columns>x<rows>`, such as `3x4`");
+        let (columns, rows) = value.split_once('x').ok_or_else(error)?;
+        let columns = columns.trim().parse().map_err(|_| error())?;
+        let rows = rows.trim().parse().map_err(|_| error())?;
+        if columns == 0 || rows == 0 {
+            return Err(format!(
+                "grid {value:?} must have at least one column and row"
+            ));
+        }
+        Ok(Grid { columns, rows })
+    }
+}
+
+/// The positions of the slots in a grid, in points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridLayout {
+    pub grid: Grid,
+    /// The width and height of each slot.
+    pub cell: (f32, f32),
+    /// The space between neighbouring slots.
+    pub gap: f32,
+    /// The space between the grid and the edges of the sheet.
+    pub margin: f32,
+}
+
+impl GridLayout {
+    /// The width and height of the sheet, which fits the grid and its margins exactly.
+    pub fn sheet_size(&self) -> (f32, f32) {
+        let (width, height) = self.grid_size();
+        (width + 2.0 * self.margin, height + 2.0 * self.margin)
+    }
+
+    /// The width and height of the grid, not including its margins.
+    fn grid_size(&self) -> (f32, f32) {
+        let Grid { columns, rows } = self.grid;
+        let (width, height) = self.cell;
+        (
+            columns as f32 * width + (columns - 1) as f32 * self.gap,
+            rows as f32 * height + (rows - 1) as f32 * self.gap,
+        )
+    }
+
+    /// The rectangle of each slot, filling the rows from left to right, starting with the top row.
+    pub fn slots(&self) -> Vec<[f32; 4]> {
+        let Grid { columns, rows } = self.grid;
+        bookbinding::nup_slots(columns, rows, self.cell, self.gap, self.margin)
+    }
+
+    /// The cut marks, as line segments `[x0, y0, x1, y1]` in the margins, lined up with the edges
+    /// of each column and row. Edges which are shared by neighbouring slots (when there is no gap)
+    /// only have one mark. See [`edge_marks`].
+    pub fn cut_marks(&self) -> Vec<[f32; 4]> {
+        let (sheet_width, sheet_height) = self.sheet_size();
+        let (width, height) = self.cell;
+        let edges = |count: usize, size: f32| {
+            let mut edges = (0..count)
+                .flat_map(|i| {
+                    let start = self.margin + i as f32 * (size + self.gap);
+                    [start, start + size]
+                })
+                .collect::<Vec<_>>();
+            edges.dedup();
+            edges
+        };
+        let block = [
+            self.margin,
+            self.margin,
+            sheet_width - self.margin,
+            sheet_height - self.margin,
+        ];
+        edge_marks(
+            block,
+            &edges(self.grid.columns, width),
+            &edges(self.grid.rows, height),
+        )
+    }
+
+    /// The center of the registration mark in each corner of the sheet, which is clear of the cut
+    /// marks as long as the margin is at least [`MARK_MARGIN`].
+    pub fn registration_marks(&self) -> [(f32, f32); 4] {
+        let (sheet_width, sheet_height) = self.sheet_size();
+        let offset = self.margin / 2.0;
+        [
+            (offset, offset),
+            (sheet_width - offset, offset),
+            (offset, sheet_height - offset),
+            (sheet_width - offset, sheet_height - offset),
+        ]
+    }
+
+    /// The rectangles and colors of the patches of the color bar, in a row along the bottom edge
+    /// of the sheet, under the grid. The margin must be at least [`color_bar_margin`] for the bar
+    /// to be clear of the cut marks. Only as many patches as fit under the grid are included.
+    pub fn color_bar(&self) -> Vec<([f32; 4], [f32; 4])> {
+        let (sheet_width, _) = self.sheet_size();
+        let available = ((sheet_width - 2.0 * self.margin) / PATCH_SIZE).floor() as usize;
+        let y0 = MARK_OFFSET;
+        COLOR_BAR
+            .iter()
+            .take(available)
+            .enumerate()
+            .map(|(index, &color)| {
+                let x0 = self.margin + index as f32 * PATCH_SIZE;
+                ([x0, y0, x0 + PATCH_SIZE, y0 + PATCH_SIZE], color)
+            })
+            .collect()
+    }
+}
+
+/// The margin needed to fit the color bar below the cut marks.
+pub fn color_bar_margin() -> f32 {
+    MARK_MARGIN + PATCH_SIZE + MARK_OFFSET
+}
+
+/// Replaces the pages of the document with sheets which each have the pages in a grid, in order,
+/// with cut marks around the grid, and any press marks. Each page is placed upright in the middle
+/// of its slot, and the slots are all the size of the largest page. Returns the number of sheets.
+pub fn impose(
+    document: &mut Document,
+    grid: Grid,
+    gap: f32,
+    press_marks: PressMarks,
+) -> color_eyre::Result<usize> {
+    let root_id = document.catalog()?.get(b"Pages")?.as_reference()?;
+    let mut placed = Vec::new();
+    for page_id in document.page_iter().collect::<Vec<_>>() {
+        let rect = pdf::placed_box(document, page_id, PlacedBox::Crop)
+            .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+        let transform = PageTransform::rotation(pdf::rotation(document, page_id), rect);
+        let xobject_id = pdf::page_xobject(document, page_id)?;
+        placed.push((xobject_id, transform, transform.transform_rect(rect)));
+    }
+    let cell = placed.iter().fold(
+        (0.0f32, 0.0f32),
+        |(width, height), (_, _, [x0, y0, x1, y1])| (width.max(x1 - x0), height.max(y1 - y0)),
+    );
+    let layout = GridLayout {
+        grid,
+        cell,
+        gap,
+        margin: if press_marks.color_bar {
+            color_bar_margin()
+        } else {
+            MARK_MARGIN
+        },
+    };
+    let slots = layout.slots();
+    let (sheet_width, sheet_height) = layout.sheet_size();
+    let sheet_box = Object::from(vec![
+        0.into(),
+        0.into(),
+        sheet_width.into(),
+        sheet_height.into(),
+    ]);
+    let mut marks = mark_operations(&layout.cut_marks());
+    let mut resources = Dictionary::new();
+    if press_marks.registration {
+        marks.extend(registration_operations(&layout.registration_marks()));
+        let color_spaces = [(REGISTRATION, registration_color_space())];
+        resources.set(
+            "ColorSpace",
+            Object::Dictionary(color_spaces.into_iter().collect()),
+        );
+    }
+    if press_marks.color_bar {
+        marks.extend(color_bar_operations(&layout.color_bar()));
+    }
+
+    let mut sheet_ids = Vec::new();
+    for pages in placed.chunks(slots.len()) {
+        let mut operations = marks.clone();
+        let mut xobjects = Dictionary::new();
+        for (index, ((xobject_id, transform, rect), slot)) in pages.iter().zip(&slots).enumerate() {
+            // the page's own resources stay with its form XObject, so this only needs to be
+            // unique on the sheet, even if the page uses the same name for one of its own forms
+            let name = format!("P{index}");
+            // center the page in its slot
+            let [x0, y0, x1, y1] = *rect;
+            let [sx0, sy0, sx1, sy1] = *slot;
+            let dx = sx0 + ((sx1 - sx0) - (x1 - x0)) / 2.0 - x0;
+            let dy = sy0 + ((sy1 - sy0) - (y1 - y0)) / 2.0 - y0;
+            let matrix = transform.then([1.0, 0.0, 0.0, 1.0, dx, dy]).matrix;
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.map(Object::from).to_vec()),
+                Operation::new("Do", vec![Object::Name(name.clone().into_bytes())]),
+                Operation::new("Q", vec![]),
+            ]);
+            xobjects.set(name, *xobject_id);
+        }
+        let mut sheet_resources = resources.clone();
+        sheet_resources.set("XObject", xobjects);
+        let content = Content { operations }.encode()?;
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+        // every inheritable attribute is set, so that nothing is inherited from the page tree
+        let sheet = Object::Dictionary(
+            [
+                ("Type", Object::from("Page")),
+                ("Parent", Object::from(root_id)),
+                ("Contents", Object::from(content_id)),
+                ("Resources", Object::Dictionary(sheet_resources)),
+                ("MediaBox", sheet_box.clone()),
+                ("CropBox", sheet_box.clone()),
+                ("Rotate", Object::from(0)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        sheet_ids.push(document.add_object(sheet));
+    }
+    pdf::set_page_order(document, &sheet_ids)?;
+    document.prune_objects();
+    Ok(sheet_ids.len())
+}
+
+/// The `/Separation /All` color space, which is printed on every plate, so that registration marks
+/// show on each of them. Its alternate for display is the tint on all four process colors.
+fn registration_color_space() -> Object {
+    let tint_transform = Object::Dictionary(
+        [
+            ("FunctionType", Object::from(2)),
+            ("Domain", Object::from(vec![0.into(), 1.into()])),
+            ("C0", Object::from(vec![0.into(); 4])),
+            ("C1", Object::from(vec![1.into(); 4])),
+            ("N", Object::from(1)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    Object::from(vec![
+        Object::from("Separation"),
+        Object::from("All"),
+        Object::from("DeviceCMYK"),
+        tint_transform,
+    ])
+}
+
+/// Operations which stroke a registration mark, a circle with a cross through it, centered on each
+/// of the given points, in the registration color.
+fn registration_operations(centers: &[(f32, f32)]) -> Vec<Operation> {
+    // the distance of the control points of a cubic Bézier curve approximating a quarter circle
+    let control = REGISTRATION_RADIUS * 0.552_284_8;
+    let (radius, arm) = (REGISTRATION_RADIUS, REGISTRATION_RADIUS + MARK_OFFSET);
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("w", vec![MARK_WIDTH.into()]),
+        Operation::new("CS", vec![Object::from(REGISTRATION)]),
+        Operation::new("SCN", vec![1.into()]),
+    ];
+    let point = |x: f32, y: f32| [Object::from(x), Object::from(y)];
+    for &(x, y) in centers {
+        operations.extend([
+            Operation::new("m", point(x - arm, y).to_vec()),
+            Operation::new("l", point(x + arm, y).to_vec()),
+            Operation::new("m", point(x, y - arm).to_vec()),
+            Operation::new("l", point(x, y + arm).to_vec()),
+            Operation::new("m", point(x + radius, y).to_vec()),
+        ]);
+        // the four quarters of the circle, counterclockwise from the right
+        let (r, k) = (radius, control);
+        let quarters = [
+            [(r, k), (k, r), (0.0, r)],
+            [(-k, r), (-r, k), (-r, 0.0)],
+            [(-r, -k), (-k, -r), (0.0, -r)],
+            [(k, -r), (r, -k), (r, 0.0)],
+        ];
+        for quarter in quarters {
+            let operands = quarter
+                .into_iter()
+                .flat_map(|(dx, dy)| point(x + dx, y + dy));
+            operations.push(Operation::new("c", operands.collect()));
+        }
+        operations.push(Operation::new("S", vec![]));
+    }
+    operations.push(Operation::new("Q", vec![]));
+    operations
+}
+
+/// Operations which fill each patch of a color bar with its CMYK color.
+fn color_bar_operations(patches: &[([f32; 4], [f32; 4])]) -> Vec<Operation> {
+    let mut operations = vec![Operation::new("q", vec![])];
+    for &([x0, y0, x1, y1], color) in patches {
+        operations.extend([
+            Operation::new("k", color.map(Object::from).to_vec()),
+            Operation::new(
+                "re",
+                vec![x0.into(), y0.into(), (x1 - x0).into(), (y1 - y0).into()],
+            ),
+            Operation::new("f", vec![]),
+        ]);
+    }
+    operations.push(Operation::new("Q", vec![]));
+    operations
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{Document, Object, Stream};
+    use test_case::test_case;
+
+    use super::{Grid, GridLayout};
+    use crate::testing;
+
+    #[test_case("3x4" => Some((3, 4)))]
+    #[test_case(" 2 x 1 " => Some((2, 1)))]
+    #[test_case("0x4" => None)]
+    #[test_case("3" => None)]
+    #[test_case("3x4x5" => None)]
+    fn parse(value: &str) -> Option<(usize, usize)> {
+        let Grid { columns, rows } = value.trim().parse().ok()?;
+        Some((columns, rows))
+    }
+
+    #[test]
+    fn slots() {
+        let layout = GridLayout {
+            grid: Grid {
+                columns: 3,
+                rows: 4,
+            },
+            cell: (100.0, 50.0),
+            gap: 10.0,
+            margin: 18.0,
+        };
+        assert_eq!(layout.sheet_size(), (356.0, 266.0));
+        let slots = layout.slots();
+        assert_eq!(slots.len(), 12);
+        assert_eq!(slots[0], [18.0, 198.0, 118.0, 248.0]);
+        assert_eq!(slots[1], [128.0, 198.0, 228.0, 248.0]);
+        assert_eq!(slots[2], [238.0, 198.0, 338.0, 248.0]);
+        assert_eq!(slots[3], [18.0, 138.0, 118.0, 188.0]);
+        assert_eq!(slots[11], [238.0, 18.0, 338.0, 68.0]);
+        // two marks for each edge of each column and row
+        assert_eq!(layout.cut_marks().len(), 2 * (2 * 3 + 2 * 4));
+        let no_gap = GridLayout { gap: 0.0, ..layout };
+        assert_eq!(no_gap.cut_marks().len(), 2 * (4 + 5));
+    }
+
+    #[test_case(0.0)]
+    #[test_case(2.0)]
+    #[test_case(20.0)]
+    fn marks_outside_slots(gap: f32) {
+        let layout = GridLayout {
+            grid: Grid {
+                columns: 3,
+                rows: 2,
+            },
+            cell: (60.0, 90.0),
+            gap,
+            margin: bookbinding::MARK_MARGIN,
+        };
+        let (sheet_width, sheet_height) = layout.sheet_size();
+        let half_width = bookbinding::MARK_WIDTH / 2.0;
+        let slots = layout.slots();
+        for [x0, y0, x1, y1] in layout.cut_marks() {
+            let mark = [
+                x0.min(x1) - half_width,
+                y0.min(y1) - half_width,
+                x0.max(x1) + half_width,
+                y0.max(y1) + half_width,
+            ];
+            assert!(mark[0] >= 0.0 && mark[1] >= 0.0);
+            assert!(mark[2] <= sheet_width && mark[3] <= sheet_height);
+            for slot in &slots {
+                let overlaps = mark[0] < slot[2]
+                    && slot[0] < mark[2]
+                    && mark[1] < slot[3]
+                    && slot[1] < mark[3];
+                assert!(!overlaps, "mark {mark:?} overlaps slot {slot:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn press_marks() {
+        let layout = GridLayout {
+            grid: Grid {
+                columns: 2,
+                rows: 2,
+            },
+            cell: (100.0, 50.0),
+            gap: 0.0,
+            margin: super::color_bar_margin(),
+        };
+        // 200 points under the grid fit 20 patches, the whole bar
+        let patches = layout.color_bar();
+        assert_eq!(patches.len(), 20);
+        let cut_marks_bottom = layout
+            .cut_marks()
+            .iter()
+            .map(|&[_, y0, _, y1]| y0.min(y1))
+            .fold(f32::INFINITY, f32::min);
+        for ([_, y0, _, y1], _) in patches {
+            assert!(y0 >= 0.0 && y1 < cut_marks_bottom);
+        }
+        let narrow = GridLayout {
+            cell: (25.0, 50.0),
+            ..layout
+        };
+        assert_eq!(narrow.color_bar().len(), 5);
+
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 50.0]; 2]);
+        let press_marks = super::PressMarks {
+            registration: true,
+            color_bar: true,
+        };
+        super::impose(&mut document, layout.grid, 0.0, press_marks).unwrap();
+        let page_id = document.page_iter().next().unwrap();
+        let content = String::from_utf8(document.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.contains("/Registration CS"));
+        assert_eq!(content.matches(" SCN").count(), 1);
+        assert_eq!(content.matches(" c\n").count(), 4 * 4);
+        assert_eq!(content.matches(" k\n").count(), 20);
+        let color_space = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Resources")
+            .and_then(|resources| resources.as_dict()?.get(b"ColorSpace")?.as_dict())
+            .and_then(|color_spaces| color_spaces.get(b"Registration")?.as_array())
+            .unwrap();
+        let names = color_space[..3]
+            .iter()
+            .map(|name| name.as_name().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [&b"Separation"[..], b"All", b"DeviceCMYK"]);
+    }
+
+    #[test]
+    fn impose() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 50.0]; 5]);
+        let grid = Grid {
+            columns: 2,
+            rows: 2,
+        };
+        let press_marks = super::PressMarks::default();
+        assert_eq!(
+            super::impose(&mut document, grid, 0.0, press_marks).unwrap(),
+            2
+        );
+        let contents = document
+            .page_iter()
+            .map(|id| String::from_utf8(document.get_page_content(id).unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(contents[0].matches(" Do").count(), 4);
+        assert_eq!(contents[1].matches(" Do").count(), 1);
+        let page_id = document.page_iter().next().unwrap();
+        let xobjects = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Resources")
+            .and_then(|resources| resources.as_dict()?.get(b"XObject")?.as_dict())
+            .unwrap();
+        let first = xobjects.get(b"P0").unwrap().as_reference().unwrap();
+        let content = document.get_object(first).unwrap().as_stream().unwrap();
+        assert!(content.content.starts_with(b"% page 0\n"));
+    }
+
+    #[test]
+    fn page_form_names() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 50.0]; 2]);
+        // the first page already draws forms named like the sheet's
+        let forms = ["Fm0", "P0", "P1"].map(|name| {
+            let form = Stream::new(
+                [
+                    ("Type", Object::from("XObject")),
+                    ("Subtype", Object::from("Form")),
+                ]
+                .into_iter()
+                .collect(),
+                format!("% form {name}\n").into_bytes(),
+            );
+            (name, Object::from(document.add_object(form)))
+        });
+        let page_id = document.page_iter().next().unwrap();
+        document.get_dictionary_mut(page_id).unwrap().set(
+            "Resources",
+            Object::Dictionary(
+                [("XObject", Object::Dictionary(forms.into_iter().collect()))]
+                    .into_iter()
+                    .collect(),
+            ),
+        );
+        let grid = Grid {
+            columns: 2,
+            rows: 1,
+        };
+        super::impose(&mut document, grid, 0.0, Default::default()).unwrap();
+        let xobjects = |document: &Document, id| {
+            let dict = match document.get_object(id).unwrap() {
+                Object::Stream(stream) => &stream.dict,
+                object => object.as_dict().unwrap(),
+            };
+            dict.get(b"Resources")
+                .and_then(|resources| resources.as_dict()?.get(b"XObject")?.as_dict())
+                .unwrap()
+                .clone()
+        };
+        let sheet = xobjects(&document, document.page_iter().next().unwrap());
+        let names = sheet
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [b"P0".to_vec(), b"P1".to_vec()]);
+        // each of the sheet's names is the page itself, which still has its own forms
+        for (index, (_, id)) in sheet.iter().enumerate() {
+            let id = id.as_reference().unwrap();
+            let form = document.get_object(id).unwrap().as_stream().unwrap();
+            assert!(form
+                .content
+                .starts_with(format!("% page {index}\n").as_bytes()));
+        }
+        let first = sheet.get(b"P0").unwrap().as_reference().unwrap();
+        let page_forms = xobjects(&document, first);
+        for name in ["Fm0", "P0", "P1"] {
+            let id = page_forms
+                .get(name.as_bytes())
+                .unwrap()
+                .as_reference()
+                .unwrap();
+            let form = document.get_object(id).unwrap().as_stream().unwrap();
+            assert_eq!(form.content, format!("% form {name}\n").into_bytes());
+        }
+    }
+
+    #[test]
+    fn inherited_rotation() {
+        // landscape pages turned upright, by each page or by the page tree node above them
+        let grid = Grid {
+            columns: 2,
+            rows: 1,
+        };
+        let mut direct = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 50.0]; 2]);
+        for page_id in direct.page_iter().collect::<Vec<_>>() {
+            direct
+                .get_dictionary_mut(page_id)
+                .unwrap()
+                .set("Rotate", 90);
+        }
+        let mut inherited = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 50.0]; 2]);
+        let root_id = inherited
+            .catalog()
+            .unwrap()
+            .get(b"Pages")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        inherited
+            .get_dictionary_mut(root_id)
+            .unwrap()
+            .set("Rotate", 90);
+        let mut sheets = Vec::new();
+        for mut document in [direct, inherited] {
+            super::impose(&mut document, grid, 0.0, Default::default()).unwrap();
+            let page_id = document.page_iter().next().unwrap();
+            assert_eq!(crate::pdf::rotation(&document, page_id), 0);
+            let page = document.get_dictionary(page_id).unwrap().clone();
+            let content = document.get_page_content(page_id).unwrap();
+            sheets.push((page.get(b"MediaBox").unwrap().clone(), content));
+        }
+        // the pages are placed upright, side by side
+        let width = sheets[1].0.as_array().unwrap()[2].as_float().unwrap();
+        assert_eq!(width, 2.0 * 50.0 + 2.0 * bookbinding::MARK_MARGIN);
+        assert_eq!(sheets[0], sheets[1]);
+    }
+}
@@ -0,0 +1,331 @@
+//! A page of instructions for folding and binding the printed sheets, with diagrams of the order of
+//! the folds, how the sheets nest in each signature, and how the signatures are stacked.
+
+use bookbinding::{Binding, Fold, SignaturePlan};
+use lopdf::{content::Operation, Document, Object};
+
+use crate::pdf;
+
+/// The most characters of text in each line of the instructions, which fits the width of the page
+/// inside its margins.
+const LINE_LENGTH: usize = 80;
+/// Width and height of each diagram.
+const DIAGRAM_SIZE: (f32, f32) = (200.0, 140.0);
+/// Height of the bottom of the diagrams above the bottom of the page.
+const DIAGRAM_BOTTOM: f32 = 200.0;
+/// Size of the labels in the diagrams.
+const LABEL_SIZE: f32 = 8.0;
+
+/// Creates the instructions for folding and binding the sheets of the plan, as a one-page document.
+pub fn document(plan: &SignaturePlan, binding: Binding) -> lopdf::Result<Document> {
+    let mut drawing = fold_diagram(72.0);
+    drawing.extend(nesting_diagram(plan, 612.0 - 72.0 - DIAGRAM_SIZE.0));
+    drawing.extend(stacking_diagram(plan));
+    pdf::drawing_document(&lines(plan, binding), pdf::LETTER, drawing)
+}
+
+/// The text of the instructions, wrapped to fit the page.
+fn lines(plan: &SignaturePlan, binding: Binding) -> Vec<String> {
+    let edge = if plan.writing_mode.is_rtl() {
+        "right"
+    } else {
+        "left"
+    };
+    let side = match plan.first_fold {
+        Fold::In => "outside (a mountain fold)",
+        Fold::Out => "inside (a valley fold)",
+    };
+    let mut steps = vec![format!(
+        "Print both sides of each sheet, in order. {}",
+        signature_summary(plan)
+    )];
+    steps.push(format!(
+        "Fold each sheet in half between its two pages, with the front of the sheet (the side \
+         printed first) on the {side}."
+    ));
+    steps.push(match binding {
+        Binding::Saddle => "Nest the folded sheets inside one another: sheet 1 on the outside and \
+                            the last sheet in the middle."
+            .to_owned(),
+        Binding::Perfect => "Nest the folded sheets of each signature inside one another: the \
+                             first sheet of the signature on the outside and its last sheet in \
+                             the middle."
+            .to_owned(),
+    });
+    if plan.signatures.len() > 1 {
+        steps.push(
+            "Stack the signatures in order, with signature 1 at the front of the book.".to_owned(),
+        );
+    }
+    if !plan.rtl_signatures.is_empty() {
+        let signatures = plan
+            .rtl_signatures
+            .iter()
+            .map(|signature| (signature + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        steps.push(format!(
+            "Signatures {signatures} read from right to left, but are folded and stacked the \
+             same way."
+        ));
+    }
+    steps.push(match binding {
+        Binding::Saddle => format!(
+            "Staple or sew through the middle of the fold, which is the {edge} edge of the book."
+        ),
+        Binding::Perfect => format!("Bind the signatures along their folds, on the {edge} edge."),
+    });
+
+    let mut lines = vec!["Folding and binding instructions".to_owned(), String::new()];
+    for (number, step) in steps.iter().enumerate() {
+        let mut wrapped = wrap(&format!("{}. {step}", number + 1), LINE_LENGTH).into_iter();
+        lines.extend(wrapped.next());
+        lines.extend(wrapped.map(|line| format!("    {line}")));
+    }
+    lines
+}
+
+/// Describes the number of sheets in each signature, grouping runs of signatures of the same size.
+fn signature_summary(plan: &SignaturePlan) -> String {
+    let mut runs = Vec::<(usize, usize)>::new();
+    for &sheets in &plan.signatures {
+        match runs.last_mut() {
+            Some((count, size)) if *size == sheets => *count += 1,
+            _ => runs.push((1, sheets)),
+        }
+    }
+    match runs[..] {
+        [] => "There are no sheets.".to_owned(),
+        [(1, sheets)] => format!("There is one signature of {sheets} sheets."),
+        _ => {
+            let runs = runs
+                .iter()
+                .map(|(count, sheets)| format!("{count} of {sheets} sheets"))
+                .collect::<Vec<_>>()
+                .join(", then ");
+            format!("There are {} signatures: {runs}.", plan.signatures.len())
+        }
+    }
+}
+
+/// Breaks text into lines of at most `length` characters at spaces. Words longer than a line are
+/// kept whole.
+fn wrap(text: &str, length: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > length {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// A flat sheet with a dashed line for the fold, between its two pages.
+fn fold_diagram(x: f32) -> Vec<Operation> {
+    let (width, height) = DIAGRAM_SIZE;
+    let y = DIAGRAM_BOTTOM;
+    let mut operations = caption("The fold", x, y + height);
+    operations.extend(stroke([rectangle(x, y, width, height)]));
+    operations.push(Operation::new("q", vec![]));
+    operations.push(Operation::new(
+        "d",
+        vec![Object::Array(vec![3.into(), 2.into()]), 0.into()],
+    ));
+    let line_x = x + width / 2.0;
+    operations.extend(stroke([vec![(line_x, y), (line_x, y + height)]]));
+    operations.push(Operation::new("Q", vec![]));
+    operations
+}
+
+/// The first signature seen from its head, with each sheet nested inside the one before and the
+/// spine on the binding edge.
+fn nesting_diagram(plan: &SignaturePlan, x: f32) -> Vec<Operation> {
+    let (width, height) = DIAGRAM_SIZE;
+    let y = DIAGRAM_BOTTOM;
+    let num_sheets = plan.signatures.first().copied().unwrap_or(0);
+    let mut operations = caption("Signature 1, seen from the head", x, y + height);
+    if num_sheets == 0 {
+        return operations;
+    }
+    let rtl = plan.writing_mode.is_rtl();
+    // measured from the spine towards the fore edge
+    let across = |distance: f32| {
+        if rtl {
+            x + width - distance
+        } else {
+            x + distance
+        }
+    };
+    let spacing = (height / 2.0 / (num_sheets + 1) as f32).min(6.0);
+    let paths = (0..num_sheets).map(|sheet| {
+        let inset = sheet as f32 * spacing;
+        let fore_edge = across(width - LABEL_SIZE * 3.0);
+        vec![
+            (fore_edge, y + height - inset),
+            (across(inset), y + height - inset),
+            (across(inset), y + inset),
+            (fore_edge, y + inset),
+        ]
+    });
+    operations.extend(stroke(paths));
+    let label_x = if rtl {
+        x
+    } else {
+        x + width - LABEL_SIZE * 3.0 + 2.0
+    };
+    operations.extend(pdf::text_operations(
+        &["1".to_owned()],
+        pdf::FONT,
+        label_x,
+        y + height - LABEL_SIZE / 2.0,
+        LABEL_SIZE,
+    ));
+    if num_sheets > 1 {
+        operations.extend(pdf::text_operations(
+            &[num_sheets.to_string()],
+            pdf::FONT,
+            label_x,
+            y + height - (num_sheets - 1) as f32 * spacing - LABEL_SIZE / 2.0,
+            LABEL_SIZE,
+        ));
+    }
+    operations.extend(pdf::text_operations(
+        &["spine".to_owned()],
+        pdf::FONT,
+        if rtl { x + width - 20.0 } else { x },
+        y - LABEL_SIZE * 1.5,
+        LABEL_SIZE,
+    ));
+    operations
+}
+
+/// A box for each signature, in the order they are stacked from the front of the book to the back.
+fn stacking_diagram(plan: &SignaturePlan) -> Vec<Operation> {
+    let (x, y, width, height) = (72.0, 72.0, 612.0 - 144.0, 40.0);
+    let mut operations = caption(
+        "Signatures, from the front of the book to the back",
+        x,
+        y + height,
+    );
+    let num_signatures = plan.signatures.len();
+    if num_signatures == 0 {
+        return operations;
+    }
+    let step = (width / num_signatures as f32).min(40.0);
+    let boxes = (0..num_signatures).map(|signature| {
+        let position = if plan.writing_mode.is_rtl() {
+            num_signatures - 1 - signature
+        } else {
+            signature
+        };
+        rectangle(x + position as f32 * step, y, step - 2.0, height)
+    });
+    operations.extend(stroke(boxes));
+    // label the boxes only where the numbers fit
+    if step >= 16.0 {
+        for signature in 0..num_signatures {
+            let position = if plan.writing_mode.is_rtl() {
+                num_signatures - 1 - signature
+            } else {
+                signature
+            };
+            operations.extend(pdf::text_operations(
+                &[(signature + 1).to_string()],
+                pdf::FONT,
+                x + position as f32 * step + 3.0,
+                y + height / 2.0,
+                LABEL_SIZE,
+            ));
+        }
+    }
+    operations
+}
+
+/// The corners of a rectangle, as a closed path.
+fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Vec<(f32, f32)> {
+    vec![
+        (x, y),
+        (x + width, y),
+        (x + width, y + height),
+        (x, y + height),
+        (x, y),
+    ]
+}
+
+/// Operations which stroke each path of straight lines through the given points.
+fn stroke(paths: impl IntoIterator<Item = Vec<(f32, f32)>>) -> Vec<Operation> {
+    let mut operations = vec![Operation::new("w", vec![0.5.into()])];
+    for path in paths {
+        for (i, (x, y)) in path.into_iter().enumerate() {
+            let operator = if i == 0 { "m" } else { "l" };
+            operations.push(Operation::new(operator, vec![x.into(), y.into()]));
+        }
+        operations.push(Operation::new("S", vec![]));
+    }
+    operations
+}
+
+/// A caption above a diagram whose top is at `y`.
+fn caption(text: &str, x: f32, y: f32) -> Vec<Operation> {
+    pdf::text_operations(&[text.to_owned()], pdf::FONT, x, y + LABEL_SIZE, 10.0)
+}
+
+#[cfg(test)]
+mod test {
+    use bookbinding::{plan_signatures, Binding, Sheets, SignatureParams, WritingMode};
+    use lopdf::Content;
+    use test_case::test_case;
+
+    #[test_case(Sheets(5), SignatureParams { binding: Binding::Saddle, ..Default::default() })]
+    #[test_case(Sheets(20), SignatureParams::default())]
+    #[test_case(Sheets(300), SignatureParams::default())]
+    #[test_case(Sheets(12), SignatureParams { writing_mode: WritingMode::VerticalRtl, ..Default::default() })]
+    fn document(num_sheets: Sheets, params: SignatureParams) {
+        let plan = plan_signatures(num_sheets, params);
+        let document = super::document(&plan, params.binding).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 1);
+        let content = document.get_page_content(pages[&1]).unwrap();
+        assert!(!Content::decode(&content).unwrap().operations.is_empty());
+    }
+
+    #[test]
+    fn lines() {
+        let params = SignatureParams {
+            signature_size: 4,
+            writing_mode: WritingMode::VerticalRtl,
+            ..Default::default()
+        };
+        let plan = plan_signatures(Sheets(13), params);
+        let lines = super::lines(&plan, params.binding);
+        assert!(lines
+            .iter()
+            .all(|line| line.len() <= super::LINE_LENGTH + 4));
+        let text = lines
+            .join(" ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(text.contains("There are 3 signatures: 2 of 4 sheets, then 1 of 5 sheets."));
+        assert!(
+            text.contains("with the front of the sheet (the side printed first) on the outside")
+        );
+        assert!(text.contains("on the right edge"));
+    }
+
+    #[test_case("a b c", 3 => vec!["a b", "c"])]
+    #[test_case("one two three", 7 => vec!["one two", "three"])]
+    #[test_case("overlong word", 4 => vec!["overlong", "word"])]
+    #[test_case("", 10 => Vec::<String>::new())]
+    fn wrap(text: &str, length: usize) -> Vec<String> {
+        super::wrap(text, length)
+    }
+}
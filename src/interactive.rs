@@ -0,0 +1,326 @@
+//! The `interactive` command, which asks questions about the book in plain language and turns the
+//! answers into the options to impose it with, for those who are new to signatures and remainders.
+//! Once the plan is confirmed, the options are imposed in the same way as if they had been given
+//! on the command line.
+
+use std::{
+    ffi::OsString,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use bookbinding::{
+    plan_signatures, Binding, Pages, RemainderStrategy, SignatureParams, WritingMode,
+    MAX_FOLDABLE_SHEETS,
+};
+use color_eyre::eyre::bail;
+use lopdf::Document;
+
+use crate::{analyze, length::POINTS_PER_MM};
+
+/// Asks for the input and the options to impose it with, and returns the arguments to run the
+/// imposition with, or `None` if the plan was not confirmed.
+pub fn run() -> color_eyre::Result<Option<Vec<OsString>>> {
+    let mut prompt = Prompt {
+        input: std::io::stdin().lock(),
+        output: std::io::stdout(),
+    };
+    let input = loop {
+        let answer = prompt.ask("Which PDF would you like to make into a book?", "")?;
+        let path = PathBuf::from(answer);
+        if path.is_file() {
+            break path;
+        }
+        prompt.say(&format!("There is no file at {}.", path.display()))?;
+    };
+    let document = Document::load(&input)?;
+    let num_pages = document.page_iter().size_hint().0;
+    match analyze::page_sizes(&document).first() {
+        Some(&((width, height), _)) => prompt.say(&format!(
+            "It has {num_pages} pages of {:.0} x {:.0} mm.",
+            width as f32 / POINTS_PER_MM,
+            height as f32 / POINTS_PER_MM,
+        ))?,
+        None => prompt.say(&format!("It has {num_pages} pages."))?,
+    }
+    let params = questions(&mut prompt, num_pages)?;
+    let output = prompt.ask(
+        "Where should the imposed PDF be written?",
+        &default_output(&input).to_string_lossy(),
+    )?;
+
+    let plan = plan_signatures(Pages(num_pages).sheets_needed(), params);
+    let options = options(params);
+    let signatures = match plan.signatures[..] {
+        [num_sheets] => format!("one section of {num_sheets} sheets"),
+        _ => format!(
+            "{} sections of {} sheets",
+            plan.signatures.len(),
+            plan.signatures
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    prompt.say(&format!(
+        "This makes {} sheets, folded into {signatures}.",
+        plan.num_sheets().0
+    ))?;
+    prompt.say(&format!(
+        "The same can be done with: bookbinding {} {output} {}",
+        input.display(),
+        options.join(" ")
+    ))?;
+    if !prompt.yes_or_no("Impose it now?", true)? {
+        return Ok(None);
+    }
+    let mut args = vec![OsString::from("bookbinding"), input.into(), output.into()];
+    args.extend(options.into_iter().map(OsString::from));
+    Ok(Some(args))
+}
+
+/// Asks how the book is bound and read, and works out the signature parameters from the answers.
+fn questions(
+    prompt: &mut Prompt<impl BufRead, impl Write>,
+    num_pages: usize,
+) -> color_eyre::Result<SignatureParams> {
+    let mut params = SignatureParams::default();
+    let num_sheets = Pages(num_pages).sheets_needed().0;
+    let booklet = prompt.choose(
+        "Will it be a single booklet, stapled or sewn through the middle of the fold (b), or \
+         several sections sewn or glued together at the spine (s)?",
+        &[("b", true), ("s", false)],
+        if num_sheets <= MAX_FOLDABLE_SHEETS {
+            "b"
+        } else {
+            "s"
+        },
+    )?;
+    if booklet {
+        params.binding = Binding::Saddle;
+        if num_sheets > MAX_FOLDABLE_SHEETS {
+            prompt.say(&format!(
+                "Note that {num_sheets} sheets may be too thick to fold as one booklet."
+            ))?;
+        }
+    } else {
+        let suggested = match analyze::suggest_signature_size(num_sheets).0 {
+            0 => num_sheets,
+            size => size,
+        };
+        params.signature_size = loop {
+            let answer = prompt.ask(
+                "How many sheets of paper can be folded together into each section? Thicker \
+                 paper folds fewer at once; 4 to 8 is usual.",
+                &suggested.to_string(),
+            )?;
+            match answer.parse() {
+                Ok(0) | Err(_) => prompt.say("Please answer with a number of sheets.")?,
+                Ok(size) => break size,
+            }
+        };
+        let remainder = num_sheets % params.signature_size;
+        if num_sheets > params.signature_size
+            && remainder != 0
+            && remainder < params.minimum_remainder_size
+        {
+            params.remainder = prompt.choose(
+                &format!(
+                    "The sheets do not divide evenly, which leaves {remainder} sheets over. \
+                     Should they be added to the last section (a), spread over the last few \
+                     sections one each (s), or kept as a short section of their own (k)?"
+                ),
+                &[
+                    ("a", RemainderStrategy::Overlong),
+                    ("s", RemainderStrategy::Distribute),
+                    ("k", RemainderStrategy::Short),
+                ],
+                "a",
+            )?;
+        }
+    }
+    if prompt.yes_or_no(
+        "Does the book read from right to left, bound on its right edge?",
+        false,
+    )? {
+        params.writing_mode = WritingMode::VerticalRtl;
+    }
+    Ok(params)
+}
+
+/// The command line options which give the same signature parameters as the answers. Options with
+/// their default value are left out.
+fn options(params: SignatureParams) -> Vec<String> {
+    let defaults = SignatureParams::default();
+    let mut options = Vec::new();
+    if params.binding == Binding::Saddle {
+        options.extend(["--binding".to_owned(), "saddle".to_owned()]);
+    } else if params.signature_size != defaults.signature_size {
+        options.extend([
+            "--signature-size".to_owned(),
+            params.signature_size.to_string(),
+        ]);
+    }
+    let remainder = match params.remainder {
+        RemainderStrategy::Overlong => None,
+        RemainderStrategy::Distribute => Some("distribute"),
+        RemainderStrategy::Short => Some("short"),
+    };
+    if let Some(remainder) = remainder {
+        options.extend(["--remainder".to_owned(), remainder.to_owned()]);
+    }
+    if params.writing_mode == WritingMode::VerticalRtl {
+        options.extend(["--writing-mode".to_owned(), "vertical-rtl".to_owned()]);
+    }
+    options
+}
+
+/// The output next to the input, with `-imposed` added to its name.
+fn default_output(input: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!("{stem}-imposed.pdf"))
+}
+
+/// Questions asked on one stream, with the answers read from another.
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    /// Writes a line of text.
+    fn say(&mut self, text: &str) -> color_eyre::Result<()> {
+        writeln!(self.output, "{text}")?;
+        Ok(())
+    }
+
+    /// Asks a question, and returns the answer, or the default for an empty answer. A question
+    /// with an empty default is asked until it is answered.
+    fn ask(&mut self, question: &str, default: &str) -> color_eyre::Result<String> {
+        loop {
+            if default.is_empty() {
+                write!(self.output, "{question} ")?;
+            } else {
+                write!(self.output, "{question} [{default}] ")?;
+            }
+            self.output.flush()?;
+            let mut answer = String::new();
+            if self.input.read_line(&mut answer)? == 0 {
+                bail!("no answer to: {question}");
+            }
+            match answer.trim() {
+                "" if default.is_empty() => continue,
+                "" => return Ok(default.to_owned()),
+                answer => return Ok(answer.to_owned()),
+            }
+        }
+    }
+
+    /// Asks a question with one of several answers, until one of them is given.
+    fn choose<T: Copy>(
+        &mut self,
+        question: &str,
+        choices: &[(&str, T)],
+        default: &str,
+    ) -> color_eyre::Result<T> {
+        loop {
+            let answer = self.ask(question, default)?;
+            match choices
+                .iter()
+                .find(|(choice, _)| choice.eq_ignore_ascii_case(&answer))
+            {
+                Some(&(_, value)) => return Ok(value),
+                None => {
+                    let choices = choices
+                        .iter()
+                        .map(|(choice, _)| *choice)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    self.say(&format!("Please answer with one of {choices}."))?;
+                }
+            }
+        }
+    }
+
+    /// Asks a question with a yes or no answer.
+    fn yes_or_no(&mut self, question: &str, default: bool) -> color_eyre::Result<bool> {
+        self.choose(
+            question,
+            &[("y", true), ("yes", true), ("n", false), ("no", false)],
+            if default { "y" } else { "n" },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use bookbinding::{Binding, RemainderStrategy, SignatureParams, WritingMode};
+    use test_case::test_case;
+
+    use super::Prompt;
+
+    /// Answers the questions for a book with this many pages, returning the options which would be
+    /// imposed with.
+    fn answer(num_pages: usize, answers: &str) -> Vec<String> {
+        let mut prompt = Prompt {
+            input: answers.as_bytes(),
+            output: Vec::new(),
+        };
+        super::options(super::questions(&mut prompt, num_pages).unwrap())
+    }
+
+    #[test_case(20, "\n\n" => vec!["--binding", "saddle"])]
+    #[test_case(20, "s\n\n\n" => vec!["--signature-size", "5"])]
+    #[test_case(100, "\n\n\n" => vec!["--signature-size", "5"])]
+    #[test_case(100, "s\n5\ny\n" => vec!["--signature-size", "5", "--writing-mode", "vertical-rtl"])]
+    #[test_case(100, "x\ns\nfive\n8\ns\n\n" => vec!["--signature-size", "8", "--remainder", "distribute"])]
+    fn questions(num_pages: usize, answers: &str) -> Vec<String> {
+        answer(num_pages, answers)
+    }
+
+    #[test]
+    fn no_answer() {
+        let mut prompt = Prompt {
+            input: "s\n".as_bytes(),
+            output: Vec::new(),
+        };
+        assert!(super::questions(&mut prompt, 100).is_err());
+    }
+
+    #[test]
+    fn options() {
+        let params = SignatureParams {
+            signature_size: 5,
+            remainder: RemainderStrategy::Short,
+            writing_mode: WritingMode::VerticalRtl,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::options(params),
+            [
+                "--signature-size",
+                "5",
+                "--remainder",
+                "short",
+                "--writing-mode",
+                "vertical-rtl"
+            ]
+        );
+        let params = SignatureParams {
+            binding: Binding::Saddle,
+            ..Default::default()
+        };
+        assert_eq!(super::options(params), ["--binding", "saddle"]);
+    }
+
+    #[test]
+    fn default_output() {
+        assert_eq!(
+            super::default_output(Path::new("books/novel.pdf")),
+            Path::new("books/novel-imposed.pdf")
+        );
+    }
+}
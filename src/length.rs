@@ -0,0 +1,112 @@
+//! Lengths given on the command line.
+
+use std::str::FromStr;
+
+/// The number of points in a millimetre.
+pub const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// A length, stored in PDF points. Parsed from a number with an optional unit of `pt`, `mm`, `cm`,
+/// or `in`, with no unit meaning points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Length(pub f32);
+
+impl Length {
+    /// The number of points in each unit.
+    const UNITS: [(&'static str, f32); 4] = [
+        ("pt", 1.0),
+        ("mm", POINTS_PER_MM),
+        ("cm", 10.0 * POINTS_PER_MM),
+        ("in", 72.0),
+    ];
+
+    /// The length in points.
+    pub fn points(self) -> f32 {
+        self.0
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (number, scale) = Length::UNITS
+            .iter()
+            .find_map(|&(unit, scale)| Some((value.strip_suffix(unit)?, scale)))
+            .unwrap_or((value, 1.0));
+        let number = number
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| format!("invalid length {value:?}, expected a number and a unit"))?;
+        if !number.is_finite() || number < 0.0 {
+            return Err(format!("length {value:?} must not be negative"));
+        }
+        Ok(Length(number * scale))
+    }
+}
+
+/// A width and height, parsed from two lengths separated by `x`, such as `6inx9in`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Size {
+    /// The width and height in points.
+    pub fn points(self) -> (f32, f32) {
+        (self.width.points(), self.height.points())
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (width, height) = value
+            .split_once('x')
+            .ok_or_else(|| format!("invalid size {value:?}, expected <width>x<height>"))?;
+        let size = Size {
+            width: width.parse()?,
+            height: height.parse()?,
+        };
+        if size.width.points() == 0.0 || size.height.points() == 0.0 {
+            return Err(format!("size {value:?} must not be empty"));
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{Length, Size};
+
+    #[test_case("12" => Some(12.0))]
+    #[test_case("12pt" => Some(12.0))]
+    #[test_case("1in" => Some(72.0))]
+    #[test_case("0.5 in" => Some(36.0))]
+    #[test_case("25.4mm" => Some(72.0))]
+    #[test_case("2.54cm" => Some(72.0))]
+    #[test_case("-3mm" => None)]
+    #[test_case("3ft" => None)]
+    fn parse(value: &str) -> Option<f32> {
+        // round to avoid floating point error in the comparison
+        let points = value.parse::<Length>().ok()?.points();
+        Some((points * 1000.0).round() / 1000.0)
+    }
+
+    #[test_case("6inx9in" => Some((432.0, 648.0)))]
+    #[test_case("148mm x 210mm" => Some((419.528, 595.276)))]
+    #[test_case("300x400" => Some((300.0, 400.0)))]
+    #[test_case("6in" => None)]
+    #[test_case("0x9in" => None)]
+    fn parse_size(value: &str) -> Option<(f32, f32)> {
+        let (width, height) = value.parse::<Size>().ok()?.points();
+        Some((
+            (width * 1000.0).round() / 1000.0,
+            (height * 1000.0).round() / 1000.0,
+        ))
+    }
+}
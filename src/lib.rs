@@ -0,0 +1,1067 @@
+//! Planning and arrangement of pages into signatures for bookbinding.
+
+mod marks;
+pub mod page;
+mod sheet;
+#[doc(hidden)]
+pub mod testing;
+mod units;
+
+pub use marks::{edge_marks, mark_operations, Marks, MARK_MARGIN, MARK_OFFSET, MARK_WIDTH};
+pub use sheet::{
+    for_each_sheet, nup_document, nup_slots, sheet_document, NupLayout, SheetSettings,
+};
+pub use units::{Leaves, Pages, Sheets};
+
+#[derive(Clone, Copy, Debug, clap::Args)]
+pub struct SignatureParams {
+    /// Preferred number of sheets per signature. Each sheet is folded into 2 leaves, with 4 pages.
+    /// 0 puts all of the sheets into a single signature, the same as `--binding saddle`.
+    #[arg(short, long, default_value_t = 6)]
+    pub signature_size: usize,
+    /// Minimum number of sheets in the last signature. If the remainder would be less than this
+    /// amount, it is handled according to `--remainder`.
+    #[arg(short, long, default_value_t = 4)]
+    pub minimum_remainder_size: usize,
+    /// How to handle a remainder shorter than `--minimum-remainder-size`.
+    #[arg(long, value_enum, default_value_t = RemainderStrategy::Overlong)]
+    pub remainder: RemainderStrategy,
+    /// Maximum number of sheets in any signature, which takes precedence over the other signature
+    /// size parameters. A larger `--signature-size` (or 0) is reduced to this, and if handling the
+    /// remainder would make a signature longer than this, the last signature and the remainder are
+    /// split into two signatures instead, even if one is shorter than `--minimum-remainder-size`.
+    /// Ignored by `--binding saddle`.
+    #[arg(long, value_parser = parse_at_least_one)]
+    pub max_signature_sheets: Option<usize>,
+    /// Exact number of signatures to divide the sheets into, as evenly as possible, with any
+    /// longer signatures at the end. This takes the place of the other signature size parameters.
+    /// Ignored by `--binding saddle`.
+    #[arg(long, value_parser = parse_at_least_one, conflicts_with = "max_signature_sheets")]
+    pub num_signatures: Option<usize>,
+    /// How the signatures are bound together.
+    #[arg(long, value_enum, default_value_t = Binding::Perfect)]
+    pub binding: Binding,
+    /// The direction the text is written in, which determines which edge the book is bound on.
+    #[arg(long, value_enum, default_value_t = WritingMode::HorizontalLtr)]
+    pub writing_mode: WritingMode,
+    /// Which way the first fold of each sheet goes, relative to the back of the sheet.
+    #[arg(long, value_enum, default_value_t = Fold::In)]
+    pub first_fold: Fold,
+}
+
+impl Default for SignatureParams {
+    fn default() -> Self {
+        SignatureParams {
+            signature_size: 6,
+            minimum_remainder_size: 4,
+            remainder: RemainderStrategy::Overlong,
+            max_signature_sheets: None,
+            num_signatures: None,
+            binding: Binding::Perfect,
+            writing_mode: WritingMode::HorizontalLtr,
+            first_fold: Fold::In,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RemainderStrategy {
+    /// Merge the remainder into the last signature, making it extra-long.
+    Overlong,
+    /// Spread the remainder across the trailing signatures one sheet at a time, starting with the
+    /// last signature and working backwards, so that the last few signatures are each one sheet
+    /// longer.
+    Distribute,
+    /// Keep the remainder as a short last signature.
+    Short,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Binding {
+    /// Each signature is folded on its own, and the folded signatures are stacked in order, as for
+    /// perfect binding or sewing. Pages flow from the end of one signature to the start of the
+    /// next, rather than nesting.
+    Perfect,
+    /// All of the sheets are nested inside one another and folded as a single signature, as for
+    /// saddle stitching. The signature size parameters are ignored.
+    Saddle,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum WritingMode {
+    /// Horizontal lines read from left to right, with the book bound on the left edge.
+    HorizontalLtr,
+    /// Vertical lines read from top to bottom, with the lines and pages progressing from right to
+    /// left, as in traditionally bound Chinese, Japanese, and Korean books. The book is bound on
+    /// the right edge, so the pages on each side of a sheet are swapped relative to a western
+    /// book. Pages are not rotated, since vertical text is still set on upright pages.
+    VerticalRtl,
+}
+
+impl WritingMode {
+    /// Whether the pages progress from right to left, with the book bound on its right edge.
+    pub fn is_rtl(self) -> bool {
+        self == WritingMode::VerticalRtl
+    }
+
+    /// Maps the index of an output page in a left-bound book to its index in a book with this
+    /// writing mode.
+    pub fn position(self, dest: usize) -> usize {
+        if self.is_rtl() {
+            // swap the left and right pages of each side
+            dest ^ 1
+        } else {
+            dest
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fold {
+    /// The back of each sheet is folded inwards, so that it is on the inside of the fold (a
+    /// valley fold, seen from the back). The front of the outermost sheet is the outside of the
+    /// signature.
+    In,
+    /// The back of each sheet is folded outwards (a mountain fold, seen from the back). This
+    /// swaps the front and back of every sheet, so that the back of the outermost sheet is the
+    /// outside of the signature.
+    Out,
+}
+
+/// Which sides of the sheets to print, when printing both sides of each sheet by hand in two
+/// passes. Output pages are printed two to a side, so each pass selects alternating pairs of pages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Pass {
+    /// The first pass, printing the front of each sheet, in order.
+    Odd,
+    /// The second pass, printing the back of each sheet. The printed stack from the first pass is
+    /// turned over and fed back in, which puts the last sheet on top, so the backs are printed in
+    /// reverse order.
+    Even,
+}
+
+/// Selects the indices of the output pages to print in the given pass, in printing order.
+pub fn pass_pages(num_pages: usize, pass: Pass) -> Vec<usize> {
+    let sides = (0..num_pages.div_ceil(2)).map(|side| [side * 2, side * 2 + 1]);
+    let pass_sides = match pass {
+        Pass::Odd => sides.step_by(2).collect::<Vec<_>>(),
+        Pass::Even => sides.skip(1).step_by(2).rev().collect(),
+    };
+    pass_sides
+        .into_iter()
+        .flatten()
+        .filter(|&page| page < num_pages)
+        .collect()
+}
+
+/// The output pages with a blank side after each side of the sheets, for printing a duplex
+/// imposition on one side of the paper. Each pair of pages (a side) is followed by two blank pages,
+/// given as `None`.
+pub fn blank_back_pages(num_pages: usize) -> Vec<Option<usize>> {
+    (0..num_pages)
+        .step_by(2)
+        .flat_map(|side| [Some(side), Some(side + 1), None, None])
+        .map(|page| page.filter(|&page| page < num_pages))
+        .collect()
+}
+
+/// Where an output page goes when the back of each sheet is turned upside down, for duplex
+/// printers which turn the sheet over along its short edge ("tumble"), and whether the page must
+/// be rotated by 180°.
+///
+/// Printing the back of a sheet upside down is the same as rotating the whole side by 180°, which
+/// swaps its left and right pages and turns each of them upside down. So the back pages (the third
+/// and fourth output pages of each sheet) swap places and are rotated, which cancels out the
+/// printer's turn, and the front pages are unchanged.
+pub fn tumble_position(page: usize) -> (usize, bool) {
+    if page & 2 == 0 {
+        (page, false)
+    } else {
+        (page ^ 1, true)
+    }
+}
+
+/// The page printed in each slot of a French fold, where the four pages of a card are printed on
+/// one side of a sheet in a 2x2 grid, and whether it is printed upside down. The slots fill the
+/// rows from left to right, starting with the top row, and each group of four slots is a sheet.
+///
+/// The sheet is folded in half across its width with the printed side out, which puts the top row
+/// on the back, upside down and with its left and right swapped. It is then folded in half down
+/// the middle, with the spine on the left (or on the right for [`WritingMode::VerticalRtl`]). So
+/// the bottom row holds the front and back covers, and the top row, printed upside down, holds the
+/// inside spread.
+pub fn french_fold_source(slot: usize, writing_mode: WritingMode) -> (usize, bool) {
+    let card = slot - slot % 4;
+    let (page, upside_down) = match (slot % 4, writing_mode.is_rtl()) {
+        // the inside spread, seen from the back of the sheet
+        (0, false) | (1, true) => (2, true),
+        (1, false) | (0, true) => (1, true),
+        // the back and front covers
+        (2, false) | (3, true) => (3, false),
+        _ => (0, false),
+    };
+    (card + page, upside_down)
+}
+
+/// Parses a number of sheets or signatures which must be at least 1, for `--max-signature-sheets`
+/// and `--num-signatures`.
+fn parse_at_least_one(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("must be at least 1".to_owned()),
+        Ok(num_sheets) => Ok(num_sheets),
+        Err(err) => Err(format!("{err}")),
+    }
+}
+
+/// Plans how many sheets go in each signature of a book with `num_sheets` sheets, using the given
+/// parameters.
+///
+/// The constraints take precedence in this order: the binding, then `num_signatures`, then
+/// `max_signature_sheets`, then `signature_size`, and finally `minimum_remainder_size`. If there
+/// are fewer sheets than `num_signatures`, each sheet is a signature of its own; this should be
+/// checked beforehand with [`check_num_signatures`].
+pub fn plan_signatures(Sheets(num_sheets): Sheets, params: SignatureParams) -> SignaturePlan {
+    let signature_size = match (params.signature_size, params.max_signature_sheets) {
+        (0, Some(max_sheets)) => max_sheets,
+        (size, Some(max_sheets)) => size.min(max_sheets),
+        (size, None) => size,
+    };
+    // a signature size of 0 means a single signature, however many sheets there are
+    if params.binding == Binding::Saddle || signature_size == 0 {
+        return SignaturePlan {
+            signatures: vec![num_sheets],
+            writing_mode: params.writing_mode,
+            rtl_signatures: Vec::new(),
+            first_fold: params.first_fold,
+        };
+    }
+    if let Some(num_signatures) = params.num_signatures {
+        let num_signatures = num_signatures.clamp(1, num_sheets.max(1));
+        let (size, longer) = (num_sheets / num_signatures, num_sheets % num_signatures);
+        // the longer signatures go at the end, as with `RemainderStrategy::Distribute`
+        let signatures = (0..num_signatures)
+            .map(|index| size + usize::from(index >= num_signatures - longer))
+            .collect();
+        return SignaturePlan {
+            signatures,
+            writing_mode: params.writing_mode,
+            rtl_signatures: Vec::new(),
+            first_fold: params.first_fold,
+        };
+    }
+    let mut signatures = vec![signature_size; num_sheets / signature_size];
+    place_remainder(&mut signatures, num_sheets % signature_size, &params);
+    SignaturePlan {
+        signatures,
+        writing_mode: params.writing_mode,
+        rtl_signatures: Vec::new(),
+        first_fold: params.first_fold,
+    }
+}
+
+/// Adds the `remainder` sheets left over after `signatures` to the plan: as a signature of their
+/// own, unless they are fewer than `minimum_remainder_size`, in which case they are handled
+/// according to `remainder`. If that would make a signature longer than `max_signature_sheets`,
+/// the last signature and the remainder are split evenly instead.
+fn place_remainder(signatures: &mut Vec<usize>, remainder: usize, params: &SignatureParams) {
+    if remainder == 0 {
+        return;
+    }
+    if remainder >= params.minimum_remainder_size || signatures.is_empty() {
+        signatures.push(remainder);
+        return;
+    }
+    // the remainder is too short to be its own signature
+    let num_signatures = signatures.len();
+    let mut merged = signatures.clone();
+    match params.remainder {
+        RemainderStrategy::Overlong => *merged.last_mut().unwrap() += remainder,
+        RemainderStrategy::Distribute => {
+            for i in 0..remainder {
+                merged[num_signatures - 1 - i % num_signatures] += 1;
+            }
+        }
+        RemainderStrategy::Short => {
+            signatures.push(remainder);
+            return;
+        }
+    }
+    let fits = |num_sheets: usize| {
+        params
+            .max_signature_sheets
+            .is_none_or(|max_sheets| num_sheets <= max_sheets)
+    };
+    if merged.iter().all(|&num_sheets| fits(num_sheets)) {
+        *signatures = merged;
+    } else {
+        // too long to merge, so split the last signature and the remainder evenly instead
+        let merged = signatures.pop().unwrap() + remainder;
+        signatures.extend([merged - merged / 2, merged / 2]);
+    }
+}
+
+/// Plans the signatures of a book with `num_sheets` sheets with sizes following `pattern`, which
+/// is repeated until all of the sheets are placed, such as `[3, 4, 5, 4, 3]` for signatures of
+/// varying thickness. Sizes longer than `max_signature_sheets` are shortened to it. Sheets left
+/// over at the end are handled as with [`plan_signatures`], and a saddle-stitched book is a single
+/// signature, as there. The signature size and number of signatures are ignored.
+///
+/// Returns an error if `pattern` is empty or any size in it is 0.
+pub fn plan_pattern(
+    Sheets(num_sheets): Sheets,
+    pattern: &[usize],
+    params: SignatureParams,
+) -> Result<SignaturePlan, String> {
+    if pattern.is_empty() {
+        return Err("the pattern of signature sizes is empty".into());
+    }
+    if pattern.contains(&0) {
+        return Err("each signature in the pattern must have at least one sheet".into());
+    }
+    let mut signatures = Vec::new();
+    let mut remainder = num_sheets;
+    if params.binding == Binding::Saddle {
+        signatures.push(num_sheets);
+        remainder = 0;
+    }
+    let sizes = pattern
+        .iter()
+        .map(|&size| match params.max_signature_sheets {
+            Some(max_sheets) => size.min(max_sheets),
+            None => size,
+        });
+    for size in sizes.cycle() {
+        if remainder < size {
+            break;
+        }
+        signatures.push(size);
+        remainder -= size;
+    }
+    place_remainder(&mut signatures, remainder, &params);
+    Ok(SignaturePlan {
+        signatures,
+        writing_mode: params.writing_mode,
+        rtl_signatures: Vec::new(),
+        first_fold: params.first_fold,
+    })
+}
+
+/// Checks that a book with `num_sheets` sheets can be divided into the number of signatures
+/// requested by the parameters, if any. Each signature needs at least one sheet.
+pub fn check_num_signatures(
+    Sheets(num_sheets): Sheets,
+    params: &SignatureParams,
+) -> Result<(), String> {
+    match params.num_signatures {
+        Some(num_signatures)
+            if num_signatures > num_sheets && params.binding != Binding::Saddle =>
+        {
+            Err(format!(
+                "cannot divide {num_sheets} sheets into {num_signatures} signatures, since each \
+                 signature needs at least one sheet"
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Merges each signature with fewer than `min_sheets` sheets into the smaller of its neighbours,
+/// as long as the merged signature has at most `max_sheets` sheets. A signature which is still too
+/// small after merging is merged again, and one which cannot be merged without exceeding
+/// `max_sheets` is left as it is.
+pub fn merge_small_signatures(
+    plan: &SignaturePlan,
+    min_sheets: usize,
+    max_sheets: usize,
+) -> SignaturePlan {
+    let mut signatures = plan.signatures.clone();
+    let mut index = 0;
+    while index < signatures.len() {
+        let num_sheets = signatures[index];
+        let neighbour = [index.checked_sub(1), Some(index + 1)]
+            .into_iter()
+            .flatten()
+            .filter(|&other| {
+                signatures
+                    .get(other)
+                    .is_some_and(|&other| num_sheets + other <= max_sheets)
+            })
+            .min_by_key(|&other| signatures[other]);
+        match neighbour {
+            Some(other) if num_sheets < min_sheets => {
+                signatures.remove(index);
+                // check the merged signature again
+                index = if other < index { other } else { index };
+                signatures[index] += num_sheets;
+            }
+            _ => index += 1,
+        }
+    }
+    SignaturePlan {
+        signatures,
+        ..plan.clone()
+    }
+}
+
+/// The number of sheets in each signature of a book, in order, and how the pages are placed on
+/// each sheet.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SignaturePlan {
+    pub signatures: Vec<usize>,
+    pub writing_mode: WritingMode,
+    /// The indices of the signatures which read from right to left, as with
+    /// [`WritingMode::VerticalRtl`], in a book which otherwise reads from left to right.
+    #[serde(default)]
+    pub rtl_signatures: Vec<usize>,
+    pub first_fold: Fold,
+}
+
+impl SignaturePlan {
+    /// The total number of sheets in the book.
+    pub fn num_sheets(&self) -> Sheets {
+        self.signatures.iter().copied().map(Sheets).sum()
+    }
+
+    /// The total number of leaves in the book.
+    pub fn num_leaves(&self) -> Leaves {
+        self.num_sheets().leaves()
+    }
+
+    /// The total number of pages in the book, including any padding.
+    pub fn num_pages(&self) -> Pages {
+        self.num_sheets().pages()
+    }
+
+    /// Where the output page with the given index is printed.
+    pub fn slot_position(&self, dest: usize) -> SlotPosition {
+        let sheet = dest / 4;
+        let mut signature_end = 0;
+        let signature = self
+            .signatures
+            .iter()
+            .position(|&num_sheets| {
+                signature_end += num_sheets;
+                sheet < signature_end
+            })
+            .unwrap_or(self.signatures.len());
+        SlotPosition {
+            signature,
+            sheet,
+            front: dest % 4 < 2,
+            left: dest.is_multiple_of(2),
+        }
+    }
+
+    /// The writing mode of the signature with the given index. Every signature of a book written
+    /// from right to left reads from right to left, and otherwise only those in
+    /// [`rtl_signatures`](Self::rtl_signatures) do.
+    pub fn signature_writing_mode(&self, signature: usize) -> WritingMode {
+        if self.rtl_signatures.contains(&signature) {
+            WritingMode::VerticalRtl
+        } else {
+            self.writing_mode
+        }
+    }
+
+    /// Maps the index of an output page of the signature with the given index, in a left-bound
+    /// book with the back of each sheet folded inwards, to its index with the signature's writing
+    /// mode and this plan's fold.
+    pub fn position(&self, signature: usize, dest: usize) -> usize {
+        let dest = self.signature_writing_mode(signature).position(dest);
+        match self.first_fold {
+            Fold::In => dest,
+            // swap the front and back of each sheet
+            Fold::Out => dest ^ 2,
+        }
+    }
+
+    /// The signatures with more than `max_sheets` sheets, as pairs of the index of the signature
+    /// and its number of sheets.
+    pub fn thick_signatures(&self, max_sheets: usize) -> Vec<(usize, usize)> {
+        self.signatures
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, num_sheets)| num_sheets > max_sheets)
+            .collect()
+    }
+
+    /// The signatures whose number of pages is not a power of two (4, 8, 16, 32, and so on), which
+    /// many folding machines can't fold, as pairs of the index of the signature and its number of
+    /// sheets.
+    pub fn non_power_of_two_signatures(&self) -> Vec<(usize, usize)> {
+        self.signatures
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, num_sheets)| !Sheets(num_sheets).pages().0.is_power_of_two())
+            .collect()
+    }
+}
+
+/// Where an output page is printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotPosition {
+    /// The index of the signature.
+    pub signature: usize,
+    /// The index of the sheet, counting from the start of the book.
+    pub sheet: usize,
+    /// Whether the page is on the front of the sheet, rather than the back.
+    pub front: bool,
+    /// Whether the page is on the left of its side of the sheet, rather than the right.
+    pub left: bool,
+}
+
+/// The width of the spine of a book with `num_leaves` leaves of paper `caliper` thick, plus
+/// `extra` for the cover. The spine is one caliper wide per leaf. The result is in the same units
+/// as `caliper` and `extra`.
+pub fn spine_width(Leaves(num_leaves): Leaves, caliper: f64, extra: f64) -> f64 {
+    num_leaves as f64 * caliper + extra
+}
+
+/// The default maximum number of sheets of typical paper which can be folded together into one
+/// signature.
+pub const MAX_FOLDABLE_SHEETS: usize = 8;
+
+/// The index of the output page printed on the other side of the same leaf as the given output
+/// page. Once the sheet is folded, the two pages are the recto and verso of the leaf, so they
+/// should be consecutive in reading order.
+pub fn leaf_partner(dest: usize) -> usize {
+    // the left page of the front of a sheet backs onto the right page of the back, and vice versa
+    dest ^ 3
+}
+
+/// Splits a permutation into its cycles, so that it can be applied in place by moving one item at
+/// a time. `sources[dest]` is the index of the item which goes to `dest`, and must be a
+/// permutation of `0..sources.len()`.
+///
+/// Each cycle is a list of destinations, where each takes the item from the next, and the last
+/// takes the item from the first. Items which stay where they are form cycles of one.
+pub fn permutation_cycles(sources: &[usize]) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; sources.len()];
+    let mut cycles = Vec::new();
+    for start in 0..sources.len() {
+        if visited[start] {
+            continue;
+        }
+        let mut cycle = Vec::new();
+        let mut dest = start;
+        while !visited[dest] {
+            visited[dest] = true;
+            cycle.push(dest);
+            dest = sources[dest];
+        }
+        cycles.push(cycle);
+    }
+    cycles
+}
+
+/// Arrange the pages according to the given plan, using the provided function to update the
+/// pages.
+/// The first argument to the function is the page index in the input document, and the second
+/// argument is the page index in the output document.
+pub fn arrange_pages_with(plan: &SignaturePlan, mut with: impl FnMut(usize, usize)) {
+    let mut start = 0;
+    for (signature, &num_sheets) in plan.signatures.iter().enumerate() {
+        signature_with(start, num_sheets, |src, dest| {
+            with(src, plan.position(signature, dest))
+        });
+        start += Sheets(num_sheets).pages().0;
+    }
+}
+
+/// Arrange the pages for a given signature using the given parameters, using the provided function
+/// to update the pages.
+/// The first argument to the function is the page index in the input document, and the second
+/// argument is the page index in the output document.
+pub fn signature_with(start: usize, num_sheets: usize, mut with: impl FnMut(usize, usize)) {
+    let num_pages = Sheets(num_sheets).pages().0;
+    for dest in 0..num_pages {
+        with(start + booklet_source(num_pages, dest), start + dest);
+    }
+}
+
+/// The index in reading order of the page printed at output index `dest`, for a saddle-stitched
+/// booklet of `num_pages` pages made of nested bifolia. Every signature is imposed this way.
+///
+/// Each bifolium is printed as four output pages: the left and right pages of its front, then
+/// the left and right pages of its back. The outermost bifolium comes first, carrying the first
+/// two and last two pages of the booklet.
+///
+/// `num_pages` must be a multiple of 4.
+pub fn booklet_source(num_pages: usize, dest: usize) -> usize {
+    debug_assert!(num_pages.is_multiple_of(4) && dest < num_pages);
+    // the first of the two consecutive pages on the inside of this bifolium's leaves
+    let s = dest / 4 * 2;
+    match dest % 4 {
+        0 => num_pages - (s + 1),
+        1 => s,
+        2 => s + 1,
+        _ => num_pages - (s + 2),
+    }
+}
+
+/// Reorders the pages of a saddle-stitched booklet from reading order into the order they are
+/// printed, two to a side, on each side of the nested bifolia in turn. See [`booklet_source`].
+///
+/// ```
+/// # use bookbinding::printer_order;
+/// let pages = (1..=16).collect::<Vec<_>>();
+/// assert_eq!(
+///     printer_order(&pages),
+///     [16, 1, 2, 15, 14, 3, 4, 13, 12, 5, 6, 11, 10, 7, 8, 9],
+/// );
+/// ```
+///
+/// # Panics
+///
+/// If the number of pages is not a multiple of 4.
+pub fn printer_order<T: Clone>(pages: &[T]) -> Vec<T> {
+    assert!(
+        pages.len().is_multiple_of(4),
+        "a booklet must have a multiple of 4 pages, not {}",
+        pages.len()
+    );
+    (0..pages.len())
+        .map(|dest| pages[booklet_source(pages.len(), dest)].clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use test_case::test_case;
+
+    use super::{
+        Binding, Fold, Leaves, Pages, Pass, RemainderStrategy, Sheets, SignatureParams,
+        SignaturePlan, WritingMode,
+    };
+
+    #[test_case(26, 5)]
+    #[test_case(36, 5)]
+    #[test_case(40, 5)]
+    #[test_case(40, 6)]
+    fn arrange_pages(num_pages: usize, signature_size: usize) {
+        let params = SignatureParams {
+            signature_size,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Pages(num_pages).sheets_needed(), params);
+        let mut pages = HashSet::new();
+        let mut duplicates = Vec::new();
+        super::arrange_pages_with(&plan, |src, _dest| {
+            if !pages.insert(src) {
+                duplicates.push(src);
+            }
+        });
+        let num_pages_rounded = num_pages.next_multiple_of(4);
+        assert_eq!(pages.len(), num_pages_rounded);
+        assert_eq!(duplicates, []);
+    }
+
+    /// Moving the pages one cycle at a time gives the same order as copying them all at once.
+    #[test_case(4)]
+    #[test_case(24)]
+    #[test_case(100)]
+    fn permutation_cycles(num_pages: usize) {
+        let plan = super::plan_signatures(Pages(num_pages).sheets_needed(), Default::default());
+        let mut sources = vec![0; plan.num_pages().0];
+        super::arrange_pages_with(&plan, |src, dest| sources[dest] = src);
+        let copied = sources.iter().map(|&src| src + 1).collect::<Vec<_>>();
+        let mut pages = (1..=sources.len()).collect::<Vec<_>>();
+        for cycle in super::permutation_cycles(&sources) {
+            let first = pages[cycle[0]];
+            for pair in cycle.windows(2) {
+                pages[pair[0]] = pages[pair[1]];
+            }
+            pages[cycle[cycle.len() - 1]] = first;
+        }
+        assert_eq!(pages, copied);
+    }
+
+    #[test_case(WritingMode::HorizontalLtr => vec![(2, true), (1, true), (3, false), (0, false), (6, true)])]
+    #[test_case(WritingMode::VerticalRtl => vec![(1, true), (2, true), (0, false), (3, false), (5, true)])]
+    fn french_fold_source(writing_mode: WritingMode) -> Vec<(usize, bool)> {
+        (0..5)
+            .map(|slot| super::french_fold_source(slot, writing_mode))
+            .collect()
+    }
+
+    #[test_case(RemainderStrategy::Overlong => vec![6, 6, 6, 6, 6, 6, 6, 8])]
+    #[test_case(RemainderStrategy::Distribute => vec![6, 6, 6, 6, 6, 6, 7, 7])]
+    #[test_case(RemainderStrategy::Short => vec![6, 6, 6, 6, 6, 6, 6, 6, 2])]
+    fn remainder(remainder: RemainderStrategy) -> Vec<usize> {
+        let params = SignatureParams {
+            remainder,
+            ..Default::default()
+        };
+        super::plan_signatures(Sheets(50), params).signatures
+    }
+
+    #[test_case(RemainderStrategy::Overlong, 6, 9 => vec![6, 6, 6, 9])]
+    #[test_case(RemainderStrategy::Overlong, 6, 8 => vec![6, 6, 6, 5, 4])]
+    #[test_case(RemainderStrategy::Distribute, 6, 7 => vec![6, 7, 7, 7])]
+    #[test_case(RemainderStrategy::Distribute, 6, 6 => vec![6, 6, 6, 5, 4])]
+    #[test_case(RemainderStrategy::Short, 6, 6 => vec![6, 6, 6, 6, 3])]
+    #[test_case(RemainderStrategy::Overlong, 8, 6 => vec![6, 6, 6, 5, 4])]
+    #[test_case(RemainderStrategy::Overlong, 0, 5 => vec![5, 5, 5, 5, 4, 3])]
+    fn max_signature_sheets(
+        remainder: RemainderStrategy,
+        signature_size: usize,
+        max_sheets: usize,
+    ) -> Vec<usize> {
+        let params = SignatureParams {
+            signature_size,
+            minimum_remainder_size: 4,
+            remainder,
+            max_signature_sheets: Some(max_sheets),
+            ..Default::default()
+        };
+        super::plan_signatures(Sheets(27), params).signatures
+    }
+
+    #[test_case("8" => Some(8))]
+    #[test_case("0" => None)]
+    #[test_case("-1" => None)]
+    fn parse_at_least_one(value: &str) -> Option<usize> {
+        super::parse_at_least_one(value).ok()
+    }
+
+    #[test_case(50, 4 => Some(vec![12, 12, 13, 13]))]
+    #[test_case(48, 4 => Some(vec![12, 12, 12, 12]))]
+    #[test_case(5, 5 => Some(vec![1, 1, 1, 1, 1]))]
+    #[test_case(7, 1 => Some(vec![7]))]
+    #[test_case(3, 4 => None)]
+    fn num_signatures(num_sheets: usize, num_signatures: usize) -> Option<Vec<usize>> {
+        let params = SignatureParams {
+            num_signatures: Some(num_signatures),
+            ..Default::default()
+        };
+        super::check_num_signatures(Sheets(num_sheets), &params).ok()?;
+        let plan = super::plan_signatures(Sheets(num_sheets), params);
+        assert_eq!(plan.num_sheets(), Sheets(num_sheets));
+        Some(plan.signatures)
+    }
+
+    #[test_case(WritingMode::HorizontalLtr => vec![7, 0, 1, 6, 5, 2, 3, 4])]
+    #[test_case(WritingMode::VerticalRtl => vec![0, 7, 6, 1, 2, 5, 4, 3])]
+    fn writing_mode(writing_mode: WritingMode) -> Vec<usize> {
+        let params = SignatureParams {
+            writing_mode,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Sheets(2), params);
+        let mut pages = vec![0; 8];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        pages
+    }
+
+    #[test_case(33, &[3, 4, 5, 4, 3], RemainderStrategy::Overlong => vec![3, 4, 5, 4, 3, 3, 4, 7])]
+    #[test_case(30, &[3, 4, 5, 4, 3], RemainderStrategy::Overlong => vec![3, 4, 5, 4, 3, 3, 4, 4])]
+    #[test_case(33, &[3, 4, 5, 4, 3], RemainderStrategy::Distribute => vec![3, 4, 5, 4, 3, 3, 5, 6])]
+    #[test_case(33, &[3, 4, 5, 4, 3], RemainderStrategy::Short => vec![3, 4, 5, 4, 3, 3, 4, 5, 2])]
+    #[test_case(12, &[4], RemainderStrategy::Overlong => vec![4, 4, 4])]
+    #[test_case(2, &[3, 4], RemainderStrategy::Overlong => vec![2])]
+    fn plan_pattern(
+        num_sheets: usize,
+        pattern: &[usize],
+        remainder: RemainderStrategy,
+    ) -> Vec<usize> {
+        let params = SignatureParams {
+            remainder,
+            ..Default::default()
+        };
+        let plan = super::plan_pattern(Sheets(num_sheets), pattern, params).unwrap();
+        assert_eq!(plan.num_sheets(), Sheets(num_sheets));
+        plan.signatures
+    }
+
+    #[test_case(SignatureParams { binding: Binding::Saddle, ..Default::default() } => Ok(vec![33]))]
+    #[test_case(SignatureParams { max_signature_sheets: Some(4), ..Default::default() } => Ok(vec![3, 4, 4, 4, 3, 3, 4, 4, 4]))]
+    fn plan_pattern_params(params: SignatureParams) -> Result<Vec<usize>, String> {
+        let plan = super::plan_pattern(Sheets(33), &[3, 4, 5, 4, 3], params)?;
+        Ok(plan.signatures)
+    }
+
+    #[test_case(&[] => Err("the pattern of signature sizes is empty".into()))]
+    #[test_case(&[3, 0] => Err("each signature in the pattern must have at least one sheet".into()))]
+    fn plan_pattern_invalid(pattern: &[usize]) -> Result<Vec<usize>, String> {
+        let plan = super::plan_pattern(Sheets(33), pattern, SignatureParams::default())?;
+        Ok(plan.signatures)
+    }
+
+    #[test_case(&[6, 6, 1], 3, 8 => vec![6, 7])]
+    #[test_case(&[1, 6, 6], 3, 8 => vec![7, 6])]
+    #[test_case(&[6, 1, 4], 3, 8 => vec![6, 5])]
+    #[test_case(&[6, 1, 1, 6], 3, 8 => vec![8, 6])]
+    #[test_case(&[6, 1, 1, 6], 3, 6 => vec![6, 2, 6])]
+    #[test_case(&[6, 1, 2, 6], 4, 8 => vec![6, 3, 6])]
+    #[test_case(&[8, 2, 8], 4, 8 => vec![8, 2, 8])]
+    #[test_case(&[2], 4, 8 => vec![2])]
+    fn merge_small_signatures(
+        signatures: &[usize],
+        min_sheets: usize,
+        max_sheets: usize,
+    ) -> Vec<usize> {
+        let plan = SignaturePlan {
+            signatures: signatures.to_vec(),
+            writing_mode: WritingMode::HorizontalLtr,
+            rtl_signatures: Vec::new(),
+            first_fold: Fold::In,
+        };
+        super::merge_small_signatures(&plan, min_sheets, max_sheets).signatures
+    }
+
+    #[test]
+    fn rtl_signatures() {
+        let params = SignatureParams {
+            signature_size: 1,
+            minimum_remainder_size: 1,
+            ..Default::default()
+        };
+        let mut plan = super::plan_signatures(Sheets(3), params);
+        plan.rtl_signatures = vec![1];
+        let mut pages = [0; 12];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        // only the middle signature has the pages on each side swapped
+        assert_eq!(pages, [3, 0, 1, 2, 4, 7, 6, 5, 11, 8, 9, 10]);
+        assert_eq!(plan.signature_writing_mode(1), WritingMode::VerticalRtl);
+        assert_eq!(plan.signature_writing_mode(2), WritingMode::HorizontalLtr);
+
+        // a book written from right to left reads every signature from right to left
+        plan.writing_mode = WritingMode::VerticalRtl;
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        assert_eq!(pages, [0, 3, 2, 1, 4, 7, 6, 5, 8, 11, 10, 9]);
+        assert!((0..3).all(|signature| plan.signature_writing_mode(signature).is_rtl()));
+    }
+
+    #[test_case(RemainderStrategy::Overlong, 7 => vec![(7, 8)])]
+    #[test_case(RemainderStrategy::Overlong, 8 => vec![])]
+    #[test_case(RemainderStrategy::Distribute, 6 => vec![(6, 7), (7, 7)])]
+    fn thick_signatures(remainder: RemainderStrategy, max_sheets: usize) -> Vec<(usize, usize)> {
+        let params = SignatureParams {
+            remainder,
+            ..Default::default()
+        };
+        super::plan_signatures(Sheets(50), params).thick_signatures(max_sheets)
+    }
+
+    #[test_case(50, 6 => vec![(0, 6), (1, 6), (2, 6), (3, 6), (4, 6), (5, 6), (6, 6)])]
+    #[test_case(32, 4 => vec![])]
+    #[test_case(34, 4 => vec![(7, 6)])]
+    #[test_case(3, 4 => vec![(0, 3)])]
+    fn non_power_of_two_signatures(
+        num_sheets: usize,
+        signature_size: usize,
+    ) -> Vec<(usize, usize)> {
+        let params = SignatureParams {
+            signature_size,
+            ..Default::default()
+        };
+        super::plan_signatures(Sheets(num_sheets), params).non_power_of_two_signatures()
+    }
+
+    #[test_case(0 => (0, 0, true, true))]
+    #[test_case(7 => (0, 1, false, false))]
+    #[test_case(9 => (1, 2, true, false))]
+    #[test_case(19 => (2, 4, false, false))]
+    fn slot_position(dest: usize) -> (usize, usize, bool, bool) {
+        let params = SignatureParams {
+            signature_size: 2,
+            minimum_remainder_size: 1,
+            ..Default::default()
+        };
+        let position = super::plan_signatures(Sheets(5), params).slot_position(dest);
+        (
+            position.signature,
+            position.sheet,
+            position.front,
+            position.left,
+        )
+    }
+
+    #[test_case(50, 0.1, 0.0 => 5.0; "100 pages")]
+    #[test_case(160, 0.1, 0.5 => 16.5; "with cover")]
+    #[test_case(0, 0.1, 0.5 => 0.5; "no pages")]
+    fn spine_width(num_leaves: usize, caliper: f64, extra: f64) -> f64 {
+        // round to avoid floating point error in the comparison
+        (super::spine_width(Leaves(num_leaves), caliper, extra) * 1000.0).round() / 1000.0
+    }
+
+    /// Folding the other way swaps the front and back of each sheet, so that in a 4 sheet
+    /// signature the first and last pages are printed on the back of the outer sheet instead of
+    /// the front, and the middle two pages on the front of the inner sheet instead of the back.
+    #[test_case(Fold::In => vec![
+        15, 0, 1, 14,
+        13, 2, 3, 12,
+        11, 4, 5, 10,
+        9, 6, 7, 8,
+    ])]
+    #[test_case(Fold::Out => vec![
+        1, 14, 15, 0,
+        3, 12, 13, 2,
+        5, 10, 11, 4,
+        7, 8, 9, 6,
+    ])]
+    fn first_fold(first_fold: Fold) -> Vec<usize> {
+        let params = SignatureParams {
+            signature_size: 4,
+            first_fold,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Sheets(4), params);
+        let mut pages = vec![0; 16];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        pages
+    }
+
+    /// After folding, the two pages on each leaf are consecutive, with the recto first.
+    #[test_case(Binding::Perfect, WritingMode::HorizontalLtr, Fold::In)]
+    #[test_case(Binding::Perfect, WritingMode::VerticalRtl, Fold::In)]
+    #[test_case(Binding::Perfect, WritingMode::HorizontalLtr, Fold::Out)]
+    #[test_case(Binding::Saddle, WritingMode::HorizontalLtr, Fold::In)]
+    #[test_case(Binding::Saddle, WritingMode::VerticalRtl, Fold::Out)]
+    fn leaves(binding: Binding, writing_mode: WritingMode, first_fold: Fold) {
+        let params = SignatureParams {
+            signature_size: 3,
+            minimum_remainder_size: 1,
+            binding,
+            writing_mode,
+            first_fold,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Sheets(7), params);
+        let mut pages = vec![0; plan.num_pages().0];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        for (dest, &page) in pages.iter().enumerate() {
+            let partner = pages[super::leaf_partner(dest)];
+            assert_eq!(page.abs_diff(partner), 1);
+            assert_eq!(page.min(partner) % 2, 0);
+        }
+    }
+
+    /// Perfect bound signatures are stacked rather than nested, so each signature holds a
+    /// contiguous run of pages, in order.
+    #[test]
+    fn perfect_binding() {
+        let params = SignatureParams {
+            signature_size: 2,
+            minimum_remainder_size: 1,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Sheets(5), params);
+        assert_eq!(plan.signatures, [2, 2, 1]);
+        let mut pages = [0; 20];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        for (signature, range) in [(0, 0..8), (1, 8..16), (2, 16..20)] {
+            let mut sources = pages[range.clone()].to_vec();
+            sources.sort();
+            assert_eq!(sources, range.collect::<Vec<_>>(), "signature {signature}");
+        }
+    }
+
+    /// A signature size of 0 puts every sheet into a single signature.
+    #[test]
+    fn single_signature() {
+        let params = SignatureParams {
+            signature_size: 0,
+            minimum_remainder_size: 4,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Pages(40).sheets_needed(), params);
+        assert_eq!(plan.signatures, [10]);
+        let mut pages = [0; 40];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        assert_eq!(pages[..8], [39, 0, 1, 38, 37, 2, 3, 36]);
+        assert_eq!(pages[36..], [21, 18, 19, 20]);
+    }
+
+    /// Saddle stitched books nest every sheet into a single signature.
+    #[test]
+    fn saddle_binding() {
+        let params = SignatureParams {
+            binding: Binding::Saddle,
+            ..Default::default()
+        };
+        let plan = super::plan_signatures(Sheets(10), params);
+        assert_eq!(plan.signatures, [10]);
+        let mut pages = [0; 40];
+        super::arrange_pages_with(&plan, |src, dest| pages[dest] = src);
+        assert_eq!(pages[..4], [39, 0, 1, 38]);
+        assert_eq!(pages[36..], [21, 18, 19, 20]);
+    }
+
+    #[test_case(Pass::Odd => vec![0, 1, 4, 5, 8, 9])]
+    #[test_case(Pass::Even => vec![10, 11, 6, 7, 2, 3])]
+    fn pass_pages(pass: Pass) -> Vec<usize> {
+        super::pass_pages(12, pass)
+    }
+
+    #[test]
+    fn blank_back_pages() {
+        assert_eq!(
+            super::blank_back_pages(8),
+            [0, 1, 8, 8, 2, 3, 8, 8, 4, 5, 8, 8, 6, 7, 8, 8].map(|page| (page < 8).then_some(page))
+        );
+    }
+
+    #[test_case(0 => (0, false))]
+    #[test_case(1 => (1, false))]
+    #[test_case(2 => (3, true))]
+    #[test_case(3 => (2, true))]
+    #[test_case(6 => (7, true))]
+    fn tumble_position(page: usize) -> (usize, bool) {
+        super::tumble_position(page)
+    }
+
+    #[test_case(4 => vec![3, 0, 1, 2])]
+    #[test_case(8 => vec![7, 0, 1, 6, 5, 2, 3, 4])]
+    #[test_case(12 => vec![11, 0, 1, 10, 9, 2, 3, 8, 7, 4, 5, 6])]
+    fn printer_order(num_pages: usize) -> Vec<usize> {
+        super::printer_order(&(0..num_pages).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn printer_order_spreads() {
+        for num_pages in (4..=128_usize).step_by(4) {
+            let order = super::printer_order(&(0..num_pages).collect::<Vec<_>>());
+            // every page is printed exactly once
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..num_pages).collect::<Vec<_>>());
+            for side in order.chunks(2) {
+                // the two pages on each side of a bifolium are mirrored around the centre fold
+                assert_eq!(side[0] + side[1], num_pages - 1);
+            }
+            for (dest, &src) in order.iter().enumerate() {
+                // pages on either side of a leaf are consecutive in reading order
+                assert_eq!(src.abs_diff(order[super::leaf_partner(dest)]), 1);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn printer_order_partial_sheet() {
+        super::printer_order(&[0, 1, 2]);
+    }
+
+    #[test]
+    fn signature() {
+        let mut pages = [0; 16];
+        super::signature_with(0, 4, |src, dest| {
+            pages[dest] = src;
+        });
+        assert_eq!(
+            pages,
+            [15, 0, 1, 14, 13, 2, 3, 12, 11, 4, 5, 10, 9, 6, 7, 8]
+        )
+    }
+}
@@ -0,0 +1,69 @@
+//! Messages about the imposition, at the level of detail chosen with `--quiet` and `--verbose`:
+//!
+//! - `--quiet`: only errors.
+//! - by default: warnings, the summary of the imposition, and any other output asked for, such as
+//!   `--reader-order` or `--timings`.
+//! - `-v`: also each step as it happens, and the number of sheets in each signature.
+//! - `-vv`: also the decisions made about each page, such as where it is placed in the output.
+//!
+//! The summary and other output asked for are written to standard output, and everything else to
+//! standard error, so that the output can be piped without warnings getting mixed into it.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes this crate's messages to the terminal, ignoring those of its dependencies.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+            && metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("error: {}", record.args()),
+            Level::Warn => eprintln!("warning: {}", record.args()),
+            Level::Info => println!("{}", record.args()),
+            Level::Debug | Level::Trace => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The most detailed level of messages to print. See the [module documentation](self).
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Starts printing messages up to the given level.
+pub fn init(level: LevelFilter) -> color_eyre::Result<()> {
+    log::set_logger(&Logger)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use log::LevelFilter;
+    use test_case::test_case;
+
+    #[test_case(true, 0 => LevelFilter::Error)]
+    #[test_case(true, 2 => LevelFilter::Error)]
+    #[test_case(false, 0 => LevelFilter::Info)]
+    #[test_case(false, 1 => LevelFilter::Debug)]
+    #[test_case(false, 2 => LevelFilter::Trace)]
+    #[test_case(false, 5 => LevelFilter::Trace)]
+    fn level(quiet: bool, verbose: u8) -> LevelFilter {
+        super::level(quiet, verbose)
+    }
+}
@@ -1,91 +1,2487 @@
-use std::path::PathBuf;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use clap::Parser;
-use lopdf::{Document, Object};
+#[cfg(test)]
+use bookbinding::testing;
+use bookbinding::{
+    arrange_pages_with, blank_back_pages, check_num_signatures, french_fold_source, leaf_partner,
+    merge_small_signatures, nup_document, pass_pages, permutation_cycles, plan_pattern,
+    plan_signatures, signature_with, spine_width, tumble_position, Binding, Marks, NupLayout,
+    Pages, Pass, RemainderStrategy, SheetSettings, Sheets, SignatureParams, SignaturePlan,
+    SlotPosition, MAX_FOLDABLE_SHEETS,
+};
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{bail, eyre};
+use log::{debug, info, trace, warn};
+use lopdf::{xref::XrefType, Dictionary, Document, Object, ObjectId};
+
+use crate::{
+    grid::Grid,
+    length::{Length, Size, POINTS_PER_MM},
+    pdf::{BlankStyle, Fit, PdfX, PlacedBox},
+    schemes::Scheme,
+};
+
+mod analyze;
+mod font;
+mod glob;
+mod grid;
+mod instructions;
+mod interactive;
+mod length;
+mod logging;
+mod name_pattern;
+mod pdf;
+mod postscript;
+mod report;
+mod schemes;
+mod svg;
+mod transform;
 
 #[derive(Debug, Parser)]
+#[command(
+    args_override_self = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_help = AFTER_HELP
+)]
 struct Args {
-    /// Path to the input PDF
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Path to the input PDF. The file name can contain the wildcards `*` and `?`, such as
+    /// `chapters/*.pdf`, to impose all of the matching files as one document. They are joined in
+    /// natural order, so that `ch2.pdf` comes before `ch10.pdf`.
+    #[arg(required = true)]
+    input: Option<PathBuf>,
     /// Path to the output PDF
-    output: PathBuf,
+    #[arg(required = true)]
+    output: Option<PathBuf>,
+    /// Allow the output to be the same file as the input, replacing it. The output is written to
+    /// a temporary file next to it, which only replaces the input once it is complete.
+    #[arg(long, conflicts_with = "name_pattern")]
+    in_place: bool,
+    /// The format of the output. PostScript output is for RIPs which do not accept PDF, and only
+    /// supports simple pages: vector graphics, text in the standard 14 fonts, and no images.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pdf, conflicts_with = "name_pattern")]
+    output_format: OutputFormat,
     #[command(flatten)]
     signature_params: SignatureParams,
     /// Adds an extra page at the start and end of the document.
     #[arg(long)]
     end_pages: bool,
+    /// Whether the first page of the input is a recto (right-hand page) or a verso (left-hand
+    /// page). If it is a verso, a blank page is added before it, so that it faces the second page
+    /// and the pages which follow are on the right side of the spine. With `--end-pages`, the end
+    /// page at the front takes the place of the blank page.
+    #[arg(long, value_enum, default_value_t = FirstPage::Recto)]
+    first_page: FirstPage,
+    /// Minimum number of pages in the output. If the padded document would be shorter than this,
+    /// blank endpapers are added evenly to the front and back to make up the difference. These
+    /// are added outside of any pages added by `--end-pages`, and count towards the minimum.
+    #[arg(long)]
+    min_pages: Option<usize>,
+    /// Fail instead of adding pages to fill the last sheet, if the number of pages (including any
+    /// end pages and endpapers) is not already a whole number of sheets.
+    #[arg(long)]
+    no_pad: bool,
+    /// The fewest pages the end of the book can be made of: 4 for a whole folded sheet, or 2 for
+    /// a single leaf, cut from half of a sheet, after the last signature. With 2, a thin pamphlet
+    /// whose number of pages is 2 more than a multiple of 4 is padded to a multiple of 2 rather
+    /// than 4, and its last two pages are printed on one half of the last sheet, which is cut off
+    /// and tipped in after the folded signatures.
+    #[arg(long, default_value_t = 4, value_parser = parse_min_fold, conflicts_with = "grid")]
+    min_fold: usize,
+    /// Sizes of the signatures, in sheets, as a pattern separated by commas, such as `3,4,5,4,3`,
+    /// which is repeated until all of the sheets are placed, for signatures of varying thickness.
+    /// Sheets left over at the end are handled as with `--signature-size`, according to
+    /// `--minimum-remainder-size` and `--remainder`, and sizes longer than
+    /// `--max-signature-sheets` are shortened to it.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = [
+            "signature_size", "num_signatures", "leaves_per_section", "apply_plan", "grid"
+        ]
+    )]
+    vary_signatures: Vec<usize>,
+    /// Number of leaves in each section (or quire) of sheets folded together, for gathering
+    /// several sections into each signature, nested one inside the next. This must be a whole
+    /// number of sheets, which is a multiple of 2. Used with `--sections-per-gathering`, as a
+    /// shorthand for `--signature-size`: nested sections read the same as a single signature of
+    /// all of their sheets, so each gathering is a signature of that many sheets. Sheets left over
+    /// at the end are handled as with `--signature-size`, so the last signatures may not hold a
+    /// whole number of sections.
+    #[arg(
+        long,
+        requires = "sections_per_gathering",
+        conflicts_with_all = ["signature_size", "num_signatures", "grid"]
+    )]
+    leaves_per_section: Option<usize>,
+    /// Number of sections nested into each signature. See `--leaves-per-section`.
+    #[arg(long, requires = "leaves_per_section")]
+    sections_per_gathering: Option<usize>,
+    /// Maximum number of pages which padding may insert, counting end pages, endpapers and the
+    /// pages which fill the last sheet. More padding than expected usually means that the page
+    /// count or the padding options are wrong, so this is an error.
+    #[arg(long)]
+    max_padding: Option<usize>,
+    /// Fail if any signature has a number of pages which is not a power of two (4, 8, 16, 32, and
+    /// so on), which many folding machines need, listing the signatures which don't and
+    /// suggesting a signature size and number of pages which work.
+    #[arg(long)]
+    require_power_of_two: bool,
+    /// Warn about consecutive pages with identical content, which often indicates a scanning
+    /// mistake. Blank pages are not reported.
+    #[arg(long)]
+    dedup_warn: bool,
+    /// Page number (starting from 1) of the page to use as a template for inserted blank pages.
+    /// Blank pages inherit the size and other attributes of this page. By default, pages added at
+    /// the start copy the first page, and pages added at the end copy the last page.
+    #[arg(long)]
+    blank_template: Option<u32>,
+    /// What to draw on inserted blank pages (padding, endpapers and end pages). `note` marks them
+    /// as intentionally left blank, and `grid` and `dots` fill them with a light 5 mm grid, such
+    /// as for notebooks.
+    #[arg(long, value_enum, default_value_t = BlankStyle::Empty)]
+    blank_style: BlankStyle,
+    /// Also write the pages in reading order to this path, for reviewing on screen before
+    /// printing. The proof has the pages as they are imposed, once they are cropped, scaled, and
+    /// padded with blank pages, but not rearranged onto the sheets.
+    #[arg(long)]
+    proof: Option<PathBuf>,
+    /// Also write a page summarising the imposition to this path. The summary is written as a
+    /// separate PDF so that it does not affect the signatures.
+    #[arg(long)]
+    colophon: Option<PathBuf>,
+    /// Also write a page of instructions for folding and binding the sheets to this path, with
+    /// diagrams of the order of the folds, how the sheets nest, and which edge to bind.
+    #[arg(long, conflicts_with = "grid")]
+    instructions: Option<PathBuf>,
+    /// Apply each page's `/Rotate` to its content before imposing, so that every page is upright
+    /// with no rotation.
+    #[arg(long)]
+    normalize_rotation: bool,
+    /// Move the content of each page whose media box does not start at (0, 0), such as
+    /// `[100 100 712 892]`, so that it does, for printers and later tools which assume that it
+    /// does. This is applied after the pages are clipped to `--placed-box`.
+    #[arg(long)]
+    normalize_origin: bool,
+    /// Repair problems in the content of the input's pages which would affect the imposed pages
+    /// around them. For now, this balances unmatched `q` and `Q` operators, which save and restore
+    /// the graphics state. Pages whose content cannot be parsed are left as they are, with a
+    /// warning.
+    #[arg(long)]
+    sanitize_content: bool,
+    /// Print the wall-clock time taken by each phase of the imposition.
+    #[arg(long)]
+    timings: bool,
+    /// Print nothing but errors.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print each step as it happens. Give twice to also print the decisions made about each
+    /// page, such as where it is placed in the output.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Maximum size of the output file, in bytes. If the output is larger than this, it is deleted
+    /// and an error is returned.
+    #[arg(long)]
+    max_output_bytes: Option<u64>,
+    /// Only output the sides of the sheets for one pass of printing by hand on both sides, running
+    /// the tool once for each pass.
+    #[arg(long, value_enum)]
+    pass: Option<Pass>,
+    /// Path to a separate PDF for the cover, which is added as an extra sheet before the imposed
+    /// pages. The cover must have either 4 pages (front, inside front, inside back, and back), or
+    /// a single page containing the whole outside of a wrap-around cover.
+    #[arg(long)]
+    cover_file: Option<PathBuf>,
+    /// Shift the content of the front and back of a four page `--cover-file` outwards, each by
+    /// half the width of the spine, so that the cover fits around the thickness of the bound book
+    /// instead of folding at the middle of the sheet. The width of the spine comes from
+    /// `--paper-caliper` and `--cover-allowance`.
+    #[arg(long, requires = "cover_file", requires = "paper_caliper")]
+    cover_creep: bool,
+    /// Instead of writing the output PDF, write a diagram of the signatures and the pages on each
+    /// sheet to this path as an SVG.
+    #[arg(long)]
+    dry_run_svg: Option<PathBuf>,
+    /// Which of each page's boxes defines the region that is placed on the sheet. Any content
+    /// outside of this region is hidden. Missing crop boxes default to the media box, and missing
+    /// trim boxes to the crop box. By default, the pages' boxes are left as they are, and the crop
+    /// box is placed.
+    #[arg(long = "box", value_enum)]
+    placed_box: Option<PlacedBox>,
+    /// Write the objects needed to show the first page at the start of the output PDF, so that
+    /// viewers reading it as it downloads can show the first page sooner. This is a best effort at
+    /// "fast web view": the output is not fully linearized, since no linearization dictionary or
+    /// hint tables are written.
+    #[arg(long)]
+    linearize: bool,
+    /// PDF version to write in the output, such as `1.4`, for printers which only accept certain
+    /// versions. Defaults to the version of the input.
+    #[arg(long, value_parser = parse_pdf_version, conflicts_with = "pdfx")]
+    pdf_version: Option<String>,
+    /// How the cross-reference section of the output is written. A classic table can be read by
+    /// every viewer and RIP, while a cross-reference stream needs PDF 1.5 or later, so an older
+    /// document is written as PDF 1.5. Defaults to the format of the input.
+    #[arg(long, value_enum)]
+    xref_format: Option<XrefFormat>,
+    /// Add the output intent, metadata, and trim boxes required by a PDF/X standard, for printers
+    /// which require it, and warn about fonts which are not embedded. This does not check
+    /// everything the standard requires, such as colour spaces and transparency, so the output
+    /// should still be checked with a preflight tool.
+    #[arg(long, value_enum)]
+    pdfx: Option<PdfX>,
+    /// Embed the font of the text which the tool draws, such as notes on blank pages, signature
+    /// stamps, and the colophon, for printers which reject fonts which are not embedded, as PDF/X
+    /// does. The text is set in DejaVu Sans instead of the standard Helvetica, with only the
+    /// glyphs it uses. The fonts of the input are not changed. Not supported with
+    /// `--output-format ps`, which only prints the standard fonts.
+    #[arg(long)]
+    embed_fonts: bool,
+    /// Set an entry in the output's document information, written as `KEY=VALUE`, such as
+    /// `Subject="Job 4417"`. Can be given more than once. The key must be one of the standard
+    /// entries (Title, Author, Subject, Keywords, Creator, Producer, CreationDate, ModDate, and
+    /// Trapped) unless `--custom-meta` is given. Dates are written as they are given, in the PDF
+    /// date format such as `D:20240131120000Z`.
+    #[arg(long, value_parser = parse_meta)]
+    set_meta: Vec<(String, String)>,
+    /// Remove the document information (such as the author, producer, and dates) and XMP metadata
+    /// from the output, such as before publishing it.
+    #[arg(long, conflicts_with_all = ["set_meta", "pdfx"])]
+    strip_metadata: bool,
+    /// Allow `--set-meta` to set keys which are not standard entries in the document information.
+    #[arg(long)]
+    custom_meta: bool,
+    /// Numbers (starting from 1) of signatures which read from right to left, such as inserts in
+    /// another language, separated by commas. The other signatures follow `--writing-mode`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "apply_plan")]
+    signature_rtl: Vec<usize>,
+    /// Warn about signatures with more than this many sheets, which may be too thick to fold.
+    #[arg(long, default_value_t = MAX_FOLDABLE_SHEETS)]
+    max_foldable_sheets: usize,
+    /// Merge each signature with fewer than this many sheets into a neighbouring signature, as
+    /// long as the merged signature has at most `--max-foldable-sheets` sheets.
+    #[arg(long)]
+    merge_below: Option<usize>,
+    /// Print a table of where each page is printed, with its position in reading order, and
+    /// whether the two pages on each leaf are consecutive once the sheets are folded.
+    #[arg(long)]
+    reader_order: bool,
+    /// Remove the structure tree from tagged PDFs. Reordering the pages leaves the structure tree
+    /// pointing at the wrong pages, which can make the output fail validation.
+    #[arg(long)]
+    drop_tags: bool,
+    /// Thickness of the paper, such as `0.1mm`, used to print the width of the spine.
+    #[arg(long)]
+    paper_caliper: Option<Length>,
+    /// Extra width to add to the spine for the thickness of the cover, such as `1mm`.
+    #[arg(long, default_value = "0", requires = "paper_caliper")]
+    cover_allowance: Length,
+    /// Path to a second PDF with the same number of pages, whose pages are interleaved with the
+    /// input's before imposing, such as a translation to show on facing pages.
+    #[arg(long)]
+    interleave: Option<PathBuf>,
+    /// Pass the first page of the input through to the output unchanged, as its first page, such
+    /// as a cover to print separately at full size. The rest of the pages are imposed as if they
+    /// were the whole input. This is applied after `--order`.
+    #[arg(long, conflicts_with_all = ["grid", "pass", "name_pattern", "validate"])]
+    passthrough_first: bool,
+    /// Reverse the order of the input's pages, such as for a document scanned back to front. This
+    /// only changes the order the pages are read in, unlike `--writing-mode vertical-rtl`, which
+    /// binds the book on the right. It is applied first, so that the page numbers given to the
+    /// other options, such as `--order`, are of the reversed pages, and the padding is added after
+    /// the last page in the reversed order.
+    #[arg(long)]
+    reverse_input: bool,
+    /// Split each page of the input which is a spread of two pages down the middle, cropping it
+    /// into a left and a right page. Pages which are wider than they are tall, as displayed, are
+    /// split, and any others, such as a single front cover, are left whole. This is applied after
+    /// `--interleave` and before `--order`, so the page numbers in the order are of the split
+    /// pages.
+    #[arg(long)]
+    split_spreads: bool,
+    /// Path to a file listing the numbers of the input's pages in the order to impose them, such
+    /// as to substitute corrected pages in a reprint. Pages can be repeated or left out. Numbers
+    /// are separated by whitespace or commas, ranges are written like `5-8` (or `8-5` to reverse
+    /// them), and `#` starts a comment. This is applied after `--interleave`.
+    #[arg(long)]
+    order: Option<PathBuf>,
+    /// What to fill the pages added to make up the last sheet with: either `blank`, or the number
+    /// (starting from 1) of a page to repeat, such as for note pads. End pages and endpapers are
+    /// always blank.
+    #[arg(long, value_parser = parse_pad_with, default_value = "blank")]
+    pad_with: PadWith,
+    /// Distance to trim from the head, foot, and fore-edge of each page, such as `3mm`, setting
+    /// its trim box and crop box. Lengths can be given in `pt`, `mm`, `cm`, or `in`, and default to
+    /// points.
+    #[arg(long, default_value = "0")]
+    trim: Length,
+    /// Scale each page to this trim size of the finished book, such as `6inx9in`, for pages which
+    /// are larger (or smaller) than the book. Unlike `--trim`, which crops the pages, this scales
+    /// their content, as set by `--book-fit`. The new size becomes the trim box of each page.
+    #[arg(long)]
+    book_size: Option<Size>,
+    /// How pages are scaled to `--book-size` if their proportions differ from it.
+    #[arg(long, value_enum, default_value_t = Fit::Contain, requires = "book_size")]
+    book_fit: Fit,
+    /// Distance to trim from the head (top) of each page. Defaults to `--trim`.
+    #[arg(long)]
+    trim_head: Option<Length>,
+    /// Distance to trim from the foot (bottom) of each page. Defaults to `--trim`.
+    #[arg(long)]
+    trim_foot: Option<Length>,
+    /// Distance to trim from the fore-edge of each page, which is the right of rectos and the left
+    /// of versos (or the reverse for right-to-left books). Defaults to `--trim`.
+    #[arg(long)]
+    trim_fore: Option<Length>,
+    /// Record where each page came from and where it is printed in the page's `/PieceInfo`
+    /// dictionary, which is not displayed or printed, so that misprinted pages can be traced.
+    #[arg(long)]
+    annotate_provenance: bool,
+    /// Open the output in the default PDF viewer once it has been written. Does nothing if there
+    /// is no display to show it on.
+    #[cfg(feature = "open")]
+    #[arg(long)]
+    open: bool,
+    /// Instead of folding, place the pages in a grid of `<columns>x<rows>` on each sheet, such as
+    /// `3x4`, with cut marks around it, for items which are cut apart such as cards. The pages are
+    /// placed in order, filling each row from left to right, starting with the top row.
+    #[arg(
+        long,
+        conflicts_with_all = ["pass", "cover_file", "dry_run_svg", "tumble", "name_pattern"]
+    )]
+    grid: Option<Grid>,
+    /// Instead of folding into signatures, print each group of four pages as a French fold, for
+    /// cards: all four on one side of a sheet, in a 2x2 grid. The sheet is folded in half across
+    /// its width with the printed side out, and then in half down the middle, which hides the
+    /// blank side inside. The top row is printed upside down, since it ends up on the inside of
+    /// the card. The marks around the grid show where to fold.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "grid", "pass", "cover_file", "dry_run_svg", "tumble", "name_pattern", "mapping",
+            "mapping_file", "apply_plan", "passthrough_first", "validate", "qa_summary",
+            "instructions", "report", "reader_order", "min_fold", "require_power_of_two"
+        ]
+    )]
+    french_fold: bool,
+    /// Space between the pages in a `--grid`, such as `5mm`.
+    #[arg(long, default_value = "0", requires = "grid")]
+    grid_gap: Length,
+    /// Draw registration marks in the corners of each `--grid` sheet, in the registration color
+    /// (`/Separation /All`) so that they print on every plate, for lining up the plates on an
+    /// offset press.
+    #[arg(long, requires = "grid")]
+    registration: bool,
+    /// Draw a bar of CMYK color patches along the bottom of each `--grid` sheet, for checking the
+    /// ink on an offset press. The margins are made larger to fit it below the cut marks.
+    #[arg(long, requires = "grid")]
+    color_bar: bool,
+    /// Turn the back of each sheet upside down, for duplex printers which turn sheets over along
+    /// their short edge ("tumble"), which would otherwise print the backs upside down. The two
+    /// pages on the back of each sheet (the third and fourth output pages of each sheet) swap
+    /// places and are rotated by 180°; the fronts are unchanged.
+    #[arg(long)]
+    tumble: bool,
+    /// Bind along the top edge of the pages, as for a wall calendar, rather than along the side.
+    /// Every page is turned a quarter turn on its sheet, so that the book is folded and bound as
+    /// usual and hung on its side. The front of each leaf then has its top edge along the fold,
+    /// and the back of each leaf reads the right way up when it is flipped over the top, above the
+    /// front of the next.
+    #[arg(long, conflicts_with_all = ["grid", "french_fold", "signature_rtl"])]
+    head_binding: bool,
+    /// Place the pages of each sheet together on the output pages, as they are printed, in this
+    /// layout of `<columns>x<rows>`, instead of writing each page on its own: `2x1` puts the two
+    /// pages of each side of a sheet side by side, `1x2` one above the other, head to head, and
+    /// `2x2` or `4x1` puts all four pages of a sheet on one output page, such as for proofing.
+    #[arg(
+        long,
+        conflicts_with_all = ["grid", "french_fold", "name_pattern", "passthrough_first"]
+    )]
+    nup_layout: Option<NupLayout>,
+    /// Draw marks in a margin around the pages on each `--nup-layout` page, lined up with the
+    /// edges of the pages: `outer` marks only the outer edges, where folded booklets are trimmed,
+    /// `cut` also marks the edges between pages which are cut apart, for sheets which are cut and
+    /// stacked, and `all` also marks the fold between the two pages of each side. The folds of
+    /// `--grid` and `--french-fold` sheets are always marked.
+    #[arg(long, value_enum, requires = "nup_layout")]
+    marks: Option<Marks>,
+    /// The size of each `--nup-layout` page, such as `17inx11in`, instead of fitting it to the
+    /// pages placed on it. The pages are centered on it, and shrunk to fit inside the
+    /// `--sheet-margin` if they are too large.
+    #[arg(long, requires = "nup_layout")]
+    sheet_size: Option<Size>,
+    /// Space to leave clear at the edges of each `--nup-layout` page, such as `10mm` for the
+    /// gripper of a press, which can't print there. The pages are placed inside it, while the
+    /// `--marks` are lined up with the edges of the pages and go in the margin around them.
+    #[arg(long, default_value = "0", requires = "nup_layout")]
+    sheet_margin: Length,
+    /// The size of the `--nup-layout` pages of one signature, written as `<signature>:<size>`
+    /// with the signatures numbered from 1, such as `1:17inx11in` for a first signature printed on
+    /// larger sheets. Can be given more than once. The other signatures, and the `--cover-file`
+    /// sheet, are printed on `--sheet-size`.
+    #[arg(
+        long,
+        value_parser = parse_signature_sheet,
+        requires = "nup_layout",
+        conflicts_with_all = ["pass", "blank_backs"]
+    )]
+    signature_sheet: Vec<(usize, Size)>,
+    /// With a `--sheet-size` or `--signature-sheet`, keep room for the `--marks` inside the
+    /// `--sheet-margin`, shrinking the pages if needed, so that the marks are never in the margin,
+    /// nor over the pages.
+    #[arg(long, requires = "marks")]
+    reserve_marks: bool,
+    /// Follow each side of the sheets with a blank side, so that every side is printed on the
+    /// front of its own sheet and the backs are left blank. This is for printing on one side only
+    /// where the print driver or service still expects a duplex job: every side keeps the layout
+    /// it has when printed on both sides, so the sheets can be pasted back to back and folded
+    /// along the same lines, or used to proof the imposition. Unlike `--pass`, the whole job is a
+    /// single file.
+    #[arg(long, conflicts_with_all = ["pass", "tumble", "grid", "validate"])]
+    blank_backs: bool,
+    /// Write each signature to a separate file instead of a single output, named by this pattern,
+    /// such as `{stem}_sig{sig:02}_of_{total}.pdf`. The files are written next to the output,
+    /// which is not itself written. The placeholders are `{stem}` for the output's file name
+    /// without its extension, `{sig}` for the number of the signature, and `{total}` for the
+    /// number of signatures, and numbers can be padded with zeros, as in `{sig:02}`.
+    #[arg(long, value_parser = parse_name_pattern, conflicts_with_all = ["pass", "cover_file"])]
+    name_pattern: Option<String>,
+    /// Stamp the first page of each signature file from `--name-pattern` with its file name, the
+    /// number of the signature, its number of sheets, and the range of pages in it, in small text
+    /// in a slug added below the page, outside its trim box, so that the signatures can't be
+    /// mixed up at the bindery.
+    #[arg(long, requires = "name_pattern")]
+    stamp_signature_info: bool,
+    /// Remove any blank pages from the end of the input before padding, so that they don't push
+    /// the padding onto extra sheets. Pages are blank if they draw nothing visible (see
+    /// `--blank-tolerance`).
+    #[arg(long)]
+    trim_trailing_blanks: bool,
+    /// The side of a square, such as `2mm`, whose area is the smallest a shape drawn on a page
+    /// can cover and still count when deciding whether the page is blank, so that pages with only
+    /// specks or stray marks count as blank. Text and images always count.
+    #[arg(long, default_value = "0")]
+    blank_tolerance: Length,
+    /// Check the imposed output before writing it: each page which is not blank must have the
+    /// same content as the page the plan says belongs there, or the run fails.
+    #[arg(long, conflicts_with_all = ["pass", "grid"])]
+    validate: bool,
+    /// Place the pages according to an explicit mapping instead of the signatures, for impositions
+    /// which the built-in arrangements don't cover. The mapping is either a list of `dest=src`
+    /// pairs, or a list of source pages in output order, and must place each page of the padded
+    /// document exactly once. Page numbers start from 1, and are separated by whitespace or
+    /// commas. The signatures are still planned, for the summary and for `--cover-file`, but the
+    /// options which describe the plan's order of the pages, such as `--report`, can't be used.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "grid", "validate", "report", "reader_order", "dry_run_svg", "name_pattern"
+        ]
+    )]
+    mapping: Option<String>,
+    /// Path to a file containing a mapping, as for `--mapping`, where `#` starts a comment.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "grid", "validate", "mapping", "report", "reader_order", "dry_run_svg", "name_pattern"
+        ]
+    )]
+    mapping_file: Option<PathBuf>,
+    /// Write a JSON report of the plan and the order of the pages to this path, which can be
+    /// passed to `--apply-plan` to impose a reprint in exactly the same way.
+    #[arg(long, conflicts_with = "grid")]
+    report: Option<PathBuf>,
+    /// Impose the document with the plan from a report written by `--report`, instead of planning
+    /// the signatures again, so the options which control the plan, such as `--signature-size` and
+    /// `--binding`, can't be given with it. The document must have the same number of pages as the
+    /// plan once padded.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "signature_size", "minimum_remainder_size", "remainder", "max_signature_sheets",
+            "num_signatures", "binding", "first_fold", "leaves_per_section",
+            "sections_per_gathering", "merge_below", "signature_rtl", "grid"
+        ]
+    )]
+    apply_plan: Option<PathBuf>,
+    /// Write a single line summarising the imposition to this path, or to standard error for `-`,
+    /// for automated checks: the number of input, blank, and output pages, the sheets and
+    /// signatures, and whether each page of the input was found where it was imposed, by comparing
+    /// the content of the output's pages. The keys are always given in the same order, as in
+    /// `bookbinding-qa version=1 input_pages=10 blank_pages=2 output_pages=12 sheets=3
+    /// signatures=1 permutation=ok`.
+    #[arg(
+        long,
+        conflicts_with_all = ["grid", "pass", "blank_backs", "passthrough_first", "nup_layout"]
+    )]
+    qa_summary: Option<PathBuf>,
+}
+
+impl Args {
+    /// The path to the input, which is required unless a command is given.
+    fn input(&self) -> &Path {
+        self.input.as_deref().expect("the input is required")
+    }
+
+    /// The path to the output, which is required unless a command is given.
+    fn output(&self) -> &Path {
+        self.output.as_deref().expect("the output is required")
+    }
+}
+
+/// The commands which do something other than imposing a PDF.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the page sizes of a PDF, and suggest a signature size and sheet size for it, without
+    /// imposing it.
+    Analyze(analyze::AnalyzeArgs),
+    /// Ask plain questions about the book, such as how many sheets can be folded together, and
+    /// impose it with the options worked out from the answers.
+    Interactive,
+    /// List the ways of placing the pages on the sheets, with the number of pages on each sheet
+    /// and an example of the options for each.
+    Schemes,
+}
+
+/// Parses the value of `--name-pattern`, checking that it can be expanded.
+fn parse_name_pattern(value: &str) -> Result<String, String> {
+    name_pattern::expand(value, "", 1, 1)?;
+    Ok(value.to_owned())
+}
+
+/// Parses the value of `--set-meta`.
+fn parse_meta(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected `KEY=VALUE`, not {value:?}"))?;
+    let valid = |c: char| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(c);
+    if key.is_empty() || !key.chars().all(valid) {
+        return Err(format!("invalid document information key {key:?}"));
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// The format to write the output in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// PDF.
+    Pdf,
+    /// PostScript (DSC-conforming, language level 2).
+    Ps,
+}
+
+/// How the cross-reference section of the output is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum XrefFormat {
+    /// A classic cross-reference table, which every reader supports.
+    Table,
+    /// A cross-reference stream, which is more compact, but needs PDF 1.5 or later.
+    Stream,
+}
+
+impl From<XrefFormat> for XrefType {
+    fn from(format: XrefFormat) -> Self {
+        match format {
+            XrefFormat::Table => XrefType::CrossReferenceTable,
+            XrefFormat::Stream => XrefType::CrossReferenceStream,
+        }
+    }
+}
+
+/// The side of the spine the first page of the input is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum FirstPage {
+    /// A right-hand page, as usual.
+    Recto,
+    /// A left-hand page, which needs a blank page before it.
+    Verso,
+}
+
+/// What to fill padding pages with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PadWith {
+    /// Blank pages.
+    Blank,
+    /// Copies of the page with this number, starting from 1.
+    Page(u32),
+}
+
+/// Parses the value of `--pad-with`.
+fn parse_pad_with(value: &str) -> Result<PadWith, String> {
+    match value {
+        "blank" => Ok(PadWith::Blank),
+        _ => match value.parse() {
+            Ok(number) if number > 0 => Ok(PadWith::Page(number)),
+            _ => Err(format!(
+                "expected `blank` or a page number starting from 1, not {value:?}"
+            )),
+        },
+    }
+}
+
+/// Parses the value of `--min-fold`.
+fn parse_min_fold(value: &str) -> Result<usize, String> {
+    match value {
+        "2" => Ok(2),
+        "4" => Ok(4),
+        _ => Err(format!("expected 2 or 4, not {value:?}")),
+    }
+}
+
+/// Parses the value of `--signature-sheet`.
+fn parse_signature_sheet(value: &str) -> Result<(usize, Size), String> {
+    let (signature, size) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected `<signature>:<size>`, not {value:?}"))?;
+    match signature.trim().parse() {
+        Ok(signature) if signature > 0 => Ok((signature, size.parse()?)),
+        _ => Err(format!(
+            "expected a signature number starting from 1, not {signature:?}"
+        )),
+    }
+}
+
+/// Flags which are shorthand for a group of other options. See [`expand_presets`].
+const PRESETS: &[(&str, &[&str])] = &[
+    (
+        "--newspaper",
+        &[
+            "--binding",
+            "saddle",
+            "--nup-layout",
+            "2x1",
+            "--sheet-size",
+            "17inx11in",
+            "--marks",
+            "outer",
+        ],
+    ),
+    ("--calendar", &["--binding", "saddle", "--head-binding"]),
+];
+
+/// Help text describing the presets.
+const AFTER_HELP: &str = "\
+Presets:
+      --newspaper  A folded newspaper or newsletter, with all of the sheets nested in a single
+                   signature, and the two pages of each side of a sheet placed side by side on a
+                   landscape tabloid sheet, with crop marks at the outer edges only, since the
+                   middle is folded. Equivalent to `--binding saddle --nup-layout 2x1
+                   --sheet-size 17inx11in --marks outer`, so it can't be combined with the
+                   options which conflict with `--nup-layout`, such as `--grid`.
+      --calendar   A wall calendar bound along the top edge, such as one month to each opening:
+                   all of the sheets are nested in a single signature, and every page is turned
+                   a quarter turn, so that the book is bound along the top edge of the pages and
+                   the backs read the right way up when the leaves are flipped over the top.
+                   Equivalent to `--binding saddle --head-binding`.
+
+Any of the options set by a preset which take a value can be overridden by giving them
+explicitly.";
+
+/// Replaces each preset flag in the arguments with the options it stands for, which are inserted
+/// right after the program name. Since later options take precedence, any options given
+/// explicitly override the preset.
+fn expand_presets(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args = args.into_iter().collect::<Vec<_>>();
+    let mut expansion = Vec::new();
+    for (flag, options) in PRESETS {
+        let len = args.len();
+        args.retain(|arg| arg != flag);
+        if args.len() < len {
+            expansion.extend(options.iter().map(OsString::from));
+        }
+    }
+    let at = args.len().min(1);
+    args.splice(at..at, expansion);
+    args
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let args = Args::parse_from(expand_presets(std::env::args_os()));
+    let args = match args.command {
+        Some(Command::Analyze(analyze_args)) => {
+            logging::init(logging::level(args.quiet, args.verbose))?;
+            return analyze::run(&analyze_args);
+        }
+        Some(Command::Schemes) => {
+            logging::init(logging::level(args.quiet, args.verbose))?;
+            return schemes::run();
+        }
+        Some(Command::Interactive) => match interactive::run()? {
+            Some(args) => Args::parse_from(args),
+            None => return Ok(()),
+        },
+        None => args,
+    };
+    logging::init(logging::level(args.quiet, args.verbose))?;
+    let inputs = if glob::is_pattern(args.input()) {
+        let inputs = glob::expand(args.input())?;
+        info!("Inputs matching {}:", args.input().display());
+        for input in &inputs {
+            info!("  {}", input.display());
+        }
+        inputs
+    } else {
+        vec![args.input().to_path_buf()]
+    };
+    // with `--name-pattern`, the output itself is not written
+    let in_place =
+        args.name_pattern.is_none() && inputs.iter().any(|input| same_file(input, args.output()));
+    if in_place && !args.in_place {
+        bail!(
+            "the output {} is the same file as the input, which would be overwritten; pass \
+             --in-place to replace it",
+            args.output().display()
+        );
+    }
+    if args.embed_fonts && args.output_format == OutputFormat::Ps {
+        bail!("--embed-fonts is not supported with --output-format ps, which only prints the standard fonts");
+    }
+    let mut timer = Timer::start();
+    let mut document = load_inputs(&inputs)?;
+    if args.reverse_input {
+        debug!("Reversing the order of the pages");
+        reverse_pages(&mut document)?;
+    }
+    if let Some(path) = &args.interleave {
+        debug!("Interleaving {}", path.display());
+        interleave(&mut document, Document::load(path)?)?;
+    }
+    if args.split_spreads {
+        debug!("Splitting spreads");
+        split_spreads(&mut document)?;
+    }
+    if let Some(path) = &args.order {
+        let order = parse_order(&std::fs::read_to_string(path)?)
+            .map_err(|err| eyre!("invalid order in {}: {err}", path.display()))?;
+        reorder(&mut document, &order)?;
+    }
+    let passthrough = if args.passthrough_first {
+        Some(detach_first_page(&mut document)?)
+    } else {
+        None
+    };
+    timer.lap("load");
+    let scheme = scheme(&args);
+    debug!("Imposing with the {} scheme", schemes::info(scheme).name);
+    let imposed = match scheme {
+        Scheme::FrenchFold => {
+            prepare(&mut document, &args)?;
+            write_proof(&document, None, &args)?;
+            debug!("Imposing the pages as French folds");
+            Imposed::Sheets(french_fold(&mut document, &args)?)
+        }
+        Scheme::Grid => {
+            let grid = args
+                .grid
+                .expect("the grid scheme is only selected by --grid");
+            prepare(&mut document, &args)?;
+            write_proof(&document, None, &args)?;
+            debug!(
+                "Imposing the pages onto a {}x{} grid",
+                grid.columns, grid.rows
+            );
+            let press_marks = grid::PressMarks {
+                registration: args.registration,
+                color_bar: args.color_bar,
+            };
+            Imposed::Sheets(grid::impose(
+                &mut document,
+                grid,
+                args.grid_gap.points(),
+                press_marks,
+            )?)
+        }
+        Scheme::Folded => {
+            let counts = pad(&mut document, &args)?;
+            timer.lap("pad");
+            write_proof(&document, passthrough, &args)?;
+            let num_pages = Pages(document.page_iter().size_hint().0);
+            debug!("Padded the document to {} pages", num_pages.0);
+            let plan = match &args.apply_plan {
+                Some(path) => report::read_plan(path, num_pages.0)?,
+                None => plan(num_pages, counts.half_sheet, &args)?,
+            };
+            if let Some(layout) = args.nup_layout {
+                layout.check().map_err(|err| eyre!("{err}"))?;
+            }
+            let signature_sheets = signature_sheets(&args.signature_sheet, &plan)?;
+            if let Some(shift) = cover_creep(&args, &plan) {
+                info!(
+                    "Cover creep: front and back covers shifted outwards by {:.2} mm",
+                    shift / POINTS_PER_MM
+                );
+            }
+            if args.require_power_of_two {
+                check_power_of_two(&plan, &signature_params(&args)?)?;
+            }
+            for (index, num_sheets) in plan.thick_signatures(args.max_foldable_sheets) {
+                warn!(
+                    "signature {} has {num_sheets} sheets, which may be too thick to \
+                     fold; consider a smaller --signature-size",
+                    index + 1
+                );
+            }
+            if args.reader_order {
+                for line in reader_order_lines(&plan) {
+                    info!("{line}");
+                }
+            }
+            if let Some(path) = &args.report {
+                report::write(path, &plan)?;
+            }
+            if let Some(path) = &args.dry_run_svg {
+                std::fs::write(path, svg::plan_diagram(&plan))?;
+                return Ok(());
+            }
+            let expected = if args.validate || args.qa_summary.is_some() {
+                source_hashes(&document)?
+            } else {
+                Vec::new()
+            };
+            for (index, num_sheets) in plan.signatures.iter().enumerate() {
+                debug!("Signature {}: {num_sheets} sheets", index + 1);
+            }
+            let mapping = match (&args.mapping, &args.mapping_file) {
+                (Some(text), _) => Some(
+                    parse_mapping(text, num_pages.0)
+                        .map_err(|err| eyre!("invalid mapping: {err}"))?,
+                ),
+                (None, Some(path)) => Some(
+                    parse_mapping(&std::fs::read_to_string(path)?, num_pages.0)
+                        .map_err(|err| eyre!("invalid mapping in {}: {err}", path.display()))?,
+                ),
+                (None, None) => None,
+            };
+            debug!("Imposing the pages");
+            impose(&mut document, &plan, mapping.as_deref(), passthrough, &args)?;
+            if args.validate {
+                validate(&document, &plan, &expected, args.tumble)?;
+            }
+            if let Some(path) = &args.qa_summary {
+                let summary = qa_summary(
+                    &document,
+                    &plan,
+                    mapping.as_deref(),
+                    &counts,
+                    &expected,
+                    args.tumble,
+                );
+                if path.as_os_str() == "-" {
+                    eprintln!("{summary}");
+                } else {
+                    std::fs::write(path, summary + "\n")?;
+                }
+            }
+            if let Some(layout) = args.nup_layout {
+                let cover_pages = if args.cover_file.is_some() { 4 } else { 0 };
+                let settings = |side| {
+                    let signature = side_signature(&plan, layout, cover_pages, side);
+                    SheetSettings {
+                        size: signature
+                            .and_then(|signature| signature_sheets[signature])
+                            .or(args.sheet_size)
+                            .map(Size::points),
+                        margin: args.sheet_margin.points(),
+                        marks: args.marks,
+                        reserve_marks: args.reserve_marks,
+                    }
+                };
+                document = nup_document(&document, layout, settings)?;
+            }
+            Imposed::Folded(counts, plan)
+        }
+    };
+    if let Some(format) = args.xref_format {
+        let version = args
+            .pdf_version
+            .as_deref()
+            .or(args.pdfx.map(PdfX::pdf_version));
+        set_xref_format(&mut document, format, version)?;
+    }
+    if let Some(version) = &args.pdf_version {
+        set_pdf_version(&mut document, version)?;
+    }
+    if args.embed_fonts {
+        let count = pdf::embed_fonts(&mut document)?;
+        debug!("Embedded {count} fonts");
+    }
+    if let Some(standard) = args.pdfx {
+        pdf::add_pdfx(&mut document, standard, &pdf_date(SystemTime::now()))?;
+        for font in pdf::unembedded_fonts(&document) {
+            warn!("font {font} is not embedded, which PDF/X requires");
+        }
+    }
+    if !args.set_meta.is_empty() {
+        set_metadata(&mut document, &args.set_meta, args.custom_meta)?;
+    }
+    if args.strip_metadata {
+        pdf::strip_metadata(&mut document)?;
+        document.prune_objects();
+    }
+    timer.lap("arrange");
+    let mut paths = match (&args.name_pattern, &imposed) {
+        (Some(pattern), Imposed::Folded(_, plan)) => {
+            let stamp = args.stamp_signature_info;
+            save_signatures(
+                &document,
+                plan,
+                pattern,
+                args.output(),
+                args.linearize,
+                stamp,
+                args.embed_fonts,
+            )?
+        }
+        _ => {
+            let path = if in_place {
+                temporary_path(args.output())
+            } else {
+                args.output().to_path_buf()
+            };
+            debug!("Writing {}", path.display());
+            match args.output_format {
+                OutputFormat::Pdf => {
+                    if args.linearize {
+                        pdf::first_page_first(&mut document)?;
+                    }
+                    document.save(&path)?;
+                }
+                OutputFormat::Ps => {
+                    std::fs::write(&path, postscript::to_postscript(&document)?)?;
+                }
+            }
+            vec![path]
+        }
+    };
+    if let Some(max_bytes) = args.max_output_bytes {
+        for path in &paths {
+            check_output_size(path, max_bytes)?;
+        }
+    }
+    if in_place {
+        debug!("Replacing {}", args.output().display());
+        std::fs::rename(&paths[0], args.output())?;
+        args.output().clone_into(&mut paths[0]);
+    }
+    timer.lap("save");
+
+    match imposed {
+        Imposed::Folded(counts, plan) => print_summary(&args, counts, &plan)?,
+        Imposed::Sheets(num_sheets) => info!("Number of sheets:          {num_sheets}"),
+    }
+    if args.timings {
+        timer.print();
+    }
+    #[cfg(feature = "open")]
+    if args.open {
+        open_output(&paths[0]);
+    }
+    Ok(())
+}
+
+/// The imposition scheme selected by the options.
+fn scheme(args: &Args) -> Scheme {
+    if args.grid.is_some() {
+        Scheme::Grid
+    } else if args.french_fold {
+        Scheme::FrenchFold
+    } else {
+        Scheme::Folded
+    }
+}
+
+/// Prints the statistics of a folded imposition, and writes the colophon and instructions if
+/// requested.
+fn print_summary(args: &Args, counts: PageCounts, plan: &SignaturePlan) -> color_eyre::Result<()> {
+    let PageCounts {
+        non_blank: num_pages,
+        blank: blanks_needed,
+        trimmed,
+        ..
+    } = counts;
+    info!("Number of non-blank pages: {num_pages}");
+    info!("Number of blank pages:     {blanks_needed}");
+    if args.trim_trailing_blanks {
+        info!("Trailing blanks removed:   {trimmed}");
+    }
+    info!("Number of sheets:          {}", plan.num_sheets().0);
+    info!("Number of signatures:      {}", plan.signatures.len());
+    match signature_params(args)?.signature_size {
+        _ if !args.vary_signatures.is_empty() => info!(
+            "Sheets per signature:      varying, {}",
+            args.vary_signatures
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        0 => info!("Sheets per signature:      all"),
+        signature_size => info!("Sheets per signature:      {signature_size}"),
+    }
+    if let (Some(leaves), Some(sections)) = (args.leaves_per_section, args.sections_per_gathering) {
+        info!("Sections per signature:    {sections}, of {leaves} leaves");
+    }
+    info!(
+        "Sheets in last signature:  {}",
+        plan.signatures.last().copied().unwrap_or(0)
+    );
+    if let Some(caliper) = args.paper_caliper {
+        let spine = spine_width(
+            plan.num_leaves(),
+            caliper.points().into(),
+            args.cover_allowance.points().into(),
+        );
+        info!(
+            "Spine width:               {:.2} mm",
+            spine / f64::from(POINTS_PER_MM)
+        );
+    }
+
+    if let Some(path) = &args.colophon {
+        let lines = colophon_lines(args, num_pages, blanks_needed, plan);
+        save_generated(pdf::text_document(&lines, pdf::LETTER)?, path, args)?;
+    }
+    if let Some(path) = &args.instructions {
+        let document = instructions::document(plan, args.signature_params.binding)?;
+        save_generated(document, path, args)?;
+    }
+    Ok(())
+}
+
+/// Opens the output in the default viewer, without waiting for the viewer to exit. Failures are
+/// only warnings, since the output has already been written.
+#[cfg(feature = "open")]
+fn open_output(path: &Path) {
+    use std::io::IsTerminal;
+
+    let has_display = cfg!(not(unix))
+        || cfg!(target_os = "macos")
+        || std::env::var_os("DISPLAY").is_some()
+        || std::env::var_os("WAYLAND_DISPLAY").is_some();
+    if !has_display || !std::io::stdin().is_terminal() {
+        warn!("not opening the output, since this is not an interactive session");
+        return;
+    }
+    if let Err(err) = opener::open(path) {
+        warn!("could not open the output: {err}");
+    }
+}
+
+/// Plans the signatures for a document padded to `num_pages` pages. With `half_sheet`, the last
+/// sheet only holds a single leaf, and is a signature of its own after the others.
+fn plan(num_pages: Pages, half_sheet: bool, args: &Args) -> color_eyre::Result<SignaturePlan> {
+    let num_sheets = num_pages.sheets_needed();
+    let folded_sheets = Sheets(num_sheets.0 - usize::from(half_sheet));
+    let params = signature_params(args)?;
+    let plan = if args.vary_signatures.is_empty() {
+        check_num_signatures(folded_sheets, &params).map_err(|err| eyre!("{err}"))?;
+        plan_signatures(folded_sheets, params)
+    } else {
+        plan_pattern(folded_sheets, &args.vary_signatures, params)
+            .map_err(|err| eyre!("invalid --vary-signatures: {err}"))?
+    };
+    let mut plan = match args.merge_below {
+        Some(min_sheets) => merge_small_signatures(&plan, min_sheets, args.max_foldable_sheets),
+        None => plan,
+    };
+    if half_sheet {
+        plan.signatures.retain(|&num_sheets| num_sheets > 0);
+        plan.signatures.push(1);
+    }
+    plan.rtl_signatures = rtl_signatures(&args.signature_rtl, &plan)?;
+    Ok(plan)
+}
+
+/// The signature parameters, with the signature size of a gathering of sections with
+/// `--leaves-per-section` and `--sections-per-gathering`, which are a shorthand for
+/// `--signature-size`. Nesting the sections gives the same order as a single signature of all of
+/// their sheets, so each gathering is planned as a signature.
+fn signature_params(args: &Args) -> color_eyre::Result<SignatureParams> {
+    let mut params = args.signature_params;
+    if let (Some(leaves), Some(sections)) = (args.leaves_per_section, args.sections_per_gathering) {
+        if leaves == 0 || !leaves.is_multiple_of(2) {
+            bail!(
+                "--leaves-per-section must be a positive multiple of 2, the number of leaves on \
+                 each sheet, not {leaves}"
+            );
+        }
+        if sections == 0 {
+            bail!("--sections-per-gathering must be at least 1");
+        }
+        params.signature_size = sections * leaves / 2;
+    }
+    Ok(params)
+}
+
+/// The number of pages of each kind in the padded document.
+#[derive(Debug)]
+struct PageCounts {
+    /// Pages from the input.
+    non_blank: usize,
+    /// Pages added by padding, including end pages and endpapers.
+    blank: usize,
+    /// Blank pages removed from the end of the input by `--trim-trailing-blanks`.
+    trimmed: usize,
+    /// Whether the last sheet only holds a single leaf, with `--min-fold 2`. The blank pages on the
+    /// other half of the sheet, which is cut off, are counted in `blank`.
+    half_sheet: bool,
+}
+
+/// The result of imposing the document, for the summary.
+enum Imposed {
+    /// Folded into signatures, with the counts of the input's pages and the plan of the signatures.
+    Folded(PageCounts, SignaturePlan),
+    /// Placed on sheets which are not folded into signatures, with the number of sheets.
+    Sheets(usize),
+}
+
+/// Prepares the pages of the document for imposition, clipping each to the placed box and
+/// applying the other options which change the input's pages.
+fn prepare(document: &mut Document, args: &Args) -> color_eyre::Result<()> {
+    if args.dedup_warn {
+        warn_duplicate_pages(document, blank_tolerance(args))?;
+    }
+    if args.drop_tags {
+        pdf::drop_structure(document)?;
+    } else if pdf::is_tagged(document) {
+        warn!(
+            "the document is tagged, and imposing it will leave its structure tree \
+             referring to the wrong pages; use --drop-tags to remove it"
+        );
+    }
+    if args.sanitize_content {
+        sanitize_content(document);
+    }
+    if args.normalize_rotation {
+        for page_id in document.page_iter().collect::<Vec<_>>() {
+            pdf::bake_rotation(document, page_id)?;
+        }
+    }
+    for page_id in document.page_iter().collect::<Vec<_>>() {
+        if let Some(placed_box) = args.placed_box {
+            pdf::clip_to_box(document, page_id, placed_box)?;
+        }
+        if args.normalize_origin {
+            pdf::normalize_origin(document, page_id)?;
+        }
+        if let Some(size) = args.book_size {
+            pdf::scale_to_size(document, page_id, size.points(), args.book_fit)?;
+        }
+    }
+    Ok(())
+}
+
+/// Balances the graphics state operators of each page. See [`pdf::balance_graphics_state`].
+fn sanitize_content(document: &mut Document) {
+    for (index, page_id) in document
+        .page_iter()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .enumerate()
+    {
+        match pdf::balance_graphics_state(document, page_id) {
+            Ok(0) => {}
+            Ok(fixed) => debug!(
+                "Balanced the graphics state of page {}, changing {fixed} operators",
+                index + 1
+            ),
+            Err(err) => warn!(
+                "could not parse the content of page {}, so it is left as it is: {err}",
+                index + 1
+            ),
+        }
+    }
+}
+
+/// Prepares the pages of the document and adds blank pages so that it fills a whole number of
+/// sheets.
+fn pad(document: &mut Document, args: &Args) -> color_eyre::Result<PageCounts> {
+    prepare(document, args)?;
+    let template = blank_template(document, args.blank_template)?;
+    let blank = Fill::Blank(args.blank_style);
+    let padding = match args.pad_with {
+        PadWith::Blank => None,
+        PadWith::Page(number) => Some(
+            document
+                .get_pages()
+                .get(&number)
+                .copied()
+                .ok_or_else(|| eyre!("page {number} to pad with does not exist"))?,
+        ),
+    };
+    let trimmed = if args.trim_trailing_blanks {
+        trim_trailing_blanks(document, blank_tolerance(args))?
+    } else {
+        0
+    };
+    let end_pages = if args.end_pages { 2 } else { 0 };
+    // the first page is a verso when an odd number of pages come before it, and the endpapers
+    // come in pairs, so an end page at the front already puts it on a verso
+    let verso_blanks = match args.first_page {
+        FirstPage::Recto => 0,
+        FirstPage::Verso => usize::from(!args.end_pages),
+    };
+    let num_pages = document.page_iter().size_hint().0 + end_pages + verso_blanks;
+    // the document can end with half of a sheet with `--min-fold 2`
+    let modulus = match args.min_fold {
+        2 => 2,
+        _ => 4,
+    };
+    let (front_endpapers, back_endpapers) =
+        endpapers_needed(num_pages, args.min_pages.unwrap_or(0), modulus);
+    // round pages up
+    let blanks_needed = num_pages.next_multiple_of(modulus) - num_pages;
+    if args.no_pad && blanks_needed > 0 {
+        bail!(
+            "the document has {num_pages} pages, which is {blanks_needed} pages short of a whole \
+             number of sheets of {modulus} pages; add pages, or leave out --no-pad to fill the \
+             last sheet with blank pages"
+        );
+    }
+    let inserted = end_pages + verso_blanks + front_endpapers + back_endpapers + blanks_needed;
+    if let Some(max_padding) = args
+        .max_padding
+        .filter(|&max_padding| inserted > max_padding)
+    {
+        bail!(
+            "padding would insert {inserted} pages ({end_pages} end pages, {} endpapers and \
+             {blanks_needed} pages to fill the last sheet), which is more than the \
+             --max-padding of {max_padding}; check the page count and the padding options",
+            verso_blanks + front_endpapers + back_endpapers
+        );
+    }
+
+    // the blank before a verso first page goes directly before it, inside the end pages
+    if verso_blanks > 0 {
+        trace!("Adding a blank page before the first page, which is a verso");
+    }
+    add_pages(document, template, verso_blanks, true, blank)?;
+    if args.end_pages {
+        add_pages(document, template, 1, true, blank)?;
+        add_pages(document, template, 1, false, blank)?;
+    }
+    if front_endpapers + back_endpapers > 0 {
+        trace!("Adding {front_endpapers} endpapers at the front and {back_endpapers} at the back");
+    }
+    add_pages(document, template, front_endpapers, true, blank)?;
+    add_pages(document, template, back_endpapers, false, blank)?;
+    if blanks_needed > 0 {
+        trace!("Adding {blanks_needed} pages to fill the last sheet");
+    }
+    match padding {
+        Some(page) => add_pages(document, Some(page), blanks_needed, false, Fill::Template)?,
+        None => add_pages(document, template, blanks_needed, false, blank)?,
+    }
+    let half_sheet = !document.page_iter().count().is_multiple_of(4);
+    if half_sheet {
+        trace!("Adding 2 pages for the half of the last sheet which is cut off");
+        add_pages(document, template, 2, false, blank)?;
+    }
+
+    let trim = pdf::Trim {
+        head: args.trim_head.unwrap_or(args.trim).points(),
+        foot: args.trim_foot.unwrap_or(args.trim).points(),
+        fore_edge: args.trim_fore.unwrap_or(args.trim).points(),
+    };
+    if trim != pdf::Trim::default() {
+        let rtl = args.signature_params.writing_mode.is_rtl();
+        for (index, page_id) in document.page_iter().enumerate().collect::<Vec<_>>() {
+            // pages with an even index are rectos, whose fore-edge is on the right in a
+            // left-to-right book
+            let recto = index % 2 == 0;
+            pdf::trim_page(document, page_id, trim, recto != rtl)?;
+        }
+    }
+
+    let mut counts = PageCounts {
+        non_blank: num_pages - verso_blanks,
+        blank: blanks_needed + verso_blanks + front_endpapers + back_endpapers,
+        trimmed,
+        half_sheet,
+    };
+    if half_sheet {
+        counts.blank += 2;
+    }
+    if args.end_pages {
+        counts.non_blank -= 2;
+        counts.blank += 2;
+    }
+    Ok(counts)
+}
+
+/// The tolerance for [`pdf::is_blank`] from `--blank-tolerance`, in square points.
+fn blank_tolerance(args: &Args) -> f32 {
+    args.blank_tolerance.points().powi(2)
+}
+
+/// Removes blank pages from the end of the document, always leaving at least one page. Returns the
+/// number of pages removed.
+fn trim_trailing_blanks(document: &mut Document, tolerance: f32) -> color_eyre::Result<usize> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut len = page_ids.len();
+    while len > 1 && pdf::is_blank(document, page_ids[len - 1], tolerance)? {
+        trace!("Removing blank page {len} from the end");
+        len -= 1;
+    }
+    if len < page_ids.len() {
+        pdf::set_page_order(document, &page_ids[..len])?;
+    }
+    Ok(page_ids.len() - len)
+}
+
+/// Rearranges the pages of the padded document into the order they are printed in, or by the
+/// mapping from `--mapping`, which gives the source page of each output page. The `passthrough`
+/// page from [`detach_first_page`] is put back in front of the imposed pages.
+///
+/// The page objects are moved into their new positions rather than copied, so only one copy of
+/// each page dictionary exists at a time. Content streams and resources are shared between the
+/// old and new positions, and are never copied. The whole input document is still held in memory,
+/// since it is loaded and saved in one piece.
+fn impose(
+    document: &mut Document,
+    plan: &SignaturePlan,
+    mapping: Option<&[usize]>,
+    passthrough: Option<ObjectId>,
+    args: &Args,
+) -> color_eyre::Result<()> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut sources = vec![None; page_ids.len()];
+    match mapping {
+        Some(mapping) => mapping
+            .iter()
+            .enumerate()
+            .for_each(|(dest, &src)| sources[dest] = Some(src)),
+        None => arrange_pages_with(plan, |src, dest| sources[dest] = Some(src)),
+    }
+    let sources = sources
+        .into_iter()
+        .enumerate()
+        .map(|(dest, src)| {
+            src.ok_or_else(|| eyre!("no page is placed at output page {}", dest + 1))
+        })
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+    // each position keeps the parent of the page that was there before
+    let parents = page_ids
+        .iter()
+        .map(|&id| {
+            document
+                .get_dictionary(id)
+                .ok()?
+                .get(b"Parent")
+                .ok()
+                .cloned()
+        })
+        .collect::<Vec<_>>();
+    let date = pdf_date(SystemTime::now());
+    // the pages are moved one cycle of the permutation at a time, so that only one page is held
+    // outside of the document at once
+    for cycle in permutation_cycles(&sources) {
+        let take_page = |document: &mut Document, index: usize| {
+            document
+                .objects
+                .remove(&page_ids[index])
+                .ok_or_else(|| eyre!("page {:?} is missing", page_ids[index]))
+        };
+        let mut first = take_page(document, cycle[0])?;
+        for (i, &dest) in cycle.iter().enumerate() {
+            let mut page = match cycle.get(i + 1) {
+                Some(&next) => take_page(document, next)?,
+                None => std::mem::replace(&mut first, Object::Null),
+            };
+            let src = sources[dest];
+            if let Ok(dict) = page.as_dict_mut() {
+                match &parents[dest] {
+                    Some(parent) => dict.set("Parent", parent.clone()),
+                    None => {
+                        dict.remove(b"Parent");
+                    }
+                }
+                if args.annotate_provenance {
+                    pdf::set_piece_info(dict, provenance(src, plan.slot_position(dest)), &date);
+                }
+            }
+            document.objects.insert(page_ids[dest], page);
+            trace!("Page {} is output page {}", src + 1, dest + 1);
+        }
+    }
+    if let Some(path) = &args.cover_file {
+        let creep = cover_creep(args, plan).unwrap_or(0.0);
+        let cover = cover_sheet(document, Document::load(path)?, plan, creep)?;
+        let page_ids = cover
+            .into_iter()
+            .chain(document.page_iter())
+            .collect::<Vec<_>>();
+        pdf::set_page_order(document, &page_ids)?;
+    }
+    if args.head_binding {
+        head_binding(document, plan.writing_mode.is_rtl())?;
+    }
+    if args.tumble {
+        tumble(document)?;
+    }
+    if plan.writing_mode.is_rtl() {
+        pdf::set_direction_rtl(document)?;
+    }
+    if let Some(pass) = args.pass {
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let pass_ids = pass_pages(page_ids.len(), pass)
+            .into_iter()
+            .map(|page| page_ids[page])
+            .collect::<Vec<_>>();
+        pdf::set_page_order(document, &pass_ids)?;
+    }
+    if args.blank_backs {
+        blank_backs(document)?;
+    }
+    if let Some(page_id) = passthrough {
+        let page_ids = std::iter::once(page_id)
+            .chain(document.page_iter())
+            .collect::<Vec<_>>();
+        pdf::set_page_order(document, &page_ids)?;
+    }
+    if args.pass.is_some() || args.drop_tags {
+        document.prune_objects();
+    }
+    Ok(())
+}
+
+/// Adds a blank side after each side of the sheets. See [`blank_back_pages`].
+fn blank_backs(document: &mut Document) -> color_eyre::Result<()> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let pages = blank_back_pages(page_ids.len());
+    let num_blanks = pages.iter().filter(|page| page.is_none()).count();
+    let blank = Fill::Blank(BlankStyle::Empty);
+    add_pages(document, None, num_blanks, false, blank)?;
+    let blank_ids = document
+        .page_iter()
+        .skip(page_ids.len())
+        .collect::<Vec<_>>();
+    let mut blank_ids = blank_ids.into_iter();
+    let page_ids = pages
+        .into_iter()
+        .map(|page| match page {
+            Some(page) => page_ids[page],
+            None => blank_ids.next().expect("blank pages were added"),
+        })
+        .collect::<Vec<_>>();
+    pdf::set_page_order(document, &page_ids)?;
+    Ok(())
+}
+
+/// Turns every page a quarter turn for `--head-binding`, which makes the book one bound on the
+/// side, turned on its side with the verso of each opening above the recto. The pages are turned
+/// anticlockwise, which puts the left-hand pages on top, or clockwise if the book is read from
+/// right to left.
+fn head_binding(document: &mut Document, rtl: bool) -> color_eyre::Result<()> {
+    let turn = if rtl { 90 } else { 270 };
+    for page_id in document.page_iter().collect::<Vec<_>>() {
+        let rotate = (pdf::rotation(document, page_id) + turn) % 360;
+        document.get_dictionary_mut(page_id)?.set("Rotate", rotate);
+    }
+    Ok(())
+}
+
+/// Turns the back of each sheet upside down. See [`tumble_position`].
+fn tumble(document: &mut Document) -> color_eyre::Result<()> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut tumbled = page_ids.clone();
+    for (page, &page_id) in page_ids.iter().enumerate() {
+        let (position, rotated) = tumble_position(page);
+        if rotated {
+            let rotate = (pdf::rotation(document, page_id) + 180) % 360;
+            document.get_dictionary_mut(page_id)?.set("Rotate", rotate);
+        }
+        tumbled[position] = page_id;
+    }
+    pdf::set_page_order(document, &tumbled)?;
+    Ok(())
+}
+
+/// Imposes the pages as French folds, four to a side of each sheet, after adding blank pages to
+/// fill the last card. Returns the number of sheets. See [`french_fold_source`].
+fn french_fold(document: &mut Document, args: &Args) -> color_eyre::Result<usize> {
+    let num_pages = document.page_iter().count();
+    let blank = Fill::Blank(args.blank_style);
+    add_pages(
+        document,
+        None,
+        num_pages.next_multiple_of(4) - num_pages,
+        false,
+        blank,
+    )?;
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut slots = Vec::new();
+    for slot in 0..page_ids.len() {
+        let (page, upside_down) = french_fold_source(slot, args.signature_params.writing_mode);
+        let page_id = page_ids[page];
+        if upside_down {
+            let rotate = (pdf::rotation(document, page_id) + 180) % 360;
+            document.get_dictionary_mut(page_id)?.set("Rotate", rotate);
+        }
+        trace!("Page {} is in slot {} of its sheet", page + 1, slot % 4 + 1);
+        slots.push(page_id);
+    }
+    pdf::set_page_order(document, &slots)?;
+    let grid = grid::Grid {
+        columns: 2,
+        rows: 2,
+    };
+    grid::impose(document, grid, 0.0, grid::PressMarks::default())
+}
+
+/// The content hash of each page of the padded document, in order, or `None` for blank pages,
+/// which can't be told apart. See [`validate`].
+fn source_hashes(document: &Document) -> color_eyre::Result<Vec<Option<u64>>> {
+    let mut hashes = Vec::new();
+    for page_id in document.page_iter() {
+        // pages with any marks can be told apart, whatever `--blank-tolerance` is
+        hashes.push(if pdf::is_blank(document, page_id, 0.0)? {
+            None
+        } else {
+            Some(pdf::content_hash(document, page_id)?)
+        });
+    }
+    Ok(hashes)
+}
+
+/// Checks that each page of the imposed document has the content of the source page that the
+/// plan puts there, given the [`source_hashes`] of the padded document before it was imposed. See
+/// [`misplaced_pages`].
+fn validate(
+    document: &Document,
+    plan: &SignaturePlan,
+    expected: &[Option<u64>],
+    tumble: bool,
+) -> color_eyre::Result<()> {
+    let misplaced = misplaced_pages(document, plan, None, expected, tumble)?;
+    if !misplaced.is_empty() {
+        let misplaced = misplaced
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!("validation failed: pages {misplaced} are not where the plan puts them");
+    }
+    Ok(())
+}
+
+/// The pages of the padded document, numbered from 1, which are not on the output page that
+/// `mapping`, or the plan if there is none, puts them on, given the [`source_hashes`] of the
+/// padded document before it was imposed. Blank source pages are not checked. Any cover sheet is
+/// expected before the imposed pages, and `tumble` should be set if the backs of the sheets were
+/// turned with [`tumble`].
+fn misplaced_pages(
+    document: &Document,
+    plan: &SignaturePlan,
+    mapping: Option<&[usize]>,
+    expected: &[Option<u64>],
+    tumble: bool,
+) -> color_eyre::Result<Vec<usize>> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let Some(cover_pages) = page_ids.len().checked_sub(plan.num_pages().0) else {
+        bail!(
+            "validation failed: the output has {} pages, but the plan has {}",
+            page_ids.len(),
+            plan.num_pages().0
+        );
+    };
+    let mut placed = Vec::new();
+    match mapping {
+        Some(mapping) => placed.extend(mapping.iter().enumerate().map(|(dest, &src)| (src, dest))),
+        None => arrange_pages_with(plan, |src, dest| placed.push((src, dest))),
+    }
+    let mut misplaced = Vec::new();
+    for (src, dest) in placed {
+        let dest = if tumble {
+            tumble_position(dest).0
+        } else {
+            dest
+        };
+        let Some(hash) = expected[src] else {
+            continue;
+        };
+        let page_id = page_ids[cover_pages + dest];
+        if pdf::content_hash(document, page_id).ok() != Some(hash) {
+            misplaced.push(src + 1);
+        }
+    }
+    Ok(misplaced)
+}
+
+/// The line written by `--qa-summary` for the imposed document, given the [`source_hashes`] of the
+/// padded document before it was imposed. See [`report::qa_summary`].
+fn qa_summary(
+    document: &Document,
+    plan: &SignaturePlan,
+    mapping: Option<&[usize]>,
+    counts: &PageCounts,
+    expected: &[Option<u64>],
+    tumble: bool,
+) -> String {
+    let permutation = misplaced_pages(document, plan, mapping, expected, tumble)
+        .is_ok_and(|misplaced| misplaced.is_empty());
+    report::qa_summary(
+        counts.non_blank,
+        counts.blank,
+        document.page_iter().count(),
+        plan,
+        permutation,
+    )
+}
+
+/// Loads the input files, joining them into one document in order.
+fn load_inputs(paths: &[PathBuf]) -> color_eyre::Result<Document> {
+    let (first, rest) = paths.split_first().ok_or_else(|| eyre!("no input files"))?;
+    debug!("Loading {}", first.display());
+    let mut document = Document::load(first)?;
+    if rest.is_empty() {
+        return Ok(document);
+    }
+    let mut page_ids = document.page_iter().collect::<Vec<_>>();
+    for path in rest {
+        debug!("Loading {}", path.display());
+        page_ids.extend(pdf::merge_document(&mut document, Document::load(path)?)?);
+    }
+    pdf::set_page_order(&mut document, &page_ids)?;
+    document.prune_objects();
+    Ok(document)
+}
+
+/// Merges another document into the document, alternating between pages of each, starting with
+/// the first page of `document`.
+fn interleave(document: &mut Document, other: Document) -> color_eyre::Result<()> {
+    let num_pages = document.get_pages().len();
+    let other_pages = other.get_pages().len();
+    if num_pages != other_pages {
+        bail!(
+            "documents to interleave must have the same number of pages, but they have \
+             {num_pages} and {other_pages} pages"
+        );
+    }
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let other_ids = pdf::merge_document(document, other)?;
+    let interleaved = page_ids
+        .into_iter()
+        .zip(other_ids)
+        .flat_map(|(page, other_page)| [page, other_page])
+        .collect::<Vec<_>>();
+    pdf::set_page_order(document, &interleaved)?;
+    Ok(())
+}
+
+/// Removes the first page from the document's page tree, for `--passthrough-first`, returning its
+/// id so that it can be put back in front of the imposed pages. The page itself is left in the
+/// document unchanged. There must be at least one other page to impose.
+fn detach_first_page(document: &mut Document) -> color_eyre::Result<ObjectId> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let [first, rest @ ..] = &page_ids[..] else {
+        bail!("the document has no pages to pass through");
+    };
+    if rest.is_empty() {
+        bail!("--passthrough-first needs at least one page to impose after the first");
+    }
+    pdf::set_page_order(document, rest)?;
+    Ok(*first)
+}
+
+/// Writes the reading-order proof to the path given by `--proof`, if any. See [`proof_document`].
+fn write_proof(
+    document: &Document,
+    passthrough: Option<ObjectId>,
+    args: &Args,
+) -> color_eyre::Result<()> {
+    if let Some(path) = &args.proof {
+        debug!("Writing the proof to {}", path.display());
+        save_generated(proof_document(document, passthrough)?, path, args)?;
+    }
+    Ok(())
+}
+
+/// Saves a document besides the output, embedding the fonts of the text drawn by the tool with
+/// `--embed-fonts`.
+fn save_generated(mut document: Document, path: &Path, args: &Args) -> color_eyre::Result<()> {
+    if args.embed_fonts {
+        pdf::embed_fonts(&mut document)?;
+    }
+    document.save(path)?;
+    Ok(())
+}
+
+/// A copy of the prepared and padded document, before it is imposed, with any page passed through
+/// by `--passthrough-first` back in front, so that the pages are in reading order.
+fn proof_document(
+    document: &Document,
+    passthrough: Option<ObjectId>,
+) -> color_eyre::Result<Document> {
+    let mut proof = document.clone();
+    if let Some(page_id) = passthrough {
+        let page_ids = std::iter::once(page_id)
+            .chain(proof.page_iter())
+            .collect::<Vec<_>>();
+        pdf::set_page_order(&mut proof, &page_ids)?;
+    }
+    proof.prune_objects();
+    Ok(proof)
+}
+
+/// Reverses the order of the pages of the document.
+fn reverse_pages(document: &mut Document) -> color_eyre::Result<()> {
+    let mut page_ids = document.page_iter().collect::<Vec<_>>();
+    page_ids.reverse();
+    pdf::set_page_order(document, &page_ids)?;
+    Ok(())
+}
+
+/// Splits each page which is wider than it is tall, as displayed, into two pages. See
+/// [`pdf::split_page`].
+fn split_spreads(document: &mut Document) -> color_eyre::Result<()> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut split = Vec::with_capacity(2 * page_ids.len());
+    for (index, page_id) in page_ids.into_iter().enumerate() {
+        let [x0, y0, x1, y1] = pdf::placed_box(document, page_id, pdf::PlacedBox::Crop)
+            .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+        let (width, height) = match pdf::rotation(document, page_id) {
+            90 | 270 => (y1 - y0, x1 - x0),
+            _ => (x1 - x0, y1 - y0),
+        };
+        split.push(page_id);
+        if width > height {
+            split.push(pdf::split_page(document, page_id)?);
+        } else {
+            trace!("Page {} is not a spread, so it is left whole", index + 1);
+        }
+    }
+    pdf::set_page_order(document, &split)?;
+    Ok(())
+}
+
+/// Sets entries of the document information, checking that the keys are standard entries unless
+/// `custom` is set. `Trapped` is written as a name, and everything else as text.
+fn set_metadata(
+    document: &mut Document,
+    entries: &[(String, String)],
+    custom: bool,
+) -> color_eyre::Result<()> {
+    for (key, value) in entries {
+        if !custom && !pdf::INFO_KEYS.contains(&key.as_str()) {
+            bail!(
+                "{key} is not a standard document information entry ({}); pass --custom-meta \
+                 to set it anyway",
+                pdf::INFO_KEYS.join(", ")
+            );
+        }
+        let value = match key.as_str() {
+            "Trapped" => match value.as_str() {
+                "True" | "False" | "Unknown" => Object::from(value.as_str()),
+                _ => bail!("Trapped must be True, False, or Unknown, not {value:?}"),
+            },
+            _ => pdf::text_string(value),
+        };
+        pdf::set_info(document, key, value)?;
+    }
+    Ok(())
+}
+
+/// Checks that every signature has a power of two number of pages, for `--require-power-of-two`.
+/// The error lists the signatures which don't, and suggests how to plan the same sheets so that
+/// they all do: with the power of two signature size nearest to the one the plan was made with
+/// (`params`, as given by [`signature_params`]) and a short last signature, and, if that still
+/// leaves one which doesn't, with blank pages added to make a number of sheets which works.
+fn check_power_of_two(plan: &SignaturePlan, params: &SignatureParams) -> color_eyre::Result<()> {
+    let irregular = plan.non_power_of_two_signatures();
+    if irregular.is_empty() {
+        return Ok(());
+    }
+    let listed = irregular
+        .iter()
+        .map(|&(index, num_sheets)| {
+            let num_pages = Sheets(num_sheets).pages().0;
+            format!("{} ({num_sheets} sheets, {num_pages} pages)", index + 1)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let num_sheets = plan.num_sheets().0;
+    let mut suggested = *params;
+    let mut options = Vec::new();
+    if params.binding == Binding::Perfect {
+        let size = match (params.signature_size, params.max_signature_sheets) {
+            (0, None) => num_sheets,
+            (0, Some(max_sheets)) => max_sheets,
+            (size, max_sheets) => size.min(max_sheets.unwrap_or(size)),
+        };
+        // every fold has a power of two pages on each sheet, so the sheets must be a power of two
+        let size = size.max(1);
+        let below = 1 << size.ilog2();
+        suggested.signature_size = if size - below <= below * 2 - size {
+            below
+        } else {
+            below * 2
+        };
+        suggested.remainder = RemainderStrategy::Short;
+        suggested.max_signature_sheets = None;
+        suggested.num_signatures = None;
+        if suggested.signature_size != params.signature_size || params.num_signatures.is_some() {
+            options.push(format!("--signature-size {}", suggested.signature_size));
+        }
+        if params.remainder != RemainderStrategy::Short {
+            options.push("--remainder short".to_owned());
+        }
+    }
+    // the fewest sheets, starting from those in the plan, which the suggestion plans in signatures
+    // of a power of two pages, which a whole signature of the suggested size always is
+    let padded_sheets = (num_sheets..)
+        .find(|&num_sheets| {
+            plan_signatures(Sheets(num_sheets), suggested)
+                .non_power_of_two_signatures()
+                .is_empty()
+        })
+        .expect("a whole number of signatures of a power of two sheets is found");
+    let (noun, verb) = if irregular.len() == 1 {
+        ("signature", "does")
+    } else {
+        ("signatures", "do")
+    };
+    let padding = format!(
+        "blank pages to make {} pages",
+        Sheets(padded_sheets).pages().0
+    );
+    let suggestion = match (&options[..], padded_sheets > num_sheets) {
+        // the plan was not made from the signature size, such as with `--vary-signatures`
+        ([], false) => format!(
+            "try --signature-size {} --remainder short in place of the other signature options",
+            suggested.signature_size
+        ),
+        ([], true) => format!("add {padding}"),
+        (options, false) => format!("try {}", options.join(" ")),
+        (options, true) => format!("try {}, with {padding}", options.join(" ")),
+    };
+    bail!("{noun} {listed} {verb} not have a power of two number of pages; {suggestion}");
+}
+
+/// Converts the numbers of the signatures given to `--signature-rtl` to indices, checking that
+/// the plan has those signatures.
+fn rtl_signatures(numbers: &[usize], plan: &SignaturePlan) -> color_eyre::Result<Vec<usize>> {
+    let num_signatures = plan.signatures.len();
+    numbers
+        .iter()
+        .map(|&number| match number {
+            1.. if number <= num_signatures => Ok(number - 1),
+            _ => bail!(
+                "signature {number} given to --signature-rtl does not exist, since there are \
+                 {num_signatures} signatures"
+            ),
+        })
+        .collect()
+}
+
+/// The size given to `--signature-sheet` for each signature of the plan, if any, checking that
+/// the plan has those signatures.
+fn signature_sheets(
+    sheets: &[(usize, Size)],
+    plan: &SignaturePlan,
+) -> color_eyre::Result<Vec<Option<Size>>> {
+    let num_signatures = plan.signatures.len();
+    let mut sizes = vec![None; num_signatures];
+    for &(number, size) in sheets {
+        match number {
+            1.. if number <= num_signatures => sizes[number - 1] = Some(size),
+            _ => bail!(
+                "signature {number} given to --signature-sheet does not exist, since there are \
+                 {num_signatures} signatures"
+            ),
+        }
+    }
+    Ok(sizes)
+}
+
+/// The signature printed on the `--nup-layout` page with the given index, or `None` for the
+/// sheet added by `--cover-file`, which has the given number of pages.
+fn side_signature(
+    plan: &SignaturePlan,
+    layout: NupLayout,
+    cover_pages: usize,
+    side: usize,
+) -> Option<usize> {
+    let dest = (side * layout.pages_per_side()).checked_sub(cover_pages)?;
+    Some(plan.slot_position(dest).signature)
+}
+
+/// Parses a list of page numbers for `--order`.
+fn parse_order(text: &str) -> Result<Vec<usize>, String> {
+    let mut order = Vec::new();
+    for line in text.lines() {
+        let line = line.split_once('#').map_or(line, |(line, _comment)| line);
+        for item in line.split([',', ' ', '\t']).filter(|item| !item.is_empty()) {
+            let number = |number: &str| match number.parse() {
+                Ok(number) if number > 0 => Ok(number),
+                _ => Err(format!(
+                    "expected a page number starting from 1, not {number:?}"
+                )),
+            };
+            match item.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (number(first)?, number(last)?);
+                    if first <= last {
+                        order.extend(first..=last);
+                    } else {
+                        order.extend((last..=first).rev());
+                    }
+                }
+                None => order.push(number(item)?),
+            }
+        }
+    }
+    if order.is_empty() {
+        return Err("no pages are listed".to_owned());
+    }
+    Ok(order)
+}
+
+/// Parses a mapping for `--mapping`, returning the index of the source page for each output
+/// page. Each item is either `dest=src` or, for a list in output order, just `src`, and the
+/// mapping must be a permutation of the `num_pages` pages.
+fn parse_mapping(text: &str, num_pages: usize) -> Result<Vec<usize>, String> {
+    let number = |number: &str| match number.trim().parse() {
+        Ok(number) if (1..=num_pages).contains(&number) => Ok(number - 1),
+        _ => Err(format!(
+            "expected a page number from 1 to {num_pages}, not {number:?}"
+        )),
+    };
+    let mut mapping = vec![None; num_pages];
+    let (mut pairs, mut listed) = (false, 0);
+    for line in text.lines() {
+        let line = line.split_once('#').map_or(line, |(line, _comment)| line);
+        for item in line.split([',', ' ', '\t']).filter(|item| !item.is_empty()) {
+            let (dest, src) = match item.split_once('=') {
+                Some((dest, src)) => {
+                    pairs = true;
+                    (number(dest)?, number(src)?)
+                }
+                None => {
+                    listed += 1;
+                    (listed - 1, number(item)?)
+                }
+            };
+            if pairs && listed > 0 {
+                return Err("pairs and a list of pages can't be mixed".to_owned());
+            }
+            if dest >= num_pages {
+                return Err(format!("more than {num_pages} pages are listed"));
+            }
+            if let Some(previous) = mapping[dest].replace(src) {
+                return Err(format!(
+                    "output page {} is given twice, as pages {} and {}",
+                    dest + 1,
+                    previous + 1,
+                    src + 1
+                ));
+            }
+        }
+    }
+    let mapping = mapping
+        .into_iter()
+        .enumerate()
+        .map(|(dest, src)| src.ok_or_else(|| format!("output page {} is not given", dest + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut used = vec![false; num_pages];
+    for &src in &mapping {
+        if std::mem::replace(&mut used[src], true) {
+            return Err(format!("page {} is placed more than once", src + 1));
+        }
+    }
+    Ok(mapping)
+}
+
+/// Replaces the pages of the document with the pages with the given numbers, starting from 1, in
+/// order. A page which is listed more than once is copied, and a warning is printed for any page
+/// which is not listed.
+fn reorder(document: &mut Document, order: &[usize]) -> color_eyre::Result<()> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut used = vec![false; page_ids.len()];
+    let mut reordered = Vec::with_capacity(order.len());
+    for &number in order {
+        let Some(&page_id) = page_ids.get(number - 1) else {
+            bail!(
+                "page {number} in the order does not exist, since the document has {} pages",
+                page_ids.len()
+            );
+        };
+        if std::mem::replace(&mut used[number - 1], true) {
+            let copy = pdf::page_with_inherited(document, page_id)?;
+            reordered.push(document.add_object(copy));
+            trace!("Copying page {number}, which is listed more than once");
+        } else {
+            reordered.push(page_id);
+        }
+    }
+    let unused = (1..=page_ids.len())
+        .filter(|number| !used[number - 1])
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>();
+    if !unused.is_empty() {
+        warn!(
+            "pages not listed in the order are left out: {}",
+            unused.join(", ")
+        );
+    }
+    pdf::set_page_order(document, &reordered)?;
+    document.prune_objects();
+    Ok(())
+}
+
+/// Merges a separate cover document into the document, returning the cover's pages in the order
+/// they are printed on the cover sheet. A four page cover is arranged as a one-sheet signature in
+/// the same way as the sheets in the plan, and a one page wrap-around cover is arranged on the
+/// outside of a sheet which is otherwise blank. The content of the front and back of a four page
+/// cover is shifted outwards by `creep` points, away from the spine.
+fn cover_sheet(
+    document: &mut Document,
+    cover: Document,
+    plan: &SignaturePlan,
+    creep: f32,
+) -> color_eyre::Result<Vec<ObjectId>> {
+    let num_pages = cover.get_pages().len();
+    if num_pages != 4 && num_pages != 1 {
+        bail!(
+            "cover must have 4 pages (front, inside front, inside back, and back) or 1 page (a \
+             wrap-around cover), but it has {num_pages}"
+        );
+    }
+    let pages = pdf::merge_document(document, cover)?;
+    if let [wrap] = pages[..] {
+        let mut blank = document.get_object(wrap)?.clone();
+        blank.as_dict_mut()?.remove(b"Contents");
+        // the wrap-around cover takes the place of the back cover, which is first on the outside,
+        // and the rest of the sheet is blank, so that it has as many pages as any other sheet
+        let mut sheet = vec![wrap; 4];
+        signature_with(0, 1, |src, dest| {
+            if src != 3 {
+                sheet[plan.position(0, dest)] = document.add_object(blank.clone());
+            }
+        });
+        return Ok(sheet);
+    }
+    let mut sheet = pages.clone();
+    // covers read the same way as the first signature
+    signature_with(0, 1, |src, dest| sheet[plan.position(0, dest)] = pages[src]);
+    if creep > 0.0 {
+        for (index, &page_id) in sheet.iter().enumerate() {
+            if page_id == pages[0] || page_id == pages[3] {
+                // the page on the right of the sheet is shifted right, and the one on the left,
+                // left
+                let shift = if index % 2 == 1 { creep } else { -creep };
+                let before = format!("q 1 0 0 1 {shift} 0 cm").into_bytes();
+                pdf::wrap_contents(document, page_id, before, b"Q".to_vec())?;
+            }
+        }
+    }
+    Ok(sheet)
+}
+
+/// The distance in points to shift the front and back covers outwards with `--cover-creep`, which
+/// is half the width of the spine.
+fn cover_creep(args: &Args, plan: &SignaturePlan) -> Option<f32> {
+    let caliper = args.paper_caliper.filter(|_| args.cover_creep)?;
+    let spine = spine_width(
+        plan.num_leaves(),
+        caliper.points().into(),
+        args.cover_allowance.points().into(),
+    );
+    Some(spine as f32 / 2.0)
+}
+
+/// The PDF versions which can be written with `--pdf-version`.
+const PDF_VERSIONS: [&str; 9] = [
+    "1.0", "1.1", "1.2", "1.3", "1.4", "1.5", "1.6", "1.7", "2.0",
+];
+
+/// Parses and validates a PDF version.
+fn parse_pdf_version(version: &str) -> Result<String, String> {
+    if PDF_VERSIONS.contains(&version) {
+        Ok(version.to_owned())
+    } else {
+        Err(format!(
+            "unsupported PDF version {version:?}, expected one of {}",
+            PDF_VERSIONS.join(", ")
+        ))
+    }
+}
+
+/// Sets the version of the output, warning about any features of the document which need a newer
+/// version.
+fn set_pdf_version(document: &mut Document, version: &str) -> color_eyre::Result<()> {
+    // versions compare correctly as strings, since they all have a single digit on each side
+    let xref_type = &document.reference_table.cross_reference_type;
+    if version < "1.5" && matches!(xref_type, XrefType::CrossReferenceStream) {
+        warn!(
+            "the document uses a cross-reference stream, which needs PDF 1.5 or later, \
+             but the output is PDF {version}"
+        );
+    }
+    document.version = version.to_owned();
+    document.catalog_mut()?.set("Version", version);
+    Ok(())
+}
+
+/// Sets how the cross-reference section of the output is written. A cross-reference stream needs
+/// PDF 1.5 or later, so an older document is written as PDF 1.5, unless `version` is the version
+/// the output must have, in which case it is an error.
+fn set_xref_format(
+    document: &mut Document,
+    format: XrefFormat,
+    version: Option<&str>,
+) -> color_eyre::Result<()> {
+    if format == XrefFormat::Stream {
+        match version {
+            Some(version) if version < "1.5" => bail!(
+                "--xref-format stream needs PDF 1.5 or later, but the output is PDF {version}"
+            ),
+            Some(_) => {}
+            None if document.version.as_str() < "1.5" => {
+                info!(
+                    "Writing PDF 1.5 instead of PDF {}, since --xref-format stream needs it",
+                    document.version
+                );
+                set_pdf_version(document, "1.5")?;
+            }
+            None => {}
+        }
+    }
+    document.reference_table.cross_reference_type = format.into();
+    Ok(())
+}
+
+/// Writes each signature to a separate file, named by the pattern given to `--name-pattern`, next
+/// to the output, stamping the first page of each with [`signature_stamp`] if `stamp` is set.
+/// Returns the paths of the files.
+fn save_signatures(
+    document: &Document,
+    plan: &SignaturePlan,
+    pattern: &str,
+    output: &Path,
+    linearize: bool,
+    stamp: bool,
+    embed_fonts: bool,
+) -> color_eyre::Result<Vec<PathBuf>> {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let names = name_pattern::signature_names(pattern, &stem, plan.signatures.len())
+        .map_err(|err| eyre!("{err}"))?;
+    let directory = output.parent().unwrap_or(Path::new(""));
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut start = 0;
+    let mut paths = Vec::new();
+    for (index, (name, &num_sheets)) in names.iter().zip(&plan.signatures).enumerate() {
+        let end = start + Sheets(num_sheets).pages().0;
+        let mut signature = document.clone();
+        pdf::set_page_order(&mut signature, &page_ids[start..end])?;
+        if stamp {
+            let text = signature_stamp(name, index, plan);
+            pdf::stamp_text(&mut signature, page_ids[start], &text)?;
+            if embed_fonts {
+                pdf::embed_fonts(&mut signature)?;
+            }
+        }
+        signature.prune_objects();
+        if linearize {
+            pdf::first_page_first(&mut signature)?;
+        }
+        let path = directory.join(name);
+        debug!("Writing {}", path.display());
+        signature.save(&path)?;
+        paths.push(path);
+        start = end;
+    }
+    Ok(paths)
+}
+
+/// The text stamped on the first page of a signature's file by `--stamp-signature-info`: its
+/// file name, its number, its number of sheets, and the pages in it, numbered from the start of
+/// the padded document.
+fn signature_stamp(name: &str, signature: usize, plan: &SignaturePlan) -> String {
+    let pages = |signatures: &[usize]| Sheets(signatures.iter().sum()).pages().0;
+    let first = pages(&plan.signatures[..signature]) + 1;
+    let last = pages(&plan.signatures[..=signature]);
+    let num_sheets = plan.signatures[signature];
+    format!(
+        "{name}: signature {} of {}, {num_sheets} {}, pages {first}-{last}",
+        signature + 1,
+        plan.signatures.len(),
+        if num_sheets == 1 { "sheet" } else { "sheets" }
+    )
+}
+
+/// Whether two paths refer to the same file. The second path does not need to exist, in which
+/// case it is the same file as the first if it names the same file in the same directory.
+fn same_file(existing: &Path, path: &Path) -> bool {
+    let canonical = |path: &Path| {
+        path.canonicalize().ok().or_else(|| {
+            let parent = match path.parent()? {
+                parent if parent.as_os_str().is_empty() => Path::new("."),
+                parent => parent,
+            };
+            Some(parent.canonicalize().ok()?.join(path.file_name()?))
+        })
+    };
+    matches!((canonical(existing), canonical(path)), (Some(a), Some(b)) if a == b)
 }
 
-#[derive(Clone, Copy, Debug, clap::Args)]
-struct SignatureParams {
-    /// Preferred number of sheets per signature
-    #[arg(short, long, default_value_t = 6)]
-    signature_size: usize,
-    /// Minimum number of sheets in the last signature. If the remainder would be less than this
-    /// amount, the last signature will instead be extra-long.
-    #[arg(short, long, default_value_t = 4)]
-    minimum_remainder_size: usize,
+/// A path for a temporary file in the same directory as `path`, so that it can be renamed over
+/// `path` without copying.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
 }
 
-fn main() -> color_eyre::Result<()> {
-    color_eyre::install()?;
-    let args = Args::parse();
-    let mut document = Document::load(args.input)?;
-    if args.end_pages {
-        add_pages(&mut document, 1, true)?;
-        add_pages(&mut document, 1, false)?;
+/// Checks that the output file is no larger than `max_bytes`, deleting it if it is.
+fn check_output_size(path: &Path, max_bytes: u64) -> color_eyre::Result<()> {
+    let size = std::fs::metadata(path)?.len();
+    if size > max_bytes {
+        std::fs::remove_file(path)?;
+        bail!(
+            "output was {size} bytes, more than the limit of {max_bytes} bytes, so it was deleted"
+        );
     }
-    let num_pages = document.page_iter().size_hint().0;
-    // round pages up
-    let blanks_needed = num_pages.next_multiple_of(4) - num_pages;
-    add_pages(&mut document, blanks_needed, false)?;
-    let pages = document
-        .page_iter()
-        .map(|id| document.get_object(id).map(|obj| (id, obj.clone())))
-        .collect::<Result<Vec<_>, _>>()?;
-    let metadata = arrange_pages_with(pages.len(), args.signature_params, |src, dest| {
-        let mut src_obj = pages[src].1.clone();
-        let dest_id = pages[dest].0;
-        if let Ok(src_dict) = src_obj.as_dict_mut() {
-            if let Ok(dest_parent) = pages[dest].1.as_dict().and_then(|dict| dict.get(b"Parent")) {
-                src_dict.set(b"Parent", dest_parent.clone());
-            } else {
-                src_dict.remove(b"Parent");
-            }
+    Ok(())
+}
+
+/// Records the wall-clock time taken by each phase of the imposition.
+struct Timer {
+    last: Instant,
+    laps: Vec<(&'static str, Duration)>,
+}
+
+impl Timer {
+    fn start() -> Self {
+        Timer {
+            last: Instant::now(),
+            laps: Vec::new(),
         }
-        document.set_object(dest_id, src_obj);
-    });
-    document.save(args.output)?;
+    }
 
-    let mut num_pages = num_pages;
-    let mut blanks_needed = blanks_needed;
-    if args.end_pages {
-        num_pages -= 2;
-        blanks_needed += 2;
+    /// Records the time since the previous lap as the time taken by the named phase.
+    fn lap(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.laps.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    fn print(&self) {
+        for (phase, duration) in &self.laps {
+            let millis = duration.as_secs_f64() * 1000.0;
+            info!("Time to {phase:<8} {millis:>10.3} ms");
+        }
+    }
+}
+
+/// The lines of text in a colophon summarising the imposition.
+fn colophon_lines(
+    args: &Args,
+    num_pages: usize,
+    blank_pages: usize,
+    plan: &SignaturePlan,
+) -> Vec<String> {
+    let source = args
+        .input()
+        .file_name()
+        .unwrap_or(args.input().as_os_str())
+        .to_string_lossy();
+    let signatures = plan
+        .signatures
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let params = args.signature_params;
+    vec![
+        "Imposition information".to_owned(),
+        String::new(),
+        format!("Source: {source}"),
+        format!("Date: {}", format_date(SystemTime::now())),
+        format!("Non-blank pages: {num_pages}"),
+        format!("Blank pages: {blank_pages}"),
+        format!("Sheets: {}", plan.num_sheets().0),
+        match params.signature_size {
+            0 => "Signature size: all sheets".to_owned(),
+            signature_size => format!("Signature size: {signature_size} sheets"),
+        },
+        format!(
+            "Minimum remainder: {} sheets ({:?})",
+            params.minimum_remainder_size, params.remainder
+        ),
+        format!("Sheets per signature: {signatures}"),
+    ]
+}
+
+/// The lines of a table listing the sheet, side, and slot that each page is printed in, with its
+/// position in printing order and in reading order. Leaves whose two pages are not consecutive in
+/// reading order are marked.
+fn reader_order_lines(plan: &SignaturePlan) -> Vec<String> {
+    let mut sources = vec![0; plan.num_pages().0];
+    arrange_pages_with(plan, |src, dest| sources[dest] = src);
+    let mut lines = vec!["Sheet  Side   Slot   Printer  Reader  Leaf".to_owned()];
+    for (dest, &src) in sources.iter().enumerate() {
+        let position = plan.slot_position(dest);
+        let side = if position.front { "front" } else { "back" };
+        let slot = if position.left { "left" } else { "right" };
+        let partner = sources[leaf_partner(dest)];
+        let leaf = if src.abs_diff(partner) == 1 && src.min(partner) % 2 == 0 {
+            "ok"
+        } else {
+            "NOT CONSECUTIVE"
+        };
+        lines.push(format!(
+            "{:<6} {side:<6} {slot:<6} {:<8} {:<7} {leaf}",
+            position.sheet + 1,
+            dest + 1,
+            src + 1,
+        ));
+    }
+    lines
+}
+
+/// A dictionary recording the number (starting from 1) of the page in the padded input, and where
+/// it is printed.
+fn provenance(src: usize, position: SlotPosition) -> Dictionary {
+    [
+        ("SourcePage", Object::from(src as i64 + 1)),
+        ("Signature", Object::from(position.signature as i64 + 1)),
+        ("Sheet", Object::from(position.sheet as i64 + 1)),
+        (
+            "Side",
+            Object::from(if position.front { "Front" } else { "Back" }),
+        ),
+        (
+            "Slot",
+            Object::from(if position.left { "Left" } else { "Right" }),
+        ),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Formats a time as a PDF date string, with only the date.
+fn pdf_date(time: SystemTime) -> String {
+    format!("D:{}", format_date(time).replace('-', ""))
+}
+
+/// Formats a time as an ISO 8601 date, in UTC.
+fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86400) as i64;
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Prints a warning for each pair of consecutive pages with identical content, other than blank
+/// pages, which are often repeated on purpose.
+fn warn_duplicate_pages(document: &Document, tolerance: f32) -> color_eyre::Result<()> {
+    let mut previous = None;
+    for (number, id) in document.get_pages() {
+        if pdf::is_blank(document, id, tolerance)? {
+            previous = None;
+            continue;
+        }
+        let hash = pdf::content_hash(document, id)?;
+        if previous == Some(hash) {
+            warn!("pages {} and {number} have identical content", number - 1);
+        }
+        previous = Some(hash);
     }
-    println!("Number of non-blank pages: {num_pages}");
-    println!("Number of blank pages:     {blanks_needed}");
-    println!("Number of sheets:          {}", metadata.num_sheets);
-    println!("Number of signatures:      {}", metadata.num_signatures);
-    println!("Sheets per signature:      {}", args.signature_params.signature_size);
-    println!("Sheets in last signature:  {}", metadata.remainder_sheets);
     Ok(())
 }
 
-/// Adds blank pages to the document. The pages will be a copy of the first page of the document
-/// with all content removed.
-fn add_pages(document: &mut Document, count: usize, at_start: bool) -> color_eyre::Result<()> {
-    // get first page of document
-    let mut page = document
-        .get_object(
+/// Computes the number of blank endpapers to add to the front and back of a document with
+/// `num_pages` pages, so that once it is padded to a whole number of sheets with `pages_per_sheet`
+/// pages it has at least `min_pages` pages. The endpapers come in whole sheets, split evenly
+/// between the front and back, so the front count is always even and the first page stays on a
+/// recto.
+fn endpapers_needed(num_pages: usize, min_pages: usize, pages_per_sheet: usize) -> (usize, usize) {
+    let padded = num_pages.next_multiple_of(pages_per_sheet);
+    let extra = min_pages
+        .next_multiple_of(pages_per_sheet)
+        .saturating_sub(padded);
+    (extra / 2, extra / 2)
+}
+
+/// Finds the page to use as a template for blank pages: the page with the given number if one was
+/// specified. Otherwise, there is no template, and [`add_pages`] copies the neighbouring page.
+fn blank_template(
+    document: &Document,
+    page_number: Option<u32>,
+) -> color_eyre::Result<Option<ObjectId>> {
+    page_number
+        .map(|number| {
             document
-                .page_iter()
-                .next()
-                .expect("document does not have any pages"),
-        )?
-        .clone();
-    // remove the contents
-    page.as_dict_mut()?.remove(b"Contents");
+                .get_pages()
+                .get(&number)
+                .copied()
+                .ok_or_else(|| eyre!("blank template page {number} does not exist"))
+        })
+        .transpose()
+}
+
+/// What to draw on the pages added by [`add_pages`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fill {
+    /// The content of the template page.
+    Template,
+    /// Only the background of the given style, in place of the template's content.
+    Blank(BlankStyle),
+}
+
+/// Adds pages to the document. The pages will be a copy of the `template` page, filled as given
+/// by `fill`. Without a template, they copy the page they are added next to: the first page if
+/// they are added at the start, or the last page otherwise. Blank pages always take the rotation
+/// of the page they are added next to, so that they fold the same way as their neighbors.
+fn add_pages(
+    document: &mut Document,
+    template: Option<ObjectId>,
+    count: usize,
+    at_start: bool,
+    fill: Fill,
+) -> color_eyre::Result<()> {
+    let neighbor = if at_start {
+        document.page_iter().next()
+    } else {
+        document.page_iter().last()
+    }
+    .expect("document does not have any pages");
+    let template = template.unwrap_or(neighbor);
+    let mut page = Object::Dictionary(pdf::page_with_inherited(document, template)?);
+    if let Fill::Blank(style) = fill {
+        page.as_dict_mut()?.remove(b"Contents");
+        let rotation = pdf::rotation(document, neighbor);
+        if rotation != pdf::rotation(document, template) {
+            page.as_dict_mut()?.set("Rotate", rotation);
+        }
+        let bbox = pdf::placed_box(document, template, PlacedBox::Trim);
+        if let (Some(bbox), true) = (bbox, count > 0) {
+            if let Some((content_id, resources)) = pdf::blank_contents(document, style, bbox)? {
+                let page = page.as_dict_mut()?;
+                page.set("Contents", content_id);
+                // the template's resources are only used by its content
+                page.set("Resources", resources);
+            }
+        }
+    }
 
     let page_tree_id = document.catalog()?.get(b"Pages")?.as_reference()?;
     // pre-allocate a new node so that we can reference it later
@@ -130,101 +2526,1197 @@ fn add_pages(document: &mut Document, count: usize, at_start: bool) -> color_eyr
     Ok(())
 }
 
-/// Arrange the pages using the given parameters, using the provided function to update the pages.
-/// The first argument to the function is the page index in the input document, and the second
-/// argument is the page index in the output document.
-fn arrange_pages_with(
-    num_pages: usize,
-    params: SignatureParams,
-    mut with: impl FnMut(usize, usize),
-) -> Metadata {
-    let pages_per_signature = params.signature_size * 4;
-    let mut num_signatures = num_pages / pages_per_signature;
-    let mut remainder = num_pages - num_signatures * pages_per_signature;
-    // if the remainder would be too short, make an overlong signature instead of a short
-    // signature.
-    if remainder > 0 && remainder < params.minimum_remainder_size * 4 && num_signatures >= 1 {
-        num_signatures -= 1;
-        remainder += pages_per_signature;
-    }
-    for sig in 0..num_signatures {
-        signature_with(sig * pages_per_signature, params.signature_size, &mut with);
-    }
-    signature_with(
-        num_signatures * pages_per_signature,
-        remainder.div_ceil(4),
-        &mut with,
-    );
-    Metadata {
-        num_sheets: num_pages.div_ceil(4),
-        num_signatures: if remainder == 0 {
-            num_signatures
-        } else {
-            num_signatures + 1
-        },
-        remainder_sheets: remainder.div_ceil(4),
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use bookbinding::{plan_signatures, Binding, Pages, Sheets, SignatureParams, SignaturePlan};
+    use clap::Parser;
+    use lopdf::Object;
+    use test_case::test_case;
+
+    use super::{Args, Command, Marks, Size};
+    use crate::{pdf, testing};
+
+    #[test]
+    fn overlong_last_signature() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "-s", "6"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 26]);
+        let counts = super::pad(&mut document, &args).unwrap();
+        assert_eq!((counts.non_blank, counts.blank), (26, 2));
+        let plan = plan_signatures(Sheets(7), args.signature_params);
+        assert_eq!(plan.signatures, [7]);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            None, Some(0), Some(1), None,
+            Some(25), Some(2), Some(3), Some(24),
+            Some(23), Some(4), Some(5), Some(22),
+            Some(21), Some(6), Some(7), Some(20),
+            Some(19), Some(8), Some(9), Some(18),
+            Some(17), Some(10), Some(11), Some(16),
+            Some(15), Some(12), Some(13), Some(14),
+        ];
+        assert_eq!(testing::page_indices(&document), expected);
     }
-}
 
-struct Metadata {
-    num_sheets: usize,
-    num_signatures: usize,
-    remainder_sheets: usize,
-}
+    #[test_case(10, 0, 4 => (0, 0))]
+    #[test_case(10, 12, 4 => (0, 0))]
+    #[test_case(10, 16, 4 => (2, 2))]
+    #[test_case(10, 17, 4 => (4, 4))]
+    #[test_case(16, 24, 4 => (4, 4))]
+    #[test_case(10, 17, 2 => (4, 4))]
+    fn endpapers(num_pages: usize, min_pages: usize, pages_per_sheet: usize) -> (usize, usize) {
+        super::endpapers_needed(num_pages, min_pages, pages_per_sheet)
+    }
+
+    #[test]
+    fn interleave() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        let other = testing::document_with_boxes(&[[0.0, 0.0, 50.0, 50.0]; 3]);
+        super::interleave(&mut document, other).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(0), Some(0), Some(1), Some(1), Some(2), Some(2)]
+        );
+        let widths = document
+            .page_iter()
+            .map(|id| pdf::page_box(&document, id, b"MediaBox").unwrap()[2])
+            .collect::<Vec<_>>();
+        assert_eq!(widths, [100.0, 50.0, 100.0, 50.0, 100.0, 50.0]);
 
-/// Arrange the pages for a given signature using the given parameters, using the provided function
-/// to update the pages.
-/// The first argument to the function is the page index in the input document, and the second
-/// argument is the page index in the output document.
-fn signature_with(start: usize, num_sheets: usize, mut with: impl FnMut(usize, usize)) {
-    let num_pages = num_sheets * 4;
-    let end = start + num_pages;
-    for i in 0..num_sheets {
-        let s = i * 2;
-        let dest = start + i * 4;
-        with(end - (s + 1), dest);
-        with(start + s, dest + 1);
-        with(start + s + 1, dest + 2);
-        with(end - (s + 2), dest + 3);
+        let other = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 2]);
+        assert!(super::interleave(&mut document, other).is_err());
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::collections::HashSet;
+    #[test_case("1 2, 3\n4" => Ok(vec![1, 2, 3, 4]))]
+    #[test_case("1-3 # front matter\n7-5\n3" => Ok(vec![1, 2, 3, 7, 6, 5, 3]))]
+    #[test_case("1 0" => Err("expected a page number starting from 1, not \"0\"".into()))]
+    #[test_case("1-x" => Err("expected a page number starting from 1, not \"x\"".into()))]
+    #[test_case("# nothing" => Err("no pages are listed".into()))]
+    fn parse_order(text: &str) -> Result<Vec<usize>, String> {
+        super::parse_order(text)
+    }
 
-    use test_case::test_case;
+    #[test_case("2 1 4 3", 4 => Ok(vec![1, 0, 3, 2]))]
+    #[test_case("1=4, 2=3\n3=2 # comment\n4=1", 4 => Ok(vec![3, 2, 1, 0]))]
+    #[test_case("1 2 3", 4 => Err("output page 4 is not given".into()))]
+    #[test_case("1 2 3 4 1", 4 => Err("more than 4 pages are listed".into()))]
+    #[test_case("1 2 2 4", 4 => Err("page 2 is placed more than once".into()))]
+    #[test_case("1=1 2=2 2=3", 4 => Err("output page 2 is given twice, as pages 2 and 3".into()))]
+    #[test_case("1=1 2", 2 => Err("pairs and a list of pages can't be mixed".into()))]
+    #[test_case("1 5", 2 => Err("expected a page number from 1 to 2, not \"5\"".into()))]
+    fn parse_mapping(text: &str, num_pages: usize) -> Result<Vec<usize>, String> {
+        super::parse_mapping(text, num_pages)
+    }
+
+    #[test]
+    fn impose_mapping() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--mapping", "4 1 2 3"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 4]);
+        let plan = plan_signatures(Sheets(1), args.signature_params);
+        let mapping = super::parse_mapping(args.mapping.as_deref().unwrap(), 4).unwrap();
+        super::impose(&mut document, &plan, Some(&mapping), None, &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(3), Some(0), Some(1), Some(2)]
+        );
+    }
+
+    #[test_case(0 => "book_sig1.pdf: signature 1 of 3, 6 sheets, pages 1-24")]
+    #[test_case(1 => "book_sig2.pdf: signature 2 of 3, 5 sheets, pages 25-44")]
+    #[test_case(2 => "book_sig3.pdf: signature 3 of 3, 1 sheet, pages 45-48")]
+    fn signature_stamp(signature: usize) -> String {
+        let plan = SignaturePlan {
+            signatures: vec![6, 5, 1],
+            ..plan_signatures(Sheets(12), Default::default())
+        };
+        let name = format!("book_sig{}.pdf", signature + 1);
+        super::signature_stamp(&name, signature, &plan)
+    }
+
+    /// Each gathering is a signature, and the sheets left over are handled as with
+    /// `--signature-size`, so the last signature isn't a whole number of sections.
+    #[test_case(&["--leaves-per-section", "4", "--sections-per-gathering", "3"], 56 => Ok(vec![6, 8]))]
+    #[test_case(&["--leaves-per-section", "3", "--sections-per-gathering", "3"], 56 => Err(()))]
+    #[test_case(&["--leaves-per-section", "4", "--sections-per-gathering", "0"], 56 => Err(()))]
+    fn gathering(options: &[&str], num_pages: usize) -> Result<Vec<usize>, ()> {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf"].iter().chain(options));
+        let plan = super::plan(Pages(num_pages), false, &args).map_err(|_| ())?;
+        Ok(plan.signatures)
+    }
+
+    /// A pamphlet with 2 pages more than a whole number of sheets ends with a single leaf, printed
+    /// on half of the last sheet.
+    #[test_case(10, "perfect" => (2, vec![2, 1]))]
+    #[test_case(9, "perfect" => (3, vec![2, 1]))]
+    #[test_case(10, "saddle" => (2, vec![2, 1]))]
+    #[test_case(2, "saddle" => (2, vec![1]))]
+    #[test_case(12, "perfect" => (0, vec![3]))]
+    fn min_fold(num_pages: usize, binding: &str) -> (usize, Vec<usize>) {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--min-fold",
+            "2",
+            "--binding",
+            binding,
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&vec![[0.0, 0.0, 100.0, 100.0]; num_pages]);
+        let counts = super::pad(&mut document, &args).unwrap();
+        let padded = Pages(document.page_iter().count());
+        let plan = super::plan(padded, counts.half_sheet, &args).unwrap();
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        if counts.half_sheet {
+            // the last two pages of the book are on one leaf of the last sheet, and the other half
+            // is blank
+            let last = padded.0 - 4;
+            let pages = testing::page_indices(&document);
+            let leaf = [
+                num_pages.next_multiple_of(2) - 2,
+                num_pages.next_multiple_of(2) - 1,
+            ];
+            let expected = leaf.map(|page| Some(page).filter(|&page| page < num_pages));
+            assert_eq!(pages[last..], [None, expected[0], expected[1], None]);
+        }
+        (counts.blank, plan.signatures)
+    }
+
+    /// The pages of a French fold are placed in the quadrants of the sheet, with the top row upside
+    /// down, and the last card is filled with blank pages.
+    #[test]
+    fn french_fold() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--french-fold"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 7]);
+        assert_eq!(super::french_fold(&mut document, &args).unwrap(), 2);
+        let sheet_id = document.page_iter().next().unwrap();
+        let content = document.get_page_content(sheet_id).unwrap();
+        let operations = lopdf::Content::decode(&content).unwrap().operations;
+        let xobjects = document
+            .get_dictionary(sheet_id)
+            .and_then(|sheet| sheet.get(b"Resources"))
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(Object::as_dict)
+            .unwrap();
+        // the page and whether it is upside down in each slot, from the top left
+        let slots = operations
+            .windows(2)
+            .filter(|pair| pair[0].operator == "cm" && pair[1].operator == "Do")
+            .map(|pair| {
+                let name = pair[1].operands[0].as_name().unwrap();
+                let xobject_id = xobjects.get(name).unwrap().as_reference().unwrap();
+                let xobject = document
+                    .get_object(xobject_id)
+                    .unwrap()
+                    .as_stream()
+                    .unwrap();
+                let page = String::from_utf8_lossy(&xobject.content)
+                    .split_whitespace()
+                    .nth(2)
+                    .unwrap()
+                    .to_owned();
+                let upside_down = pair[0].operands[0].as_float().unwrap() < 0.0;
+                (page, upside_down)
+            })
+            .collect::<Vec<_>>();
+        let expected = [("2", true), ("1", true), ("3", false), ("0", false)];
+        assert_eq!(
+            slots,
+            expected.map(|(page, upside_down)| (page.to_owned(), upside_down))
+        );
+    }
+
+    #[test_case("3,4,5,4,3", 33 => Ok(vec![3, 4, 5, 4, 3, 3, 4, 7]))]
+    #[test_case("3,0", 33 => Err(()))]
+    fn vary_signatures(pattern: &str, num_sheets: usize) -> Result<Vec<usize>, ()> {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--vary-signatures",
+            pattern,
+        ];
+        let plan =
+            super::plan(Pages(4 * num_sheets), false, &Args::parse_from(args)).map_err(|_| ())?;
+        Ok(plan.signatures)
+    }
+
+    #[test]
+    fn schemes() {
+        // the example of each scheme selects it
+        for info in crate::schemes::SCHEMES {
+            let args = ["bookbinding", "in.pdf", "out.pdf"]
+                .into_iter()
+                .chain(info.options.split_whitespace());
+            assert_eq!(super::scheme(&Args::parse_from(args)), info.scheme);
+        }
+    }
+
+    #[test]
+    fn proof_document() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--passthrough-first"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 6]);
+        let passthrough = super::detach_first_page(&mut document).unwrap();
+        super::pad(&mut document, &args).unwrap();
+        let proof = super::proof_document(&document, Some(passthrough)).unwrap();
+        // the proof is in reading order, and is not affected by imposing the document
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, None, Some(passthrough), &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&proof),
+            [
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(5),
+                None,
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn passthrough_first() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--passthrough-first"]);
+        let mut document = testing::document_with_boxes(&[
+            [0.0, 0.0, 300.0, 400.0],
+            [0.0, 0.0, 100.0, 100.0],
+            [0.0, 0.0, 100.0, 100.0],
+            [0.0, 0.0, 100.0, 100.0],
+        ]);
+        let first_id = document.page_iter().next().unwrap();
+        let first = document.get_dictionary(first_id).unwrap().clone();
+        let first_content = document.get_page_content(first_id).unwrap();
+        let passthrough = super::detach_first_page(&mut document).unwrap();
+        assert_eq!(passthrough, first_id);
+        // the other three pages are padded to a sheet of their own
+        let counts = super::pad(&mut document, &args).unwrap();
+        assert_eq!((counts.non_blank, counts.blank), (3, 1));
+        let plan = plan_signatures(Sheets(1), args.signature_params);
+        super::impose(&mut document, &plan, None, Some(passthrough), &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(0), None, Some(1), Some(2), Some(3)]
+        );
+        let page_id = document.page_iter().next().unwrap();
+        assert_eq!(page_id, first_id);
+        assert_eq!(document.get_dictionary(page_id).unwrap(), &first);
+        assert_eq!(document.get_page_content(page_id).unwrap(), first_content);
+
+        let mut single = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        assert!(super::detach_first_page(&mut single).is_err());
+    }
+
+    #[test]
+    fn normalize_origin() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--normalize-origin",
+            "--grid",
+            "1x1",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[100.0, 100.0, 712.0, 892.0]]);
+        super::prepare(&mut document, &args).unwrap();
+        let page_id = document.page_iter().next().unwrap();
+        assert_eq!(
+            pdf::page_box(&document, page_id, b"MediaBox"),
+            Some([0.0, 0.0, 612.0, 792.0])
+        );
+        crate::grid::impose(&mut document, args.grid.unwrap(), 0.0, Default::default()).unwrap();
+        // the page lands in the slot of the sheet, just inside the margin
+        let sheet_id = document.page_iter().next().unwrap();
+        let content = document.get_page_content(sheet_id).unwrap();
+        let placement = lopdf::Content::decode(&content)
+            .unwrap()
+            .operations
+            .into_iter()
+            .rfind(|operation| operation.operator == "cm")
+            .unwrap();
+        let matrix = placement
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect::<Vec<_>>();
+        let margin = bookbinding::MARK_MARGIN;
+        assert_eq!(matrix, [1.0, 0.0, 0.0, 1.0, margin, margin]);
+    }
+
+    #[test_case(&[] => [[0.0, 0.0, 200.0, 300.0], [20.0, 30.0, 180.0, 280.0]]; "left as it is")]
+    #[test_case(&["--box", "media"] => [[0.0, 0.0, 200.0, 300.0], [0.0, 0.0, 200.0, 300.0]])]
+    #[test_case(&["--box", "crop"] => [[20.0, 30.0, 180.0, 280.0], [20.0, 30.0, 180.0, 280.0]])]
+    fn placed_box(options: &[&str]) -> [[f32; 4]; 2] {
+        let args = ["bookbinding", "in.pdf", "out.pdf"].iter().chain(options);
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 300.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        document.get_dictionary_mut(page_id).unwrap().set(
+            "CropBox",
+            [20.0, 30.0, 180.0, 280.0].map(lopdf::Object::from).to_vec(),
+        );
+        super::prepare(&mut document, &args).unwrap();
+        [&b"MediaBox"[..], b"CropBox"].map(|key| pdf::page_box(&document, page_id, key).unwrap())
+    }
 
-    #[test_case(26, 5)]
-    #[test_case(36, 5)]
-    #[test_case(40, 5)]
-    #[test_case(40, 6)]
-    fn arrange_pages(num_pages: usize, signature_size: usize) {
-        let params = super::SignatureParams {
+    #[test]
+    fn book_size() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--book-size",
+            "6inx9in",
+            "--book-fit",
+            "cover",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 612.0, 792.0]; 2]);
+        super::prepare(&mut document, &args).unwrap();
+        for page_id in document.page_iter() {
+            assert_eq!(
+                pdf::page_box(&document, page_id, b"TrimBox"),
+                Some([0.0, 0.0, 432.0, 648.0])
+            );
+        }
+    }
+
+    #[test]
+    fn check_power_of_two() {
+        let check = |num_sheets: usize, params: SignatureParams| {
+            let plan = plan_signatures(Sheets(num_sheets), params);
+            super::check_power_of_two(&plan, &params).map_err(|err| err.to_string())
+        };
+        let size = |signature_size| SignatureParams {
             signature_size,
-            minimum_remainder_size: 4,
+            ..Default::default()
         };
-        let mut pages = HashSet::new();
-        let mut duplicates = Vec::new();
-        super::arrange_pages_with(num_pages, params, |src, _dest| {
-            if !pages.insert(src) {
-                duplicates.push(src);
-            }
-        });
-        let num_pages_rounded = num_pages.next_multiple_of(4);
-        assert_eq!(pages.len(), num_pages_rounded);
-        assert_eq!(duplicates, []);
+        assert!(check(32, size(4)).is_ok());
+        // the last signature takes the 2 sheets left over, which are 8 pages on their own
+        assert_eq!(
+            check(34, size(4)).unwrap_err(),
+            "signature 8 (6 sheets, 24 pages) does not have a power of two number of pages; try \
+             --remainder short"
+        );
+        let error = check(50, size(6)).unwrap_err();
+        assert!(error.starts_with("signatures 1 (6 sheets, 24 pages), 2 (6 sheets, 24 pages),"));
+        // the last signature has 8 sheets, which is 32 pages
+        assert!(error.contains(", 7 (6 sheets, 24 pages) do not"));
+        assert!(error.ends_with("try --signature-size 4 --remainder short"));
+        // 16 and 12 sheets, so the last signature needs 4 more sheets
+        assert!(check(28, size(14)).unwrap_err().ends_with(
+            "try --signature-size 16 --remainder short, with blank pages to make 128 pages"
+        ));
+        let saddle = SignatureParams {
+            binding: Binding::Saddle,
+            ..Default::default()
+        };
+        assert!(check(6, saddle)
+            .unwrap_err()
+            .ends_with("add blank pages to make 32 pages"));
     }
 
     #[test]
-    fn signature() {
-        let mut pages = [0; 16];
-        super::signature_with(0, 4, |src, dest| {
-            pages[dest] = src;
-        });
+    fn reverse_input() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--reverse-input"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        super::reverse_pages(&mut document).unwrap();
+        // the blank page is added after the reversed pages
+        super::pad(&mut document, &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(2), Some(1), Some(0), None]
+        );
+        let plan = plan_signatures(Sheets(1), args.signature_params);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [None, Some(2), Some(1), Some(0)]
+        );
+    }
+
+    #[test]
+    fn reorder() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 4]);
+        super::reorder(&mut document, &[2, 1, 2, 4]).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(1), Some(0), Some(1), Some(3)]
+        );
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        assert_ne!(page_ids[0], page_ids[2]);
+
+        assert!(super::reorder(&mut document, &[5]).is_err());
+    }
+
+    #[test_case("blank" => Some(super::PadWith::Blank))]
+    #[test_case("3" => Some(super::PadWith::Page(3)))]
+    #[test_case("0" => None)]
+    #[test_case("first" => None)]
+    fn pad_with(value: &str) -> Option<super::PadWith> {
+        super::parse_pad_with(value).ok()
+    }
+
+    #[test]
+    fn pad_with_page() {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--pad-with", "2"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 5]);
+        super::pad(&mut document, &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(1),
+                Some(1),
+                Some(1)
+            ]
+        );
+
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--pad-with", "6"]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 5]);
+        assert!(super::pad(&mut document, &args).is_err());
+    }
+
+    #[test]
+    fn trim() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--trim",
+            "10",
+            "--trim-fore",
+            "5",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 200.0]; 4]);
+        super::pad(&mut document, &args).unwrap();
+        let trim_boxes = document
+            .page_iter()
+            .map(|id| pdf::page_box(&document, id, b"TrimBox").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trim_boxes,
+            [
+                [0.0, 10.0, 95.0, 190.0],
+                [5.0, 10.0, 100.0, 190.0],
+                [0.0, 10.0, 95.0, 190.0],
+                [5.0, 10.0, 100.0, 190.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn annotate_provenance() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--annotate-provenance"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 8]);
+        super::pad(&mut document, &args).unwrap();
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        let page_id = document.page_iter().nth(6).unwrap();
+        let provenance = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"PieceInfo"))
+            .and_then(Object::as_dict)
+            .and_then(|piece_info| piece_info.get(pdf::PIECE_INFO_NAME))
+            .and_then(Object::as_dict)
+            .and_then(|data| data.get(b"Private"))
+            .and_then(Object::as_dict)
+            .unwrap();
+        let get = |key: &[u8]| provenance.get(key).unwrap();
+        assert_eq!(get(b"SourcePage").as_i64().unwrap(), 4);
+        assert_eq!(get(b"Sheet").as_i64().unwrap(), 2);
+        assert_eq!(get(b"Side").as_name().unwrap(), b"Back");
+        assert_eq!(get(b"Slot").as_name().unwrap(), b"Left");
+    }
+
+    #[test]
+    fn trim_trailing_blanks() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--trim-trailing-blanks"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 7]);
+        // a blank page in the middle is kept
+        for index in [2, 5, 6] {
+            let page_id = document.page_iter().nth(index).unwrap();
+            document
+                .get_dictionary_mut(page_id)
+                .unwrap()
+                .remove(b"Contents");
+        }
+        let counts = super::pad(&mut document, &args).unwrap();
+        assert_eq!((counts.non_blank, counts.blank, counts.trimmed), (5, 3, 2));
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(0), Some(1), None, Some(3), Some(4), None, None, None]
+        );
+    }
+
+    /// The tolerance is the area of a square with the given side.
+    #[test_case("0" => 0.0)]
+    #[test_case("3pt" => 9.0)]
+    #[test_case("1in" => 5184.0)]
+    fn blank_tolerance(side: &str) -> f32 {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--blank-tolerance",
+            side,
+        ];
+        super::blank_tolerance(&Args::parse_from(args))
+    }
+
+    #[test_case(false => true)]
+    #[test_case(true => false)]
+    fn validate(swap: bool) -> bool {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--tumble", "--validate"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 7]);
+        super::pad(&mut document, &args).unwrap();
+        let expected = super::source_hashes(&document).unwrap();
+        assert_eq!(expected.iter().filter(|hash| hash.is_none()).count(), 1);
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        if swap {
+            let mut page_ids = document.page_iter().collect::<Vec<_>>();
+            page_ids.swap(1, 2);
+            pdf::set_page_order(&mut document, &page_ids).unwrap();
+        }
+        super::validate(&document, &plan, &expected, args.tumble).is_ok()
+    }
+
+    #[test_case(false, None => "permutation=ok")]
+    #[test_case(true, None => "permutation=invalid")]
+    #[test_case(false, Some(&[1, 0, 2, 3, 4, 5, 6, 7]) => "permutation=ok")]
+    #[test_case(true, Some(&[1, 0, 2, 3, 4, 5, 6, 7]) => "permutation=invalid")]
+    fn qa_summary(swap: bool, mapping: Option<&[usize]>) -> String {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--qa-summary", "-"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 6]);
+        let counts = super::pad(&mut document, &args).unwrap();
+        let expected = super::source_hashes(&document).unwrap();
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, mapping, None, &args).unwrap();
+        if swap {
+            let mut page_ids = document.page_iter().collect::<Vec<_>>();
+            page_ids.swap(0, 1);
+            pdf::set_page_order(&mut document, &page_ids).unwrap();
+        }
+        let summary = super::qa_summary(&document, &plan, mapping, &counts, &expected, false);
+        let (rest, permutation) = summary.rsplit_once(' ').unwrap();
+        assert!(
+            rest.ends_with(" output_pages=8 sheets=2 signatures=1"),
+            "{rest}"
+        );
+        permutation.to_owned()
+    }
+
+    #[test]
+    fn tumble() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--tumble"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 8]);
+        let page_id = document.page_iter().nth(6).unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Rotate", 90);
+        super::pad(&mut document, &args).unwrap();
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [7, 0, 6, 1, 5, 2, 4, 3].map(Some)
+        );
+        let rotations = document
+            .page_iter()
+            .map(|page_id| pdf::rotation(&document, page_id))
+            .collect::<Vec<_>>();
+        assert_eq!(rotations, [0, 0, 270, 180, 0, 0, 180, 180]);
+    }
+
+    #[test_case(&["--max-padding", "3"], 5 => true)]
+    #[test_case(&["--max-padding", "2"], 5 => false)]
+    #[test_case(&["--max-padding", "5", "--end-pages"], 5 => true)]
+    #[test_case(&["--max-padding", "8", "--min-pages", "40"], 1 => false)]
+    fn max_padding(options: &[&str], num_pages: usize) -> bool {
+        let args = ["bookbinding", "in.pdf", "out.pdf"].iter().chain(options);
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&vec![[0.0, 0.0, 100.0, 100.0]; num_pages]);
+        super::pad(&mut document, &args).is_ok()
+    }
+
+    #[test]
+    fn first_page_verso() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--first-page",
+            "verso",
+            "--trim-fore",
+            "5",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 200.0]; 6]);
+        let counts = super::pad(&mut document, &args).unwrap();
+        assert_eq!((counts.non_blank, counts.blank), (6, 2));
         assert_eq!(
-            pages,
-            [15, 0, 1, 14, 13, 2, 3, 12, 11, 4, 5, 10, 9, 6, 7, 8]
+            testing::page_indices(&document),
+            [
+                None,
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(5),
+                None
+            ]
+        );
+        // the first page of the input is a verso, trimmed on the left
+        let page_id = document.page_iter().nth(1).unwrap();
+        assert_eq!(
+            pdf::page_box(&document, page_id, b"TrimBox").unwrap(),
+            [5.0, 0.0, 100.0, 200.0]
+        );
+    }
+
+    #[test_case(&[], 8 => Some(0))]
+    #[test_case(&[], 7 => None)]
+    #[test_case(&["--end-pages"], 6 => Some(2))]
+    fn no_pad(options: &[&str], num_pages: usize) -> Option<usize> {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--no-pad"]
+            .iter()
+            .chain(options);
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&vec![[0.0, 0.0, 100.0, 100.0]; num_pages]);
+        super::pad(&mut document, &args)
+            .ok()
+            .map(|counts| counts.blank)
+    }
+
+    #[test_case(2, true => vec![None, None, Some(0), Some(1), Some(2)])]
+    #[test_case(2, false => vec![Some(0), Some(1), Some(2), None, None])]
+    #[test_case(1, true => vec![None, Some(0), Some(1), Some(2)])]
+    #[test_case(1, false => vec![Some(0), Some(1), Some(2), None])]
+    #[test_case(0, true => vec![Some(0), Some(1), Some(2)])]
+    fn add_pages(count: usize, at_start: bool) -> Vec<Option<usize>> {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        let blank = super::Fill::Blank(pdf::BlankStyle::Empty);
+        super::add_pages(&mut document, None, count, at_start, blank).unwrap();
+        let root = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .and_then(|root_id| document.get_dictionary(root_id))
+            .unwrap();
+        assert_eq!(
+            root.get(b"Count").unwrap().as_i64().unwrap(),
+            3 + count as i64
+        );
+        assert_eq!(document.page_iter().count(), 3 + count);
+        testing::page_indices(&document)
+    }
+
+    /// Blank pages made from a template take the rotation of the page they are added next to.
+    #[test_case(true => 0)]
+    #[test_case(false => 90)]
+    fn add_pages_rotation(at_start: bool) -> i64 {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        document
+            .get_dictionary_mut(page_ids[2])
+            .unwrap()
+            .set("Rotate", 90);
+        let blank = super::Fill::Blank(pdf::BlankStyle::Empty);
+        super::add_pages(&mut document, Some(page_ids[1]), 1, at_start, blank).unwrap();
+        let added = if at_start {
+            document.page_iter().next()
+        } else {
+            document.page_iter().last()
+        };
+        pdf::rotation(&document, added.unwrap())
+    }
+
+    #[test_case(&[2, 3] => Some(vec![1, 2]))]
+    #[test_case(&[] => Some(vec![]))]
+    #[test_case(&[0] => None)]
+    #[test_case(&[4] => None)]
+    fn rtl_signatures(numbers: &[usize]) -> Option<Vec<usize>> {
+        let plan = plan_signatures(Sheets(18), Default::default());
+        super::rtl_signatures(numbers, &plan).ok()
+    }
+
+    #[test_case("2:17inx11in" => Some((2, (1224.0, 792.0))))]
+    #[test_case(" 1 :100x200" => Some((1, (100.0, 200.0))))]
+    #[test_case("0:100x200" => None)]
+    #[test_case("17inx11in" => None)]
+    #[test_case("1:17in" => None)]
+    fn parse_signature_sheet(value: &str) -> Option<(usize, (f32, f32))> {
+        let (signature, size) = super::parse_signature_sheet(value).ok()?;
+        Some((signature, size.points()))
+    }
+
+    #[test_case(&[(3, "100x200"), (1, "50x50")] => Some(vec![Some((50.0, 50.0)), None, Some((100.0, 200.0))]))]
+    #[test_case(&[] => Some(vec![None, None, None]))]
+    #[test_case(&[(4, "100x200")] => None)]
+    fn signature_sheets(sheets: &[(usize, &str)]) -> Option<Vec<Option<(f32, f32)>>> {
+        let plan = plan_signatures(Sheets(18), Default::default());
+        let sheets = sheets
+            .iter()
+            .map(|&(signature, size)| (signature, size.parse().unwrap()))
+            .collect::<Vec<_>>();
+        let sizes = super::signature_sheets(&sheets, &plan).ok()?;
+        Some(
+            sizes
+                .into_iter()
+                .map(|size| size.map(Size::points))
+                .collect(),
         )
     }
+
+    /// Each side of a sheet is one page with `2x1`, and the cover sheet comes first.
+    #[test_case(0, 0 => Some(0))]
+    #[test_case(11, 0 => Some(0))]
+    #[test_case(12, 0 => Some(1))]
+    #[test_case(1, 4 => None)]
+    #[test_case(2, 4 => Some(0))]
+    #[test_case(14, 4 => Some(1))]
+    fn side_signature(side: usize, cover_pages: usize) -> Option<usize> {
+        let plan = plan_signatures(Sheets(18), Default::default());
+        let layout = "2x1".parse().unwrap();
+        super::side_signature(&plan, layout, cover_pages, side)
+    }
+
+    #[test]
+    fn cover_creep() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--cover-file",
+            "cover.pdf",
+            "--cover-creep",
+            "--paper-caliper",
+            "0.1mm",
+        ];
+        let args = Args::parse_from(args);
+        // 4 sheets have 8 leaves, for a spine 0.8 mm wide
+        let plan = plan_signatures(Sheets(4), args.signature_params);
+        let creep = super::cover_creep(&args, &plan).unwrap();
+        assert!((creep - 0.4 * crate::length::POINTS_PER_MM).abs() < 1e-4);
+
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 16]);
+        let cover = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 4]);
+        let sheet = super::cover_sheet(&mut document, cover, &plan, 10.0).unwrap();
+        let contents = sheet
+            .iter()
+            .map(|&page_id| {
+                let content = pdf::concatenate_contents(&document, page_id).unwrap();
+                String::from_utf8(content).unwrap()
+            })
+            .collect::<Vec<_>>();
+        // the back cover is on the left of the outside, and the front cover on the right
+        assert!(contents[0].starts_with("q 1 0 0 1 -10 0 cm\n\n% page 3\n"));
+        assert!(contents[1].starts_with("q 1 0 0 1 10 0 cm\n\n% page 0\n"));
+        assert!(contents[2].starts_with("% page 1\n"));
+        assert!(contents[3].starts_with("% page 2\n"));
+    }
+
+    #[test]
+    fn wrap_around_cover() {
+        let dir = std::env::temp_dir().join(format!("bookbinding-cover-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cover.pdf");
+        testing::document_with_boxes(&[[0.0, 0.0, 200.0, 100.0]])
+            .save(&path)
+            .unwrap();
+        let args = Args::parse_from([
+            "bookbinding".as_ref(),
+            "in.pdf".as_ref(),
+            "out.pdf".as_ref(),
+            "--cover-file".as_ref(),
+            path.as_os_str(),
+        ]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 8]);
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        let result = super::impose(&mut document, &plan, None, None, &args);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        let pages = testing::page_indices(&document);
+        // the cover sheet has four pages, so the interior sheets are not shifted
+        assert_eq!(pages.len() % 4, 0);
+        assert_eq!(pages[..4], [Some(0), None, None, None]);
+        assert_eq!(
+            pages[4..],
+            [
+                Some(7),
+                Some(0),
+                Some(1),
+                Some(6),
+                Some(5),
+                Some(2),
+                Some(3),
+                Some(4)
+            ]
+        );
+    }
+
+    /// The end page at the front puts a verso first page on a verso, without another blank.
+    #[test]
+    fn first_page_verso_end_pages() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--first-page",
+            "verso",
+            "--end-pages",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 200.0]; 6]);
+        let counts = super::pad(&mut document, &args).unwrap();
+        assert_eq!((counts.non_blank, counts.blank), (6, 2));
+        let pages = testing::page_indices(&document);
+        assert_eq!(pages.len(), 8);
+        // the first page is the second of the output, on the back of the first leaf
+        assert_eq!(pages[1], Some(0));
+    }
+
+    #[test]
+    fn end_pages_match_neighbours() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--end-pages"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[
+            [0.0, 0.0, 300.0, 200.0],
+            [0.0, 0.0, 100.0, 150.0],
+            [0.0, 0.0, 120.0, 180.0],
+        ]);
+        super::pad(&mut document, &args).unwrap();
+        let widths = document
+            .page_iter()
+            .map(|page_id| pdf::page_box(&document, page_id, b"MediaBox").unwrap()[2])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            widths,
+            [300.0, 300.0, 100.0, 120.0, 120.0, 120.0, 120.0, 120.0]
+        );
+    }
+
+    #[test]
+    fn same_file() {
+        let dir =
+            std::env::temp_dir().join(format!("bookbinding-same-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.pdf");
+        std::fs::write(&input, b"").unwrap();
+        let same = [
+            super::same_file(&input, &dir.join(".").join("in.pdf")),
+            super::same_file(&input, &dir.join("out.pdf")),
+            super::same_file(&input, &dir.join("missing").join("in.pdf")),
+        ];
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(same, [true, false, false]);
+    }
+
+    #[test]
+    fn temporary_path() {
+        assert_eq!(
+            super::temporary_path(std::path::Path::new("dir/book.pdf")),
+            std::path::Path::new("dir/.book.pdf.tmp")
+        );
+    }
+
+    #[test]
+    fn blank_style() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--blank-style", "note"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        super::pad(&mut document, &args).unwrap();
+        let page_id = document.page_iter().nth(3).unwrap();
+        let content = pdf::concatenate_contents(&document, page_id).unwrap();
+        assert!(content.windows(2).any(|window| window == b"Tj"));
+        let page = document.get_dictionary(page_id).unwrap();
+        assert!(page
+            .get(b"Resources")
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .has(b"Font"));
+    }
+
+    #[test]
+    fn blank_backs() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--blank-backs"];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 8]);
+        super::pad(&mut document, &args).unwrap();
+        let plan = plan_signatures(Sheets(2), args.signature_params);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        let pages = testing::page_indices(&document);
+        assert_eq!(pages.len(), 16);
+        // blank pages have no index
+        let expected =
+            [7, 0, 8, 8, 1, 6, 8, 8, 5, 2, 8, 8, 3, 4, 8, 8].map(|page| (page < 8).then_some(page));
+        assert_eq!(pages, expected);
+        let blank = document
+            .page_iter()
+            .map(|page_id| pdf::is_blank(&document, page_id, 0.0).unwrap())
+            .collect::<Vec<_>>();
+        let expected = pages.iter().map(Option::is_none).collect::<Vec<_>>();
+        assert_eq!(blank, expected);
+    }
+
+    #[test]
+    fn newspaper() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--newspaper"];
+        let expanded = super::expand_presets(args.map(Into::into));
+        assert_eq!(
+            expanded,
+            [
+                "bookbinding",
+                "--binding",
+                "saddle",
+                "--nup-layout",
+                "2x1",
+                "--sheet-size",
+                "17inx11in",
+                "--marks",
+                "outer",
+                "in.pdf",
+                "out.pdf"
+            ]
+        );
+        let args = Args::parse_from(expanded);
+        assert_eq!(args.signature_params.binding, Binding::Saddle);
+        assert_eq!(args.nup_layout, Some("2x1".parse().unwrap()));
+        assert_eq!(
+            args.sheet_size.map(Size::points),
+            Some((17.0 * 72.0, 11.0 * 72.0))
+        );
+        assert_eq!(args.marks, Some(Marks::Outer));
+
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--newspaper",
+            "--binding",
+            "perfect",
+            "--nup-layout",
+            "1x2",
+        ];
+        let args = Args::parse_from(super::expand_presets(args.map(Into::into)));
+        assert_eq!(args.signature_params.binding, Binding::Perfect);
+        assert_eq!(args.nup_layout, Some("1x2".parse().unwrap()));
+    }
+
+    #[test]
+    fn commands() {
+        let args = Args::parse_from(["bookbinding", "analyze", "in.pdf"]);
+        match args.command {
+            Some(Command::Analyze(analyze_args)) => {
+                assert_eq!(analyze_args.input, PathBuf::from("in.pdf"));
+            }
+            command => panic!("expected analyze, not {command:?}"),
+        }
+        let args = Args::parse_from(["bookbinding", "schemes"]);
+        assert!(matches!(args.command, Some(Command::Schemes)));
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf"]);
+        assert!(args.command.is_none());
+        assert_eq!(args.input(), Path::new("in.pdf"));
+        assert_eq!(args.output(), Path::new("out.pdf"));
+    }
+
+    #[test]
+    fn split_spreads() {
+        let mut document = testing::document_with_boxes(&[
+            [0.0, 0.0, 200.0, 100.0],
+            // a single page, such as a cover
+            [0.0, 0.0, 100.0, 150.0],
+            [0.0, 0.0, 300.0, 100.0],
+            [0.0, 0.0, 100.0, 300.0],
+        ]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        for (index, rotation) in [(2, 180), (3, 90)] {
+            document
+                .get_dictionary_mut(page_ids[index])
+                .unwrap()
+                .set("Rotate", rotation);
+        }
+        let trim_box = vec![Object::from(10.0), 10.into(), 190.into(), 90.into()];
+        document
+            .get_dictionary_mut(page_ids[0])
+            .unwrap()
+            .set("TrimBox", trim_box);
+        super::split_spreads(&mut document).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [0, 0, 1, 2, 2, 3, 3].map(Some)
+        );
+        let crop_boxes = document
+            .page_iter()
+            .map(|page_id| pdf::placed_box(&document, page_id, pdf::PlacedBox::Crop).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            crop_boxes,
+            [
+                [0.0, 0.0, 100.0, 100.0],
+                [100.0, 0.0, 200.0, 100.0],
+                [0.0, 0.0, 100.0, 150.0],
+                [150.0, 0.0, 300.0, 100.0],
+                [0.0, 0.0, 150.0, 100.0],
+                [0.0, 0.0, 100.0, 150.0],
+                [0.0, 150.0, 100.0, 300.0],
+            ]
+        );
+        let trim_boxes = document
+            .page_iter()
+            .take(2)
+            .map(|page_id| pdf::page_box(&document, page_id, b"TrimBox").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trim_boxes,
+            [[10.0, 10.0, 100.0, 90.0], [100.0, 10.0, 190.0, 90.0]]
+        );
+    }
+
+    #[test_case("Subject=Job 4417" => Some(("Subject".into(), "Job 4417".into())))]
+    #[test_case("Keywords=a=b" => Some(("Keywords".into(), "a=b".into())))]
+    #[test_case("Title=" => Some(("Title".into(), "".into())))]
+    #[test_case("Title" => None)]
+    #[test_case("=value" => None)]
+    #[test_case("Job Number=4417" => None)]
+    fn parse_meta(value: &str) -> Option<(String, String)> {
+        super::parse_meta(value).ok()
+    }
+
+    #[test]
+    fn set_metadata() {
+        let args = [
+            "bookbinding",
+            "in.pdf",
+            "out.pdf",
+            "--set-meta",
+            "Producer=MyShop",
+            "--set-meta",
+            "Subject=Job 4417",
+            "--set-meta",
+            "Title=Café",
+            "--set-meta",
+            "Trapped=True",
+        ];
+        let args = Args::parse_from(args);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        super::set_metadata(&mut document, &args.set_meta, args.custom_meta).unwrap();
+        let info_id = document
+            .trailer
+            .get(b"Info")
+            .and_then(Object::as_reference)
+            .unwrap();
+        let info = document.get_dictionary(info_id).unwrap();
+        let get = |key: &[u8]| info.get(key).unwrap().clone();
+        assert_eq!(get(b"Producer"), Object::string_literal("MyShop"));
+        assert_eq!(get(b"Subject"), Object::string_literal("Job 4417"));
+        assert_eq!(
+            get(b"Title").as_str().unwrap(),
+            b"\xfe\xff\x00C\x00a\x00f\x00\xe9"
+        );
+        assert_eq!(get(b"Trapped"), Object::from("True"));
+
+        let custom = [("JobNumber".to_owned(), "4417".to_owned())];
+        assert!(super::set_metadata(&mut document, &custom, false).is_err());
+        super::set_metadata(&mut document, &custom, true).unwrap();
+        let info = document.get_dictionary(info_id).unwrap();
+        assert!(info.has(b"JobNumber"));
+    }
+
+    #[test]
+    fn calendar() {
+        let args = ["bookbinding", "in.pdf", "out.pdf", "--calendar"];
+        let args = Args::parse_from(super::expand_presets(args.map(Into::into)));
+        assert_eq!(args.signature_params.binding, Binding::Saddle);
+        assert!(args.head_binding);
+        assert!(!args.tumble);
+
+        // a cover and 12 months
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 13]);
+        super::pad(&mut document, &args).unwrap();
+        let plan = plan_signatures(Sheets(4), args.signature_params);
+        assert_eq!(plan.signatures, [4]);
+        super::impose(&mut document, &plan, None, None, &args).unwrap();
+        let pages = [15, 0, 1, 14, 13, 2, 3, 12, 11, 4, 5, 10, 9, 6, 7, 8]
+            .map(|index| Some(index).filter(|&index| index < 13));
+        assert_eq!(testing::page_indices(&document), pages);
+        // every page is turned anticlockwise, which puts the left-hand pages on top
+        let rotations = document
+            .page_iter()
+            .map(|page_id| pdf::rotation(&document, page_id))
+            .collect::<Vec<_>>();
+        assert_eq!(rotations, [270; 16]);
+    }
+
+    #[test]
+    fn reader_order_lines() {
+        let plan = plan_signatures(Sheets(1), Default::default());
+        assert_eq!(
+            super::reader_order_lines(&plan),
+            [
+                "Sheet  Side   Slot   Printer  Reader  Leaf",
+                "1      front  left   1        4       ok",
+                "1      front  right  2        1       ok",
+                "1      back   left   3        2       ok",
+                "1      back   right  4        3       ok",
+            ]
+        );
+    }
+
+    #[test_case("table", None => Ok((true, false, "1.4".into())))]
+    #[test_case("stream", None => Ok((false, true, "1.5".into())))]
+    #[test_case("stream", Some("1.6") => Ok((false, true, "1.4".into())))]
+    #[test_case("stream", Some("1.4") => Err(()))]
+    fn xref_format(format: &str, version: Option<&str>) -> Result<(bool, bool, String), ()> {
+        let args = Args::parse_from(["bookbinding", "in.pdf", "out.pdf", "--xref-format", format]);
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        document.version = "1.4".into();
+        super::set_xref_format(&mut document, args.xref_format.unwrap(), version)
+            .map_err(|_| ())?;
+        let mut output = Vec::new();
+        document.save_to(&mut output).unwrap();
+        let output = String::from_utf8_lossy(&output);
+        Ok((
+            output.contains("\nxref"),
+            output.contains("/XRef"),
+            document.version,
+        ))
+    }
+
+    #[test_case("1.4" => true)]
+    #[test_case("2.0" => true)]
+    #[test_case("1.8" => false)]
+    #[test_case("1" => false)]
+    fn pdf_version(version: &str) -> bool {
+        super::parse_pdf_version(version).is_ok()
+    }
+
+    #[test_case(0 => "1970-01-01")]
+    #[test_case(11016 => "2000-02-29")]
+    #[test_case(20741 => "2026-10-15")]
+    fn format_date(days: u64) -> String {
+        let time = super::UNIX_EPOCH + std::time::Duration::from_secs(days * 86400);
+        super::format_date(time)
+    }
 }
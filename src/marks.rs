@@ -0,0 +1,95 @@
+//! Printer's marks drawn in the margins around the pages placed on a sheet, lined up with the
+//! edges of the pages, which show where to cut and fold the printed sheet.
+
+use lopdf::content::Operation;
+
+/// Distance from the edge of the placed pages to the start of each mark.
+pub const MARK_OFFSET: f32 = 3.0;
+/// Length of each mark.
+pub const MARK_LENGTH: f32 = 12.0;
+/// Width of the marks.
+pub const MARK_WIDTH: f32 = 0.25;
+/// Space around the placed pages which holds the marks. A sheet with at least this margin around
+/// its pages has room for the marks without them overlapping the pages.
+pub const MARK_MARGIN: f32 = MARK_OFFSET + MARK_LENGTH + 3.0;
+
+/// Which edges of the pages placed on a side of a sheet are marked.
+///
+/// The edge between the two pages of one side of a folded sheet is where it is folded rather than
+/// cut, so the modes differ in whether it is marked: this is the fold between the pages of a
+/// spread, unlike the marks of `--grid` and `--french-fold`, where every edge is either cut or
+/// folded and all of them are marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Marks {
+    /// Every edge of every page, including the folds between the pages of each side.
+    All,
+    /// Only the outer edges of the pages, where the folded sheets are trimmed, as for booklets.
+    Outer,
+    /// Every edge which is cut: the outer edges, and the edges between pages which are cut apart
+    /// rather than folded, as for sheets which are cut and stacked.
+    Cut,
+}
+
+/// The marks for a block of pages `[x0, y0, x1, y1]`, as line segments `[x0, y0, x1, y1]` outside
+/// it: a pair above and below it lined up with each of `xs`, and a pair to its left and right
+/// lined up with each of `ys`.
+pub fn edge_marks([left, bottom, right, top]: [f32; 4], xs: &[f32], ys: &[f32]) -> Vec<[f32; 4]> {
+    let mut marks = Vec::new();
+    for &x in xs {
+        marks.push([x, top + MARK_OFFSET, x, top + MARK_OFFSET + MARK_LENGTH]);
+        marks.push([
+            x,
+            bottom - MARK_OFFSET,
+            x,
+            bottom - MARK_OFFSET - MARK_LENGTH,
+        ]);
+    }
+    for &y in ys {
+        marks.push([left - MARK_OFFSET, y, left - MARK_OFFSET - MARK_LENGTH, y]);
+        marks.push([right + MARK_OFFSET, y, right + MARK_OFFSET + MARK_LENGTH, y]);
+    }
+    marks
+}
+
+/// Operations which stroke the given line segments as thin black lines.
+pub fn mark_operations(marks: &[[f32; 4]]) -> Vec<Operation> {
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("w", vec![MARK_WIDTH.into()]),
+        Operation::new("G", vec![0.into()]),
+    ];
+    for &[x0, y0, x1, y1] in marks {
+        operations.extend([
+            Operation::new("m", vec![x0.into(), y0.into()]),
+            Operation::new("l", vec![x1.into(), y1.into()]),
+            Operation::new("S", vec![]),
+        ]);
+    }
+    operations.push(Operation::new("Q", vec![]));
+    operations
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MARK_LENGTH, MARK_MARGIN, MARK_OFFSET};
+
+    #[test]
+    fn edge_marks() {
+        let block = [20.0, 20.0, 120.0, 70.0];
+        let marks = super::edge_marks(block, &[20.0, 120.0], &[45.0]);
+        assert_eq!(marks.len(), 6);
+        assert_eq!(
+            marks[0],
+            [20.0, 73.0, 20.0, 70.0 + MARK_OFFSET + MARK_LENGTH]
+        );
+        assert_eq!(
+            marks[5],
+            [123.0, 45.0, 120.0 + MARK_OFFSET + MARK_LENGTH, 45.0]
+        );
+        // every mark fits in the margin
+        for [x0, y0, x1, y1] in marks {
+            assert!(x0.min(x1) >= block[0] - MARK_MARGIN && x0.max(x1) <= block[2] + MARK_MARGIN);
+            assert!(y0.min(y1) >= block[1] - MARK_MARGIN && y0.max(y1) <= block[3] + MARK_MARGIN);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! File names for writing each signature to a separate file.
+
+use std::collections::HashSet;
+
+/// Expands a file name pattern for one signature. The pattern can contain these placeholders:
+///
+/// - `{stem}`: the file name of the output, without its extension.
+/// - `{sig}`: the number of the signature, starting from 1.
+/// - `{total}`: the number of signatures.
+///
+/// Numbers can be padded with zeros to a minimum width, such as `{sig:02}`. Braces are written as
+/// `{{` and `}}`.
+pub fn expand(pattern: &str, stem: &str, signature: usize, total: usize) -> Result<String, String> {
+    let mut name = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                name.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                name.push('}');
+            }
+            '{' => {
+                let (placeholder, rest) = chars
+                    .as_str()
+                    .split_once('}')
+                    .ok_or_else(|| format!("unclosed `{{` in name pattern {pattern:?}"))?;
+                let (key, width) = match placeholder.split_once(':') {
+                    Some((key, width)) => (key, Some(width)),
+                    None => (placeholder, None),
+                };
+                let number = match key {
+                    "stem" if width.is_none() => {
+                        name.push_str(stem);
+                        chars = rest.chars();
+                        continue;
+                    }
+                    "sig" => signature,
+                    "total" => total,
+                    _ => return Err(format!("unknown placeholder `{{{placeholder}}}`")),
+                };
+                match width {
+                    Some(width) => {
+                        let width = width
+                            .strip_prefix('0')
+                            .and_then(|width| width.parse::<usize>().ok())
+                            .ok_or_else(|| {
+                                format!("invalid width in `{{{placeholder}}}`, expected `:0N`")
+                            })?;
+                        name.push_str(&format!("{number:0width$}"));
+                    }
+                    None => name.push_str(&number.to_string()),
+                }
+                chars = rest.chars();
+            }
+            '}' => return Err(format!("unmatched `}}` in name pattern {pattern:?}")),
+            c => name.push(c),
+        }
+    }
+    Ok(name)
+}
+
+/// The file name of each signature, checking that they are all different, so that no signature
+/// overwrites another.
+pub fn signature_names(pattern: &str, stem: &str, total: usize) -> Result<Vec<String>, String> {
+    let names = (1..=total)
+        .map(|signature| expand(pattern, stem, signature, total))
+        .collect::<Result<Vec<_>, _>>()?;
+    if names.iter().collect::<HashSet<_>>().len() < names.len() {
+        return Err(format!(
+            "name pattern {pattern:?} gives the same file name to more than one signature; \
+             include `{{sig}}` in it"
+        ));
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    #[test_case("{stem}_sig{sig:02}_of_{total}.pdf", 3 => Ok("book_sig03_of_12.pdf".into()))]
+    #[test_case("{sig}-{total:03}.pdf", 3 => Ok("3-012.pdf".into()))]
+    #[test_case("{stem}.{sig:04}.pdf", 12 => Ok("book.0012.pdf".into()))]
+    #[test_case("{{{sig}}}.pdf", 1 => Ok("{1}.pdf".into()))]
+    #[test_case("{signature}.pdf", 1 => Err("unknown placeholder `{signature}`".into()))]
+    #[test_case("{sig:2}.pdf", 1 => Err("invalid width in `{sig:2}`, expected `:0N`".into()))]
+    #[test_case("{sig.pdf", 1 => Err(r#"unclosed `{` in name pattern "{sig.pdf""#.into()))]
+    #[test_case("sig}.pdf", 1 => Err(r#"unmatched `}` in name pattern "sig}.pdf""#.into()))]
+    fn expand(pattern: &str, signature: usize) -> Result<String, String> {
+        super::expand(pattern, "book", signature, 12)
+    }
+
+    #[test_case("{stem}_{sig}.pdf", 3 => Some("book_1.pdf book_2.pdf book_3.pdf".into()))]
+    #[test_case("{stem}_{sig:02}.pdf", 1 => Some("book_01.pdf".into()))]
+    #[test_case("{stem}.pdf", 1 => Some("book.pdf".into()))]
+    #[test_case("{stem}.pdf", 2 => None)]
+    #[test_case("{stem}_of_{total}.pdf", 4 => None)]
+    fn signature_names(pattern: &str, total: usize) -> Option<String> {
+        Some(
+            super::signature_names(pattern, "book", total)
+                .ok()?
+                .join(" "),
+        )
+    }
+}
@@ -0,0 +1,206 @@
+//! Reading the pages of a PDF, for both placing them on sheets and previewing the sheets.
+
+use std::collections::BTreeMap;
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+/// Looks up an attribute of a page, following references and inheriting it from the page's
+/// ancestors in the page tree if the page does not set it directly.
+pub fn inherited<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    match inherited_entry(document, page_id, key)? {
+        Object::Reference(id) => document.get_object(*id).ok(),
+        value => Some(value),
+    }
+}
+
+/// Looks up an attribute of a page, inheriting it from the page's ancestors in the page tree if
+/// the page does not set it directly, without following references.
+pub fn inherited_entry<'a>(
+    document: &'a Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node = document.get_dictionary(page_id).ok()?;
+    loop {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node = document
+            .get_dictionary(node.get(b"Parent").ok()?.as_reference().ok()?)
+            .ok()?;
+    }
+}
+
+/// Reads a rectangle attribute of a page (such as `/MediaBox`), including inherited values.
+pub fn page_box(document: &Document, page_id: ObjectId, key: &[u8]) -> Option<[f32; 4]> {
+    let values = inherited(document, page_id, key)?.as_array().ok()?;
+    let values = values
+        .iter()
+        .map(Object::as_float)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    let [x0, y0, x1, y1] = <[f32; 4]>::try_from(values).ok()?;
+    // normalize so that the first corner is the lower left
+    Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+}
+
+/// The effective rotation of a page in degrees clockwise, normalized to one of 0, 90, 180, or
+/// 270. Rotations which are not a multiple of 90 are invalid, and are treated as 0.
+pub fn rotation(document: &Document, page_id: ObjectId) -> i64 {
+    let rotate = inherited(document, page_id, b"Rotate")
+        .and_then(|rotate| rotate.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360);
+    if rotate % 90 == 0 {
+        rotate
+    } else {
+        0
+    }
+}
+
+/// Which of a page's boundary boxes defines the region of the page that is placed on the sheet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PlacedBox {
+    /// The whole media box.
+    Media,
+    /// The crop box, which is the region a viewer shows. Defaults to the media box.
+    Crop,
+    /// The trim box, which is the intended size of the finished page. Defaults to the crop box.
+    Trim,
+}
+
+impl PlacedBox {
+    /// The keys of the boxes to use, in order of preference, following the defaults for missing
+    /// boxes.
+    fn keys(self) -> &'static [&'static [u8]] {
+        match self {
+            PlacedBox::Media => &[b"MediaBox"],
+            PlacedBox::Crop => &[b"CropBox", b"MediaBox"],
+            PlacedBox::Trim => &[b"TrimBox", b"CropBox", b"MediaBox"],
+        }
+    }
+}
+
+/// The region of a page that is placed on the sheet, including inherited values. Like a viewer,
+/// this clips the chosen box to the media box.
+pub fn placed_box(document: &Document, page_id: ObjectId, which: PlacedBox) -> Option<[f32; 4]> {
+    let [mx0, my0, mx1, my1] = page_box(document, page_id, b"MediaBox")?;
+    let [x0, y0, x1, y1] = which
+        .keys()
+        .iter()
+        .find_map(|&key| page_box(document, page_id, key))?;
+    Some([x0.max(mx0), y0.max(my0), x1.min(mx1), y1.min(my1)])
+}
+
+/// The references to a page's content streams, in order. `/Contents` can be a single stream, an
+/// array of streams, or a reference to such an array.
+pub fn content_streams(document: &Document, page_id: ObjectId) -> lopdf::Result<Vec<Object>> {
+    Ok(match document.get_dictionary(page_id)?.get(b"Contents") {
+        Ok(Object::Array(contents)) => contents.clone(),
+        Ok(Object::Reference(id)) => match document.get_object(*id)? {
+            // an indirect array of content streams
+            Object::Array(contents) => contents.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        _ => Vec::new(),
+    })
+}
+
+/// The decoded content of a page, with its content streams joined in order. The streams are
+/// separated by newlines, since a stream can end in the middle of a line, and the last token of
+/// one stream must not run into the first token of the next.
+pub fn concatenate_contents(document: &Document, page_id: ObjectId) -> lopdf::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    for stream in content_streams(document, page_id)? {
+        let stream = document.dereference(&stream)?.1.as_stream()?;
+        if !content.is_empty() {
+            content.push(b'\n');
+        }
+        match stream.decompressed_content() {
+            Ok(decompressed) => content.extend(decompressed),
+            Err(_) => content.extend(&stream.content),
+        }
+    }
+    Ok(content)
+}
+
+/// A form XObject which draws the content of a page, so that it can be placed on another page,
+/// with `bbox` as its bounding box. Its coordinate space is the page's own, ignoring its rotation.
+/// The page's resources are used as they are, referring to the objects of `document`, and its
+/// transparency group, if any, becomes the XObject's group, so that its content is blended the
+/// same way. Its private data in `/PieceInfo`, if any, is kept along with its `/LastModified`
+/// date, which form XObjects have in the same way as pages.
+pub fn page_form(document: &Document, page_id: ObjectId, bbox: [f32; 4]) -> lopdf::Result<Stream> {
+    let resources = inherited_entry(document, page_id, b"Resources")
+        .cloned()
+        .unwrap_or_else(|| Object::Dictionary(Dictionary::new()));
+    let page = document.get_dictionary(page_id)?;
+    let kept = ["Group", "PieceInfo", "LastModified"]
+        .into_iter()
+        .filter_map(|key| Some((key, page.get(key.as_bytes()).ok()?.clone())));
+    let content = concatenate_contents(document, page_id)?;
+    let dictionary = [
+        ("Type", Object::from("XObject")),
+        ("Subtype", Object::from("Form")),
+        ("BBox", Object::from(bbox.map(Object::from).to_vec())),
+        ("Resources", resources),
+    ]
+    .into_iter()
+    .chain(kept)
+    .collect();
+    Ok(Stream::new(dictionary, content))
+}
+
+/// Copies an object from `document` into `output`, along with every object it refers to. Objects
+/// which have already been copied are reused, according to the map from their ids in `document`
+/// to their ids in `output`. References to missing objects become null.
+pub fn copy_object(
+    document: &Document,
+    output: &mut Document,
+    copied: &mut BTreeMap<ObjectId, ObjectId>,
+    object: &Object,
+) -> Object {
+    match object {
+        Object::Reference(id) => {
+            if let Some(&new_id) = copied.get(id) {
+                return Object::Reference(new_id);
+            }
+            let new_id = output.new_object_id();
+            // record the copy first, so that cycles end
+            copied.insert(*id, new_id);
+            let copy = match document.get_object(*id) {
+                Ok(object) => copy_object(document, output, copied, object),
+                Err(_) => Object::Null,
+            };
+            output.set_object(new_id, copy);
+            Object::Reference(new_id)
+        }
+        Object::Array(items) => Object::Array(
+            items
+                .iter()
+                .map(|item| copy_object(document, output, copied, item))
+                .collect(),
+        ),
+        Object::Dictionary(dict) => {
+            Object::Dictionary(copy_dictionary(document, output, copied, dict))
+        }
+        Object::Stream(stream) => {
+            let mut stream = stream.clone();
+            stream.dict = copy_dictionary(document, output, copied, &stream.dict);
+            Object::Stream(stream)
+        }
+        object => object.clone(),
+    }
+}
+
+/// Copies a dictionary and the objects it refers to. See [`copy_object`].
+pub fn copy_dictionary(
+    document: &Document,
+    output: &mut Document,
+    copied: &mut BTreeMap<ObjectId, ObjectId>,
+    dict: &Dictionary,
+) -> Dictionary {
+    dict.iter()
+        .map(|(key, value)| (key.clone(), copy_object(document, output, copied, value)))
+        .collect()
+}
@@ -0,0 +1,1942 @@
+//! Helpers for inspecting and generating PDF pages.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+pub use bookbinding::page::{
+    concatenate_contents, inherited, page_box, placed_box, rotation, PlacedBox,
+};
+use bookbinding::page::{content_streams, inherited_entry, page_form};
+use color_eyre::eyre::eyre;
+use lopdf::{
+    content::Operation, Content, Dictionary, Document, Object, ObjectId, Stream, StringFormat,
+};
+
+use crate::{
+    font::{self, Font},
+    length::POINTS_PER_MM,
+    transform::PageTransform,
+};
+
+/// Width and height of a US Letter page, in points.
+pub const LETTER: (f32, f32) = (612.0, 792.0);
+
+/// Hashes the decoded content of a page. Pages whose content streams decode to the same bytes
+/// will have the same hash, regardless of how those streams are compressed.
+pub fn content_hash(document: &Document, page_id: ObjectId) -> lopdf::Result<u64> {
+    let content = concatenate_contents(document, page_id)?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Whether a page draws nothing visible. This is the basis of every feature which treats blank
+/// pages differently, such as `--trim-trailing-blanks`.
+///
+/// The page's content is parsed, and only operators which mark the page count: painted paths,
+/// shown text (unless it is invisible, with text rendering mode 3 or 7), images, form XObjects,
+/// and shadings. A painted path whose bounding box on the page, including the width of any
+/// stroke, has an area of less than `tolerance` square points does not count either, so that
+/// specks left over in otherwise blank pages can be ignored. A page whose content can't be
+/// parsed is assumed not to be blank.
+pub fn is_blank(document: &Document, page_id: ObjectId, tolerance: f32) -> lopdf::Result<bool> {
+    let content = concatenate_contents(document, page_id)?;
+    if content.iter().all(u8::is_ascii_whitespace) {
+        return Ok(true);
+    }
+    Ok(match Content::decode(&content) {
+        Ok(content) => !marks_page(&content.operations, tolerance),
+        Err(_) => false,
+    })
+}
+
+/// Whether any of the operations marks the page. See [`is_blank`].
+fn marks_page(operations: &[Operation], tolerance: f32) -> bool {
+    // the transform, line width, and text rendering mode, which are saved by `q`
+    let mut state = (PageTransform::IDENTITY, 1.0, 0);
+    let mut saved = Vec::new();
+    // the bounding box of the current path, in user space
+    let mut path: Option<[f32; 4]> = None;
+    for operation in operations {
+        let numbers = operation
+            .operands
+            .iter()
+            .filter_map(|operand| operand.as_float().ok())
+            .collect::<Vec<_>>();
+        let mut extend = |points: &[f32]| {
+            for point in points.chunks_exact(2) {
+                let [x0, y0, x1, y1] = path.unwrap_or([point[0], point[1], point[0], point[1]]);
+                path = Some([
+                    x0.min(point[0]),
+                    y0.min(point[1]),
+                    x1.max(point[0]),
+                    y1.max(point[1]),
+                ]);
+            }
+        };
+        match operation.operator.as_str() {
+            "q" => saved.push(state),
+            "Q" => state = saved.pop().unwrap_or(state),
+            "cm" => {
+                if let Ok(matrix) = <[f32; 6]>::try_from(numbers) {
+                    let transform = PageTransform { matrix, clip: None };
+                    state.0 = transform.then(state.0.matrix);
+                }
+            }
+            "w" => state.1 = numbers.first().copied().unwrap_or(state.1),
+            "Tr" => state.2 = numbers.first().map_or(state.2, |&mode| mode as i64),
+            "m" | "l" | "c" | "v" | "y" => extend(&numbers),
+            "re" => {
+                if let [x, y, width, height] = numbers[..] {
+                    extend(&[x, y, x + width, y + height]);
+                }
+            }
+            "n" => path = None,
+            operator @ ("S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*") => {
+                let Some(bounds) = path.take() else {
+                    continue;
+                };
+                let [x0, y0, x1, y1] = state.0.transform_rect(bounds);
+                let stroked = matches!(operator, "S" | "s" | "B" | "B*" | "b" | "b*");
+                let [a, b, c, d, ..] = state.0.matrix;
+                let stroke = if stroked {
+                    state.1.max(0.0) * (a * d - b * c).abs().sqrt()
+                } else {
+                    0.0
+                };
+                if (x1 - x0 + stroke) * (y1 - y0 + stroke) >= tolerance {
+                    return true;
+                }
+            }
+            "Tj" | "TJ" | "'" | "\"" if !matches!(state.2, 3 | 7) => return true,
+            "Do" | "sh" | "BI" | "EI" => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The page boundary boxes, all of which are transformed when the page's coordinate space is.
+pub const BOXES: [&[u8]; 5] = [b"MediaBox", b"CropBox", b"BleedBox", b"TrimBox", b"ArtBox"];
+
+/// The page attributes which are inherited from the page tree if a page does not set them.
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// A copy of a page's dictionary with its inherited attributes copied onto it, so that it looks
+/// the same wherever it is placed in the page tree.
+pub fn page_with_inherited(document: &Document, page_id: ObjectId) -> lopdf::Result<Dictionary> {
+    let mut page = document.get_dictionary(page_id)?.clone();
+    for key in INHERITABLE {
+        if let Some(value) = inherited_entry(document, page_id, key) {
+            page.set(key, value.clone());
+        }
+    }
+    Ok(page)
+}
+
+/// Replaces the children of the root page tree node with the given pages, in order. Any inherited
+/// attributes are first copied onto each page, so that they look the same in their new position.
+///
+/// Pages which are not listed are removed from the page tree, but their objects are left in the
+/// document. Each page must be listed at most once.
+pub fn set_page_order(document: &mut Document, page_ids: &[ObjectId]) -> lopdf::Result<()> {
+    let root_id = document.catalog()?.get(b"Pages")?.as_reference()?;
+    for &page_id in page_ids {
+        let attributes = INHERITABLE
+            .iter()
+            .filter_map(|&key| Some((key, inherited_entry(document, page_id, key)?.clone())))
+            .collect::<Vec<_>>();
+        let page = document.get_dictionary_mut(page_id)?;
+        for (key, value) in attributes {
+            page.set(key, value);
+        }
+        page.set("Parent", root_id);
+    }
+    let root = document.get_dictionary_mut(root_id)?;
+    root.set(
+        "Kids",
+        page_ids
+            .iter()
+            .map(|&id| Object::from(id))
+            .collect::<Vec<_>>(),
+    );
+    root.set("Count", page_ids.len() as i64);
+    Ok(())
+}
+
+/// Sets the document's viewer preferences so that facing pages are shown from right to left.
+pub fn set_direction_rtl(document: &mut Document) -> lopdf::Result<()> {
+    let catalog = document.catalog_mut()?;
+    let mut preferences = catalog
+        .get(b"ViewerPreferences")
+        .and_then(Object::as_dict)
+        .cloned()
+        .unwrap_or_default();
+    preferences.set("Direction", "R2L");
+    catalog.set("ViewerPreferences", preferences);
+    Ok(())
+}
+
+/// Whether the document is tagged, with a structure tree describing its logical structure.
+pub fn is_tagged(document: &Document) -> bool {
+    document
+        .catalog()
+        .is_ok_and(|catalog| catalog.has(b"StructTreeRoot"))
+}
+
+/// Removes the structure tree from a tagged document, along with the entries on pages and
+/// annotations which refer to it, so that no references to the removed structure remain. The
+/// marked content in the page contents is left in place, since it is valid without a structure
+/// tree. Objects which are no longer used are left in the document until it is pruned.
+pub fn drop_structure(document: &mut Document) -> lopdf::Result<()> {
+    let catalog = document.catalog_mut()?;
+    catalog.remove(b"StructTreeRoot");
+    catalog.remove(b"MarkInfo");
+    for object in document.objects.values_mut() {
+        if let Ok(dict) = object.as_dict_mut() {
+            dict.remove(b"StructParents");
+            dict.remove(b"StructParent");
+        }
+    }
+    Ok(())
+}
+
+/// Sets a page's media box and crop box to the region that is placed on the sheet, so that none of
+/// the content outside of that region is shown.
+pub fn clip_to_box(
+    document: &mut Document,
+    page_id: ObjectId,
+    which: PlacedBox,
+) -> color_eyre::Result<()> {
+    let rect = placed_box(document, page_id, which)
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    let page = document.get_dictionary_mut(page_id)?;
+    for key in ["MediaBox", "CropBox"] {
+        page.set(key, rect.map(Object::from).to_vec());
+    }
+    Ok(())
+}
+
+/// Splits a page down the middle as it is displayed, such as a spread of two pages. The page is
+/// cropped to its left half, and a copy of it cropped to its right half is added to the document,
+/// but not to the page tree. Returns the id of the copy. Any bleed, trim, or art box is also
+/// clipped to each half.
+pub fn split_page(document: &mut Document, page_id: ObjectId) -> color_eyre::Result<ObjectId> {
+    let [x0, y0, x1, y1] = placed_box(document, page_id, PlacedBox::Crop)
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    let (mid_x, mid_y) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+    let (first, second) = ([x0, y0, mid_x, y1], [mid_x, y0, x1, y1]);
+    let (bottom, top) = ([x0, y0, x1, mid_y], [x0, mid_y, x1, y1]);
+    // rotating the page clockwise moves the bottom of the media box to the left of the display
+    let (left, right) = match rotation(document, page_id) {
+        90 => (bottom, top),
+        180 => (second, first),
+        270 => (top, bottom),
+        _ => (first, second),
+    };
+    let mut copy = page_with_inherited(document, page_id)?;
+    for (page, half) in [
+        (&mut copy, right),
+        (document.get_dictionary_mut(page_id)?, left),
+    ] {
+        page.set("CropBox", half.map(Object::from).to_vec());
+        for key in [&b"BleedBox"[..], b"TrimBox", b"ArtBox"] {
+            let Some([bx0, by0, bx1, by1]) = page
+                .get(key)
+                .and_then(Object::as_array)
+                .ok()
+                .and_then(|values| {
+                    let values = values.iter().map(Object::as_float);
+                    <[f32; 4]>::try_from(values.collect::<Result<Vec<_>, _>>().ok()?).ok()
+                })
+            else {
+                continue;
+            };
+            let clipped = [
+                bx0.min(bx1).max(half[0]),
+                by0.min(by1).max(half[1]),
+                bx0.max(bx1).min(half[2]),
+                by0.max(by1).min(half[3]),
+            ];
+            if clipped[0] < clipped[2] && clipped[1] < clipped[3] {
+                page.set(key, clipped.map(Object::from).to_vec());
+            } else {
+                page.remove(key);
+            }
+        }
+    }
+    Ok(document.add_object(copy))
+}
+
+/// Distances in points to trim from the edges of a page, as it is displayed. The spine edge is
+/// never trimmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Trim {
+    pub head: f32,
+    pub foot: f32,
+    pub fore_edge: f32,
+}
+
+/// Sets a page's trim box and crop box to its crop box with the given distances trimmed from its
+/// edges. The fore-edge is on the right of the displayed page if `fore_edge_right` is set, and on
+/// the left otherwise. The page's rotation is taken into account, so that the head is trimmed from
+/// the top of the page as it is displayed.
+pub fn trim_page(
+    document: &mut Document,
+    page_id: ObjectId,
+    trim: Trim,
+    fore_edge_right: bool,
+) -> color_eyre::Result<()> {
+    let [x0, y0, x1, y1] = placed_box(document, page_id, PlacedBox::Crop)
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    let (right, left) = if fore_edge_right {
+        (trim.fore_edge, 0.0)
+    } else {
+        (0.0, trim.fore_edge)
+    };
+    // the insets from the top, right, bottom, and left of the page as displayed
+    let displayed = [trim.head, right, trim.foot, left];
+    // rotating the page clockwise moves each edge of the media box one place around the display
+    let turns = (rotation(document, page_id) / 90) as usize;
+    let [top, right, bottom, left] = [0, 1, 2, 3].map(|edge| displayed[(edge + turns) % 4]);
+    let rect = [x0 + left, y0 + bottom, x1 - right, y1 - top];
+    if rect[0] >= rect[2] || rect[1] >= rect[3] {
+        color_eyre::eyre::bail!("trimming page {page_id:?} would leave nothing");
+    }
+    let page = document.get_dictionary_mut(page_id)?;
+    for key in ["TrimBox", "CropBox"] {
+        page.set(key, rect.map(Object::from).to_vec());
+    }
+    Ok(())
+}
+
+/// Applies a page's `/Rotate` to its content, so that it appears the same but has a rotation of
+/// 0. The page's boxes are rotated to match.
+pub fn bake_rotation(document: &mut Document, page_id: ObjectId) -> color_eyre::Result<()> {
+    let rotate = rotation(document, page_id);
+    if rotate == 0 {
+        return Ok(());
+    }
+    let media_box = page_box(document, page_id, b"MediaBox")
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    PageTransform::rotation(rotate, media_box).apply(document, page_id)?;
+    document.get_dictionary_mut(page_id)?.set("Rotate", 0);
+    Ok(())
+}
+
+/// Moves a page's content and boxes so that the lower left corner of its media box is at the
+/// origin, for pages whose media box starts elsewhere, such as `[100 100 712 892]`.
+pub fn normalize_origin(document: &mut Document, page_id: ObjectId) -> color_eyre::Result<()> {
+    let [x0, y0, ..] = page_box(document, page_id, b"MediaBox")
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    PageTransform::IDENTITY
+        .then([1.0, 0.0, 0.0, 1.0, -x0, -y0])
+        .apply(document, page_id)
+}
+
+/// How a page is scaled to a different size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Fit {
+    /// Scale the page to fit inside the new size, keeping its proportions, and center it. If the
+    /// proportions differ, there is a margin on two of the sides.
+    #[default]
+    Contain,
+    /// Scale the page to cover the whole of the new size, keeping its proportions, and center it.
+    /// If the proportions differ, the content which overflows two of the sides is cropped.
+    Cover,
+    /// Scale the width and height of the page separately to match the new size exactly, which
+    /// distorts the content if the proportions differ.
+    Stretch,
+}
+
+/// Scales a page to the given size as it is displayed, such as the trim size of a book printed
+/// from larger pages. The page's content is scaled from its media box and clipped to the new size,
+/// which becomes its media box, crop box, and trim box. The media box should already be clipped to
+/// the part of the page to keep, as with [`clip_to_box`].
+pub fn scale_to_size(
+    document: &mut Document,
+    page_id: ObjectId,
+    (width, height): (f32, f32),
+    fit: Fit,
+) -> color_eyre::Result<()> {
+    let [x0, y0, x1, y1] = page_box(document, page_id, b"MediaBox")
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    // the size is as displayed, so it is turned along with a page which is displayed on its side
+    let (width, height) = match rotation(document, page_id) {
+        90 | 270 => (height, width),
+        _ => (width, height),
+    };
+    let (scale_x, scale_y) = (width / (x1 - x0), height / (y1 - y0));
+    let (scale_x, scale_y) = match fit {
+        Fit::Contain => (scale_x.min(scale_y), scale_x.min(scale_y)),
+        Fit::Cover => (scale_x.max(scale_y), scale_x.max(scale_y)),
+        Fit::Stretch => (scale_x, scale_y),
+    };
+    let (offset_x, offset_y) = (
+        (width - (x1 - x0) * scale_x) / 2.0,
+        (height - (y1 - y0) * scale_y) / 2.0,
+    );
+    let transform = PageTransform::IDENTITY
+        .then([1.0, 0.0, 0.0, 1.0, -x0, -y0])
+        .then([scale_x, 0.0, 0.0, scale_y, offset_x, offset_y]);
+    // the content is clipped to whichever is smaller of the scaled page and the new size
+    let [left, bottom, right, top] = transform.transform_rect([x0, y0, x1, y1]);
+    let clip = [
+        left.max(0.0),
+        bottom.max(0.0),
+        right.min(width),
+        top.min(height),
+    ];
+    PageTransform {
+        clip: Some(clip),
+        ..transform
+    }
+    .apply(document, page_id)?;
+    let size = [0.0, 0.0, width, height];
+    let page = document.get_dictionary_mut(page_id)?;
+    for key in ["MediaBox", "CropBox", "TrimBox"] {
+        page.set(key, size.map(Object::from).to_vec());
+    }
+    Ok(())
+}
+
+/// Balances the graphics state operators of a page's content, so that wrapping it in other
+/// content, such as when placing it as a form XObject, is not affected by it. Each `Q` without an
+/// earlier `q` to restore is removed, and a `Q` is added at the end for each `q` which is never
+/// restored. The page's content is only rewritten, as a single stream, if anything was changed.
+/// Returns the number of operators removed and added.
+///
+/// Other problems, such as `BX`/`EX` compatibility sections, are left as they are, and it is an
+/// error if the content cannot be parsed at all, such as because of a broken inline image.
+pub fn balance_graphics_state(document: &mut Document, page_id: ObjectId) -> lopdf::Result<usize> {
+    let mut content = Content::decode(&concatenate_contents(document, page_id)?)?;
+    let (mut depth, mut removed) = (0usize, 0);
+    content
+        .operations
+        .retain(|operation| match operation.operator.as_str() {
+            "q" => {
+                depth += 1;
+                true
+            }
+            "Q" if depth == 0 => {
+                removed += 1;
+                false
+            }
+            "Q" => {
+                depth -= 1;
+                true
+            }
+            _ => true,
+        });
+    if removed + depth == 0 {
+        return Ok(0);
+    }
+    content
+        .operations
+        .extend((0..depth).map(|_| Operation::new("Q", Vec::new())));
+    let content_id = document.add_object(Stream::new(Dictionary::new(), content.encode()?));
+    document
+        .get_dictionary_mut(page_id)?
+        .set("Contents", content_id);
+    Ok(removed + depth)
+}
+
+/// Adds content to the start and end of a page's content, leaving its existing content streams
+/// untouched. Newlines are added around the existing content, so that its tokens are kept
+/// separate from the added ones.
+pub fn wrap_contents(
+    document: &mut Document,
+    page_id: ObjectId,
+    mut before: Vec<u8>,
+    after: Vec<u8>,
+) -> lopdf::Result<()> {
+    let mut contents = content_streams(document, page_id)?;
+    before.push(b'\n');
+    let after = [b"\n".as_slice(), &after].concat();
+    let before_id = document.add_object(Stream::new(Dictionary::new(), before));
+    let after_id = document.add_object(Stream::new(Dictionary::new(), after));
+    contents.insert(0, before_id.into());
+    contents.push(after_id.into());
+    document
+        .get_dictionary_mut(page_id)?
+        .set("Contents", contents);
+    Ok(())
+}
+
+/// Moves all of the objects of another document into this one, renumbering them so that they do
+/// not collide with existing objects. Inherited attributes are copied onto each of the other
+/// document's pages, so that they can be added to this document's page tree with
+/// [`set_page_order`]. Returns the ids of the other document's pages, in order.
+pub fn merge_document(
+    document: &mut Document,
+    mut other: Document,
+) -> lopdf::Result<Vec<ObjectId>> {
+    other.renumber_objects_with(document.max_id + 1);
+    let page_ids = other.page_iter().collect::<Vec<_>>();
+    set_page_order(&mut other, &page_ids)?;
+    let max_id = other.objects.keys().map(|&(id, _)| id).max();
+    document.max_id = document.max_id.max(max_id.unwrap_or(0));
+    document.objects.extend(other.objects);
+    Ok(page_ids)
+}
+
+/// Renumbers the objects of the document so that the ones needed to show the first page come
+/// first: the catalog, the root of the page tree, the first page, and everything the first page
+/// refers to (other than its parent). Since objects are written in order of their numbers, a
+/// viewer reading the file as it downloads can show the first page sooner.
+///
+/// This is not full linearization: no linearization dictionary or hint tables are written, so
+/// viewers do not treat the file as linearized, and the cross-reference table is still at the end.
+///
+/// References to objects which do not exist are replaced with null, which is what they refer to,
+/// since they would otherwise refer to whichever object now has their number.
+pub fn first_page_first(document: &mut Document) -> lopdf::Result<()> {
+    let catalog_id = document.trailer.get(b"Root")?.as_reference()?;
+    let root_id = document.catalog()?.get(b"Pages")?.as_reference()?;
+    let mut first = vec![catalog_id, root_id];
+    let mut visited = first.iter().copied().collect::<BTreeSet<_>>();
+    let mut stack = document.page_iter().take(1).collect::<Vec<_>>();
+    while let Some(id) = stack.pop() {
+        let Ok(object) = document.get_object(id) else {
+            continue;
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        first.push(id);
+        let mut references = Vec::new();
+        collect_references(object, &mut references);
+        // visit the references in the order they appear
+        stack.extend(references.into_iter().rev());
+    }
+    let rest = document
+        .objects
+        .keys()
+        .copied()
+        .filter(|id| !visited.contains(id));
+    let numbers = first
+        .iter()
+        .copied()
+        .chain(rest)
+        .zip(1..)
+        .map(|(id, number)| (id, (number, 0)))
+        .collect::<BTreeMap<_, _>>();
+    let objects = std::mem::take(&mut document.objects);
+    document.objects = objects
+        .into_iter()
+        .filter_map(|(id, mut object)| {
+            renumber_references(&mut object, &numbers);
+            Some((*numbers.get(&id)?, object))
+        })
+        .collect();
+    for (_, value) in document.trailer.iter_mut() {
+        renumber_references(value, &numbers);
+    }
+    document.max_id = numbers.len() as u32;
+    Ok(())
+}
+
+/// Adds the ids of the objects an object refers to, in order, leaving out the parent of a page.
+fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => references.push(*id),
+        Object::Array(items) => {
+            for item in items {
+                collect_references(item, references);
+            }
+        }
+        Object::Dictionary(dict) | Object::Stream(Stream { dict, .. }) => {
+            for (_, value) in dict.iter().filter(|(key, _)| key.as_slice() != b"Parent") {
+                collect_references(value, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces each reference in an object with its new id. References to objects which are not in
+/// the map are replaced with null.
+fn renumber_references(object: &mut Object, numbers: &BTreeMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => match numbers.get(id) {
+            Some(&number) => *id = number,
+            None => *object = Object::Null,
+        },
+        Object::Array(items) => {
+            for item in items {
+                renumber_references(item, numbers);
+            }
+        }
+        Object::Dictionary(dict) | Object::Stream(Stream { dict, .. }) => {
+            for (_, value) in dict.iter_mut() {
+                renumber_references(value, numbers);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Adds a form XObject which draws the content of a page, clipped to its crop box, so that it can
+/// be placed on another page. Returns the id of the XObject, whose coordinate space is the page's
+/// own, ignoring its rotation. The page's transparency group, if any, becomes the XObject's group,
+/// so that its content is blended the same way.
+pub fn page_xobject(document: &mut Document, page_id: ObjectId) -> color_eyre::Result<ObjectId> {
+    let bbox = placed_box(document, page_id, PlacedBox::Crop)
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    let xobject = page_form(document, page_id, bbox)?;
+    Ok(document.add_object(xobject))
+}
+
+/// A PDF/X standard for exchanging print-ready files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PdfX {
+    /// PDF/X-1a:2001, which uses PDF 1.3 and only allows CMYK and spot colours.
+    #[value(name = "1a")]
+    X1a,
+    /// PDF/X-4, which uses PDF 1.6 and allows transparency and colour management.
+    #[value(name = "4")]
+    X4,
+}
+
+impl PdfX {
+    /// The PDF version required by the standard.
+    pub fn pdf_version(self) -> &'static str {
+        match self {
+            PdfX::X1a => "1.3",
+            PdfX::X4 => "1.6",
+        }
+    }
+}
+
+/// The registered output condition used for the output intent, US Web Coated (SWOP).
+const OUTPUT_CONDITION: &str = "CGATS TR 001";
+
+/// Adds the document structure required by a PDF/X standard: the PDF version, an output intent,
+/// the identifying entries in the document information dictionary, and a trim box on every page.
+/// Pages without a trim box are given one matching their crop box. `date` is used as the creation
+/// and modification date, in the PDF date format.
+///
+/// This does not make the document fully compliant. In particular, it does not embed fonts, add an
+/// ICC profile or XMP metadata, or check colour spaces and transparency. See [`unembedded_fonts`]
+/// for one of the checks that is needed.
+pub fn add_pdfx(document: &mut Document, standard: PdfX, date: &str) -> color_eyre::Result<()> {
+    document.version = standard.pdf_version().to_owned();
+    let output_intent = Object::Dictionary(
+        [
+            ("Type", Object::from("OutputIntent")),
+            ("S", Object::from("GTS_PDFX")),
+            (
+                "OutputConditionIdentifier",
+                Object::string_literal(OUTPUT_CONDITION),
+            ),
+            (
+                "RegistryName",
+                Object::string_literal("http://www.color.org"),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let output_intent_id = document.add_object(output_intent);
+    document
+        .catalog_mut()?
+        .set("OutputIntents", vec![Object::from(output_intent_id)]);
+
+    let (version, conformance) = match standard {
+        PdfX::X1a => ("PDF/X-1:2001", Some("PDF/X-1a:2001")),
+        PdfX::X4 => ("PDF/X-4", None),
+    };
+    set_info(document, "GTS_PDFXVersion", Object::string_literal(version))?;
+    if let Some(conformance) = conformance {
+        set_info(
+            document,
+            "GTS_PDFXConformance",
+            Object::string_literal(conformance),
+        )?;
+    }
+    set_info(document, "Trapped", Object::from("False"))?;
+    set_info(document, "CreationDate", Object::string_literal(date))?;
+    set_info(document, "ModDate", Object::string_literal(date))?;
+
+    for page_id in document.page_iter().collect::<Vec<_>>() {
+        let trim_box = placed_box(document, page_id, PlacedBox::Trim)
+            .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+        document
+            .get_dictionary_mut(page_id)?
+            .set("TrimBox", trim_box.map(Object::from).to_vec());
+    }
+    Ok(())
+}
+
+/// The key under which this program's private data is stored in `/PieceInfo` dictionaries.
+pub const PIECE_INFO_NAME: &[u8] = b"Bookbinding";
+
+/// Stores private data for this program in a page's `/PieceInfo` dictionary, keeping any data
+/// stored there by other programs. `date` is the modification date, in the PDF date format.
+pub fn set_piece_info(page: &mut Dictionary, data: Dictionary, date: &str) {
+    let mut piece_info = page
+        .get(b"PieceInfo")
+        .and_then(Object::as_dict)
+        .cloned()
+        .unwrap_or_default();
+    let entry = Object::Dictionary(
+        [
+            ("LastModified", Object::string_literal(date)),
+            ("Private", Object::Dictionary(data)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    piece_info.set(PIECE_INFO_NAME, entry);
+    page.set("PieceInfo", piece_info);
+    page.set("LastModified", Object::string_literal(date));
+}
+
+/// Sets an entry in the document information dictionary, creating the dictionary if there is none.
+pub fn set_info(document: &mut Document, key: &str, value: Object) -> lopdf::Result<()> {
+    let info_id = match document.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(info_id) => info_id,
+        Err(_) => {
+            let info_id = document.add_object(Dictionary::new());
+            document.trailer.set("Info", info_id);
+            info_id
+        }
+    };
+    document.get_dictionary_mut(info_id)?.set(key, value);
+    Ok(())
+}
+
+/// Removes the document information dictionary and the XMP metadata of the document and its pages,
+/// which can identify who made it and with what. The removed objects are left in the document
+/// until it is pruned.
+pub fn strip_metadata(document: &mut Document) -> lopdf::Result<()> {
+    document.trailer.remove(b"Info");
+    document.catalog_mut()?.remove(b"Metadata");
+    for page_id in document.page_iter().collect::<Vec<_>>() {
+        document.get_dictionary_mut(page_id)?.remove(b"Metadata");
+    }
+    Ok(())
+}
+
+/// The standard entries of the document information dictionary.
+pub const INFO_KEYS: [&str; 9] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+    "Trapped",
+];
+
+/// Encodes text as a PDF text string: as it is if it is ASCII, and otherwise in UTF-16 with a byte
+/// order mark.
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::string_literal(text)
+    } else {
+        let bytes = [0xfeff]
+            .into_iter()
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+}
+
+/// The names of the fonts used in the document which are not embedded in it, in sorted order.
+/// Type 3 fonts are always embedded, and composite fonts are checked through their descendants.
+pub fn unembedded_fonts(document: &Document) -> Vec<String> {
+    let mut names = document
+        .objects
+        .values()
+        .filter_map(|object| {
+            let font = object.as_dict().ok()?;
+            if font.get(b"Type").and_then(Object::as_name).ok()? != b"Font" {
+                return None;
+            }
+            let subtype = font.get(b"Subtype").and_then(Object::as_name).ok()?;
+            if subtype == b"Type3" || subtype == b"Type0" {
+                return None;
+            }
+            let descriptor = match font.get(b"FontDescriptor") {
+                Ok(Object::Reference(id)) => document.get_dictionary(*id).ok(),
+                Ok(Object::Dictionary(descriptor)) => Some(descriptor),
+                _ => None,
+            };
+            let embedded = descriptor.is_some_and(|descriptor| {
+                [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                    .iter()
+                    .any(|key| descriptor.has(key))
+            });
+            let name = font.get(b"BaseFont").and_then(Object::as_name).ok()?;
+            (!embedded).then(|| String::from_utf8_lossy(name).into_owned())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Name of the font resource for text the tool draws, such as with [`stamp_text`], chosen so that
+/// it does not collide with the page's own fonts, and so that [`embed_fonts`] can tell them apart.
+pub const FONT: &str = "FBookbinding";
+
+/// Adds a line of small text in a slug below a page: a strip added to the bottom of its media
+/// box, outside its trim box, so that the text is trimmed off with the margins rather than drawn
+/// over the page's content. The trim box is set to the page's finished size if it has none, and
+/// the crop box, if any, is extended to show the slug. The page's existing content is wrapped so
+/// that it can't change how the text is drawn, and its resources are copied onto the page before
+/// the font is added, so that other pages sharing them are not changed.
+pub fn stamp_text(
+    document: &mut Document,
+    page_id: ObjectId,
+    text: &str,
+) -> color_eyre::Result<()> {
+    const FONT_SIZE: f32 = 5.0;
+    let [x0, y0, x1, y1] = page_box(document, page_id, b"MediaBox")
+        .ok_or_else(|| eyre!("page {page_id:?} has no media box"))?;
+    let trim_box = placed_box(document, page_id, PlacedBox::Trim).unwrap_or([x0, y0, x1, y1]);
+    let crop_box = page_box(document, page_id, b"CropBox");
+    let y0 = y0 - 2.0 * FONT_SIZE;
+    let page = document.get_dictionary_mut(page_id)?;
+    page.set("MediaBox", [x0, y0, x1, y1].map(Object::from).to_vec());
+    if let Some([cx0, _, cx1, cy1]) = crop_box {
+        page.set("CropBox", [cx0, y0, cx1, cy1].map(Object::from).to_vec());
+    }
+    if !page.has(b"TrimBox") {
+        page.set("TrimBox", trim_box.map(Object::from).to_vec());
+    }
+    let mut resources = match inherited(document, page_id, b"Resources") {
+        Some(resources) => document.dereference(resources)?.1.as_dict()?.clone(),
+        None => Dictionary::new(),
+    };
+    let mut fonts = match resources.get(b"Font") {
+        Ok(fonts) => document.dereference(fonts)?.1.as_dict()?.clone(),
+        Err(_) => Dictionary::new(),
+    };
+    fonts.set(FONT, helvetica());
+    resources.set("Font", fonts);
+    document
+        .get_dictionary_mut(page_id)?
+        .set("Resources", resources);
+    let mut operations = vec![Operation::new("Q", vec![])];
+    operations.extend(text_operations(
+        &[text.to_owned()],
+        FONT,
+        x0 + FONT_SIZE / 2.0,
+        y0 + FONT_SIZE / 2.0,
+        FONT_SIZE,
+    ));
+    let after = Content { operations }.encode()?;
+    wrap_contents(document, page_id, b"q".to_vec(), after)?;
+    Ok(())
+}
+
+/// Creates a new document with a single page of the given size, with the given lines of text set
+/// in Helvetica from the top left corner.
+pub fn text_document(lines: &[String], size: (f32, f32)) -> lopdf::Result<Document> {
+    drawing_document(lines, size, Vec::new())
+}
+
+/// Creates a new document like [`text_document`], with the given operations drawn after the text.
+/// The operations can set text in Helvetica with the font resource [`FONT`].
+pub fn drawing_document(
+    lines: &[String],
+    (width, height): (f32, f32),
+    drawing: Vec<Operation>,
+) -> lopdf::Result<Document> {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(helvetica());
+    let mut operations = text_operations(lines, FONT, 72.0, height - 72.0, 12.0);
+    operations.extend(drawing);
+    let content = Content { operations };
+    let content_id = document.add_object(Stream::new(Dictionary::new(), content.encode()?));
+    let resources = Object::Dictionary(
+        [(
+            "Font",
+            Object::Dictionary([(FONT, Object::from(font_id))].into_iter().collect()),
+        )]
+        .into_iter()
+        .collect(),
+    );
+    let page_id = document.add_object(Object::Dictionary(
+        [
+            ("Type", Object::from("Page")),
+            ("Parent", Object::from(pages_id)),
+            ("Contents", Object::from(content_id)),
+            ("Resources", resources),
+            (
+                "MediaBox",
+                Object::from(vec![0.into(), 0.into(), width.into(), height.into()]),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    document.set_object(
+        pages_id,
+        Object::Dictionary(
+            [
+                ("Type", Object::from("Pages")),
+                ("Kids", Object::from(vec![page_id.into()])),
+                ("Count", Object::from(1)),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    let catalog_id = document.add_object(Object::Dictionary(
+        [
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::from(pages_id)),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    document.trailer.set("Root", catalog_id);
+    Ok(document)
+}
+
+/// What to draw on inserted blank pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlankStyle {
+    /// Nothing.
+    #[default]
+    Empty,
+    /// A small "This page intentionally left blank" note in the middle of the page.
+    Note,
+    /// A light grid of 5 mm squares, for notebooks.
+    Grid,
+    /// A light grid of dots every 5 mm, for notebooks.
+    Dots,
+}
+
+/// The note on blank pages in the [`BlankStyle::Note`] style.
+const BLANK_NOTE: &str = "This page intentionally left blank";
+/// The spacing of the lines or dots of a grid on blank pages, 5 mm in points.
+const GRID_SPACING: f32 = 5.0 * POINTS_PER_MM;
+/// The margin around a grid on blank pages, in points.
+const GRID_MARGIN: f32 = 36.0;
+
+/// Adds the content of a blank page in the given style, drawn within the given box (the page's
+/// trim box), and returns its id along with the resources it uses. Empty pages have no content,
+/// so this returns `None` for them.
+pub fn blank_contents(
+    document: &mut Document,
+    style: BlankStyle,
+    [x0, y0, x1, y1]: [f32; 4],
+) -> lopdf::Result<Option<(ObjectId, Dictionary)>> {
+    let mut resources = Dictionary::new();
+    let operations = match style {
+        BlankStyle::Empty => return Ok(None),
+        BlankStyle::Note => {
+            let font_size = 10.0;
+            // Helvetica averages about half an em per character, which is close enough to centre
+            // a short note
+            let width = BLANK_NOTE.len() as f32 * font_size * 0.5;
+            let x = (x0 + x1 - width) / 2.0;
+            let y = (y0 + y1 - font_size) / 2.0;
+            let font_id = document.add_object(helvetica());
+            resources.set(
+                "Font",
+                Object::Dictionary([(FONT, Object::from(font_id))].into_iter().collect()),
+            );
+            let mut operations = vec![Operation::new("g", vec![0.5.into()])];
+            operations.extend(text_operations(
+                &[BLANK_NOTE.to_owned()],
+                FONT,
+                x,
+                y,
+                font_size,
+            ));
+            operations
+        }
+        BlankStyle::Grid | BlankStyle::Dots => {
+            let (left, bottom) = (x0 + GRID_MARGIN, y0 + GRID_MARGIN);
+            let columns = ((x1 - x0 - 2.0 * GRID_MARGIN) / GRID_SPACING)
+                .floor()
+                .max(0.0) as usize;
+            let rows = ((y1 - y0 - 2.0 * GRID_MARGIN) / GRID_SPACING)
+                .floor()
+                .max(0.0) as usize;
+            let (right, top) = (
+                left + columns as f32 * GRID_SPACING,
+                bottom + rows as f32 * GRID_SPACING,
+            );
+            let xs = (0..=columns).map(|column| left + column as f32 * GRID_SPACING);
+            let ys = (0..=rows).map(|row| bottom + row as f32 * GRID_SPACING);
+            let line = |(from_x, from_y): (f32, f32), (to_x, to_y): (f32, f32)| {
+                [
+                    Operation::new("m", vec![from_x.into(), from_y.into()]),
+                    Operation::new("l", vec![to_x.into(), to_y.into()]),
+                ]
+            };
+            let mut operations = vec![Operation::new("G", vec![0.75.into()])];
+            if style == BlankStyle::Grid {
+                operations.push(Operation::new("w", vec![0.25.into()]));
+                operations.extend(xs.flat_map(|x| line((x, bottom), (x, top))));
+                operations.extend(ys.flat_map(|y| line((left, y), (right, y))));
+            } else {
+                // each dot is a zero-length line with round caps
+                operations.push(Operation::new("w", vec![1.0.into()]));
+                operations.push(Operation::new("J", vec![1.into()]));
+                let ys = ys.collect::<Vec<_>>();
+                operations.extend(
+                    xs.flat_map(|x| ys.iter().map(move |&y| (x, y)))
+                        .flat_map(|point| line(point, point)),
+                );
+            }
+            operations.push(Operation::new("S", vec![]));
+            operations
+        }
+    };
+    let content = Content { operations }.encode()?;
+    let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+    Ok(Some((content_id, resources)))
+}
+
+/// A font dictionary for the standard Helvetica font.
+fn helvetica() -> Object {
+    Object::Dictionary(
+        [
+            ("Type", Object::from("Font")),
+            ("Subtype", Object::from("Type1")),
+            ("BaseFont", Object::from("Helvetica")),
+            ("Encoding", Object::from("WinAnsiEncoding")),
+        ]
+        .into_iter()
+        .collect(),
+    )
+}
+
+/// Replaces the standard Helvetica which the tool draws text with (see [`helvetica`]) with the
+/// bundled DejaVu Sans, embedded with only the glyphs of the text drawn with it, for printers which
+/// need every font to be embedded, such as for PDF/X. Only fonts named [`FONT`] are replaced, so
+/// fonts of the input are left as they are. Returns the number of fonts replaced.
+pub fn embed_fonts(document: &mut Document) -> color_eyre::Result<usize> {
+    let standard = helvetica();
+    // fonts set directly in resource dictionaries, such as by `stamp_text`, are replaced with a
+    // reference to a font of their own
+    let font_id = document.new_object_id();
+    let mut referenced = BTreeSet::new();
+    let mut inline = 0;
+    for object in document.objects.values_mut() {
+        inline += replace_font(object, &standard, font_id, &mut referenced);
+    }
+    let replaced = referenced
+        .into_iter()
+        .filter(|&id| {
+            document
+                .get_object(id)
+                .is_ok_and(|object| *object == standard)
+        })
+        .collect::<Vec<_>>();
+    if replaced.is_empty() && inline == 0 {
+        return Ok(0);
+    }
+
+    // the characters shown with the font, by pages and forms whose resources name it
+    let mut contents = Vec::new();
+    for page_id in document.page_iter() {
+        if inherited(document, page_id, b"Resources")
+            .is_some_and(|resources| names_font(document, resources))
+        {
+            contents.push(concatenate_contents(document, page_id)?);
+        }
+    }
+    for object in document.objects.values() {
+        let Ok(stream) = object.as_stream() else {
+            continue;
+        };
+        let is_form = stream
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| subtype == b"Form");
+        if is_form
+            && stream
+                .dict
+                .get(b"Resources")
+                .is_ok_and(|resources| names_font(document, resources))
+        {
+            contents.extend(stream.decompressed_content().ok());
+        }
+    }
+    let mut codes = BTreeSet::from([b' ']);
+    for content in contents {
+        let Ok(content) = Content::decode(&content) else {
+            continue;
+        };
+        let mut current = None;
+        for operation in &content.operations {
+            match (operation.operator.as_str(), operation.operands.as_slice()) {
+                ("Tf", [Object::Name(name), ..]) => current = Some(name),
+                ("Tj" | "'" | "\"" | "TJ", [.., text])
+                    if current.is_some_and(|name| name == FONT.as_bytes()) =>
+                {
+                    let strings = match text {
+                        Object::Array(items) => items.iter().collect(),
+                        text => vec![text],
+                    };
+                    codes.extend(
+                        strings
+                            .into_iter()
+                            .filter_map(|text| text.as_str().ok())
+                            .flatten(),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    codes.retain(|&code| win_ansi_char(code).is_some());
+    let chars = codes
+        .iter()
+        .filter_map(|&code| win_ansi_char(code))
+        .collect();
+
+    let font = Font::parse(font::DEJAVU_SANS)?;
+    let data = font.subset(&chars)?;
+    let scale = 1000.0 / f32::from(font.units_per_em()?);
+    let (first, last) = (
+        codes.first().copied().unwrap_or(b' '),
+        codes.last().copied().unwrap_or(b' '),
+    );
+    let mut widths = Vec::new();
+    for code in first..=last {
+        let advance = match win_ansi_char(code) {
+            Some(c) => font.advance(font.glyph_id(c)?)?,
+            None => 0,
+        };
+        widths.push(Object::from((f32::from(advance) * scale).round() as i64));
+    }
+    // a subset is named with a tag of six capital letters, which differs between subsets
+    let mut hasher = DefaultHasher::new();
+    codes.hash(&mut hasher);
+    let mut hash = hasher.finish();
+    let mut name = String::new();
+    for _ in 0..6 {
+        name.push(char::from(b'A' + (hash % 26) as u8));
+        hash /= 26;
+    }
+    name.push_str("+DejaVuSans");
+
+    let length = data.len() as i64;
+    let file_id = document.add_object(Stream::new(
+        [("Length1", Object::from(length))].into_iter().collect(),
+        data,
+    ));
+    let metrics = font.metrics()?;
+    let descriptor_id = document.add_object(Object::Dictionary(
+        [
+            ("Type", Object::from("FontDescriptor")),
+            ("FontName", Object::from(name.as_str())),
+            // non-symbolic, using the standard Latin characters
+            ("Flags", Object::from(32)),
+            (
+                "FontBBox",
+                Object::from(metrics.bbox.map(Object::from).to_vec()),
+            ),
+            ("ItalicAngle", Object::from(0)),
+            ("Ascent", Object::from(metrics.ascent)),
+            ("Descent", Object::from(metrics.descent)),
+            ("CapHeight", Object::from(metrics.cap_height)),
+            ("StemV", Object::from(80)),
+            ("FontFile2", Object::from(file_id)),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    let embedded = Object::Dictionary(
+        [
+            ("Type", Object::from("Font")),
+            ("Subtype", Object::from("TrueType")),
+            ("BaseFont", Object::from(name.as_str())),
+            ("FirstChar", Object::from(i64::from(first))),
+            ("LastChar", Object::from(i64::from(last))),
+            ("Widths", Object::from(widths)),
+            ("Encoding", Object::from("WinAnsiEncoding")),
+            ("FontDescriptor", Object::from(descriptor_id)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let count = replaced.len() + inline;
+    if inline > 0 {
+        document.set_object(font_id, embedded.clone());
+    }
+    for id in replaced {
+        document.set_object(id, embedded.clone());
+    }
+    Ok(count)
+}
+
+/// Replaces each copy of the `standard` font dictionary named [`FONT`] within an object with a
+/// reference to `font_id`, and adds the fonts which are referred to by that name to `referenced`.
+/// Returns the number of copies replaced.
+fn replace_font(
+    object: &mut Object,
+    standard: &Object,
+    font_id: ObjectId,
+    referenced: &mut BTreeSet<ObjectId>,
+) -> usize {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &mut stream.dict,
+        Object::Array(items) => {
+            return items
+                .iter_mut()
+                .map(|item| replace_font(item, standard, font_id, referenced))
+                .sum()
+        }
+        _ => return 0,
+    };
+    let mut count = 0;
+    for (key, value) in dict.iter_mut() {
+        if key != FONT.as_bytes() {
+            count += replace_font(value, standard, font_id, referenced);
+        } else if value == standard {
+            *value = Object::from(font_id);
+            count += 1;
+        } else if let Ok(id) = value.as_reference() {
+            referenced.insert(id);
+        }
+    }
+    count
+}
+
+/// Whether a resource dictionary, or a reference to one, has a font named [`FONT`].
+fn names_font(document: &Document, resources: &Object) -> bool {
+    document
+        .dereference(resources)
+        .and_then(|(_, resources)| resources.as_dict()?.get(b"Font"))
+        .and_then(|fonts| document.dereference(fonts)?.1.as_dict())
+        .is_ok_and(|fonts| fonts.has(FONT.as_bytes()))
+}
+
+/// Content stream operations which draw the given lines of text using the named font resource,
+/// with the baseline of the first line at (`x`, `y`).
+pub fn text_operations(
+    lines: &[String],
+    font: &str,
+    x: f32,
+    y: f32,
+    font_size: f32,
+) -> Vec<Operation> {
+    let mut operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec![font.into(), font_size.into()]),
+        Operation::new("TL", vec![(font_size * 1.2).into()]),
+        Operation::new("Td", vec![x.into(), y.into()]),
+    ];
+    for line in lines {
+        operations.push(Operation::new("Tj", vec![encode_text(line)]));
+        operations.push(Operation::new("T*", vec![]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+    operations
+}
+
+/// Encodes text as a string object for a font using `WinAnsiEncoding`. Characters which it has
+/// no code for are replaced with `?`.
+fn encode_text(text: &str) -> Object {
+    Object::string_literal(
+        text.chars()
+            .map(|c| {
+                (32..=255)
+                    .find(|&code| win_ansi_char(code) == Some(c))
+                    .unwrap_or(b'?')
+            })
+            .collect::<Vec<u8>>(),
+    )
+}
+
+/// The character for a code in `WinAnsiEncoding`, which is Latin-1 apart from the codes from 128
+/// to 159, or `None` for control characters and the codes it leaves undefined.
+fn win_ansi_char(code: u8) -> Option<char> {
+    const CODES_128_TO_159: [Option<char>; 32] = [
+        Some('€'),
+        None,
+        Some('‚'),
+        Some('ƒ'),
+        Some('„'),
+        Some('…'),
+        Some('†'),
+        Some('‡'),
+        Some('ˆ'),
+        Some('‰'),
+        Some('Š'),
+        Some('‹'),
+        Some('Œ'),
+        None,
+        Some('Ž'),
+        None,
+        None,
+        Some('\u{2018}'),
+        Some('\u{2019}'),
+        Some('\u{201c}'),
+        Some('\u{201d}'),
+        Some('•'),
+        Some('–'),
+        Some('—'),
+        Some('˜'),
+        Some('™'),
+        Some('š'),
+        Some('›'),
+        Some('œ'),
+        None,
+        Some('ž'),
+        Some('Ÿ'),
+    ];
+    match code {
+        32..=126 | 160..=255 => Some(char::from(code)),
+        128..=159 => CODES_128_TO_159[usize::from(code - 128)],
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{Content, Dictionary, Object, Stream};
+    use test_case::test_case;
+
+    use super::Fit;
+    use crate::testing;
+
+    #[test]
+    fn set_page_order() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 4]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        super::set_page_order(&mut document, &[page_ids[3], page_ids[0], page_ids[2]]).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(3), Some(0), Some(2)]
+        );
+        assert_eq!(document.page_iter().size_hint().0, 3);
+    }
+
+    #[test]
+    fn merge_document() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 2]);
+        let other = testing::document_with_boxes(&[[0.0, 0.0, 50.0, 50.0]; 3]);
+        let num_objects = document.objects.len() + other.objects.len();
+        let merged = super::merge_document(&mut document, other).unwrap();
+        assert_eq!(document.objects.len(), num_objects);
+        let page_ids = document.page_iter().chain(merged).collect::<Vec<_>>();
+        super::set_page_order(&mut document, &page_ids).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(0), Some(1), Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            super::page_box(&document, page_ids[4], b"MediaBox"),
+            Some([0.0, 0.0, 50.0, 50.0])
+        );
+    }
+
+    #[test]
+    fn is_blank() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let whitespace = document.add_object(Stream::new(Dictionary::new(), b" \n\t".to_vec()));
+        let page = document.get_dictionary_mut(page_ids[1]).unwrap();
+        page.set("Contents", whitespace);
+        document
+            .get_dictionary_mut(page_ids[2])
+            .unwrap()
+            .remove(b"Contents");
+        let blank = page_ids
+            .iter()
+            .map(|&page_id| super::is_blank(&document, page_id, 0.0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(blank, [false, true, true]);
+    }
+
+    #[test_case(b"q 1 0 0 1 50 50 cm Q", 0.0 => true; "no marking operators")]
+    #[test_case(b"0 0 m 50 50 l n", 0.0 => true; "path which is not painted")]
+    #[test_case(b"10 10 40 40 re f", 0.0 => false; "filled rectangle")]
+    #[test_case(b"10 10 40 40 re f", 2000.0 => true; "filled rectangle below the tolerance")]
+    #[test_case(b"0.1 0 0 0.1 0 0 cm 10 10 40 40 re f", 20.0 => true; "scaled down")]
+    #[test_case(b"0 w 10 10 m 50 10 l S", 0.0 => false; "hairline")]
+    #[test_case(b"0 w 10 10 m 50 10 l S", 1.0 => true; "hairline below the tolerance")]
+    #[test_case(b"1 w 10 10 m 50 10 l S", 1.0 => false; "stroked line")]
+    #[test_case(b"BT /F1 12 Tf (x) Tj ET", 0.0 => false; "text")]
+    #[test_case(b"BT 3 Tr /F1 12 Tf (x) Tj ET", 0.0 => true; "invisible text")]
+    #[test_case(b"q 3 Tr Q BT /F1 12 Tf (x) Tj ET", 0.0 => false; "restored rendering mode")]
+    #[test_case(b"/Im0 Do", 1e9 => false; "image")]
+    fn is_blank_content(content: &[u8], tolerance: f32) -> bool {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content.to_vec()));
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Contents", content_id);
+        super::is_blank(&document, page_id, tolerance).unwrap()
+    }
+
+    #[test]
+    fn multiple_content_streams() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        // the streams split the content between tokens, without any whitespace at the ends
+        let contents = [&b"q 1 0 0 1 5 5 cm"[..], b"0 0 m 10 10 l S", b"Q"]
+            .map(|content| document.add_object(Stream::new(Dictionary::new(), content.to_vec())));
+        let contents = document.add_object(contents.map(Object::from).to_vec());
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Contents", contents);
+
+        let content = super::concatenate_contents(&document, page_id).unwrap();
+        assert_eq!(content, b"q 1 0 0 1 5 5 cm\n0 0 m 10 10 l S\nQ");
+
+        super::wrap_contents(&mut document, page_id, b"q".to_vec(), b"Q".to_vec()).unwrap();
+        let content = super::concatenate_contents(&document, page_id).unwrap();
+        let operators = Content::decode(&content)
+            .unwrap()
+            .operations
+            .into_iter()
+            .map(|operation| operation.operator)
+            .collect::<Vec<_>>();
+        assert_eq!(operators, ["q", "q", "cm", "m", "l", "S", "Q", "Q"]);
+        assert!(matches!(
+            document.get_dictionary(page_id).unwrap().get(b"Contents"),
+            Ok(Object::Array(contents)) if contents.len() == 5
+        ));
+    }
+
+    #[test_case(b"q 0 0 m 10 10 l S" => (1, "q m l S Q".to_owned()))]
+    #[test_case(b"q q Q" => (1, "q q Q Q".to_owned()))]
+    #[test_case(b"Q q 1 0 0 1 5 5 cm Q Q" => (2, "q cm Q".to_owned()))]
+    #[test_case(b"q Q" => (0, "q Q".to_owned()))]
+    fn balance_graphics_state(content: &[u8]) -> (usize, String) {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content.to_vec()));
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Contents", content_id);
+        let fixed = super::balance_graphics_state(&mut document, page_id).unwrap();
+        let content = super::concatenate_contents(&document, page_id).unwrap();
+        let operators = Content::decode(&content)
+            .unwrap()
+            .operations
+            .into_iter()
+            .map(|operation| operation.operator)
+            .collect::<Vec<_>>();
+        (fixed, operators.join(" "))
+    }
+
+    #[test]
+    fn stamp_text() {
+        let mut document = testing::document_with_boxes(&[[10.0, 20.0, 100.0, 100.0]; 2]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        super::stamp_text(&mut document, page_ids[0], "signature 1").unwrap();
+        let content = super::concatenate_contents(&document, page_ids[0]).unwrap();
+        let operations = Content::decode(&content).unwrap().operations;
+        let shown = operations
+            .iter()
+            .filter(|operation| operation.operator == "Tj")
+            .map(|operation| operation.operands[0].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(shown, [b"signature 1"]);
+        let font = operations
+            .iter()
+            .find(|operation| operation.operator == "Tf")
+            .unwrap();
+        assert_eq!(font.operands[0].as_name().unwrap(), super::FONT.as_bytes());
+        let position = operations
+            .iter()
+            .find(|operation| operation.operator == "Td")
+            .unwrap();
+        // in the slug added below the page, outside its trim box
+        assert_eq!(position.operands, [12.5.into(), 12.5.into()]);
+        let page = document.get_dictionary(page_ids[0]).unwrap();
+        let rectangle = |key| page.get(key).unwrap().as_array().unwrap().clone();
+        assert_eq!(
+            rectangle(b"MediaBox".as_slice()),
+            [10.0, 10.0, 100.0, 100.0].map(Object::from)
+        );
+        assert_eq!(
+            rectangle(b"TrimBox".as_slice()),
+            [10.0, 20.0, 100.0, 100.0].map(Object::from)
+        );
+        let depth =
+            operations
+                .iter()
+                .fold(0, |depth, operation| match operation.operator.as_str() {
+                    "q" => depth + 1,
+                    "Q" => depth - 1,
+                    _ => depth,
+                });
+        assert_eq!(depth, 0);
+        let has_font = |page_id| {
+            super::inherited(&document, page_id, b"Resources")
+                .and_then(|resources| resources.as_dict().ok()?.get(b"Font").ok()?.as_dict().ok())
+                .is_some_and(|fonts| fonts.has(super::FONT.as_bytes()))
+        };
+        assert!(has_font(page_ids[0]));
+        assert!(!has_font(page_ids[1]));
+    }
+
+    #[test]
+    fn page_xobject_group() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 2]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let group = Object::Dictionary(
+            [
+                ("Type", Object::from("Group")),
+                ("S", Object::from("Transparency")),
+                ("CS", Object::from("DeviceCMYK")),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        document
+            .get_dictionary_mut(page_ids[0])
+            .unwrap()
+            .set("Group", group.clone());
+        let groups = page_ids
+            .iter()
+            .map(|&page_id| {
+                let xobject_id = super::page_xobject(&mut document, page_id).unwrap();
+                let xobject = document
+                    .get_object(xobject_id)
+                    .unwrap()
+                    .as_stream()
+                    .unwrap();
+                xobject.dict.get(b"Group").ok().cloned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(groups, [Some(group), None]);
+    }
+
+    #[test]
+    fn first_page_first() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        super::set_page_order(&mut document, &[page_ids[2], page_ids[0], page_ids[1]]).unwrap();
+        super::first_page_first(&mut document).unwrap();
+        assert_eq!(
+            testing::page_indices(&document),
+            [Some(2), Some(0), Some(1)]
+        );
+        let catalog_id = document
+            .trailer
+            .get(b"Root")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        assert_eq!(catalog_id, (1, 0));
+        let page_id = document.page_iter().next().unwrap();
+        assert_eq!(page_id, (3, 0));
+        let content_id = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Contents")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        assert_eq!(content_id, (4, 0));
+        assert_eq!(document.max_id as usize, document.objects.len());
+    }
+
+    /// A reference to an object which does not exist becomes null, rather than referring to the
+    /// object which is given its number.
+    #[test]
+    fn first_page_first_dangling() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 2]);
+        let page_id = document.page_iter().next().unwrap();
+        let missing = (document.max_id + 1, 0);
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Thumb", missing);
+        super::first_page_first(&mut document).unwrap();
+        let page_id = document.page_iter().next().unwrap();
+        let thumb = document.get_dictionary(page_id).unwrap().get(b"Thumb");
+        assert_eq!(thumb.unwrap(), &Object::Null);
+        assert_eq!(document.max_id as usize, document.objects.len());
+    }
+
+    #[test]
+    fn strip_metadata() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 2]);
+        super::set_info(&mut document, "Author", Object::string_literal("A. Author")).unwrap();
+        let metadata =
+            document.add_object(Stream::new(Dictionary::new(), b"<x:xmpmeta/>".to_vec()));
+        document.catalog_mut().unwrap().set("Metadata", metadata);
+        let page_id = document.page_iter().next().unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Metadata", metadata);
+
+        super::strip_metadata(&mut document).unwrap();
+        document.prune_objects();
+        assert!(!document.trailer.has(b"Info"));
+        assert!(!document.catalog().unwrap().has(b"Metadata"));
+        assert!(!document.get_dictionary(page_id).unwrap().has(b"Metadata"));
+        assert!(!document.objects.contains_key(&metadata));
+        assert_eq!(document.page_iter().count(), 2);
+    }
+
+    #[test_case(Fit::Contain => [0.706, 0.0, 0.0, 0.706, 0.0, 44.471])]
+    #[test_case(Fit::Cover => [0.818, 0.0, 0.0, 0.818, -34.364, 0.0])]
+    #[test_case(Fit::Stretch => [0.706, 0.0, 0.0, 0.818, 0.0, 0.0])]
+    fn scale_to_size(fit: Fit) -> [f32; 6] {
+        // a letter page scaled to a 6x9 inch book
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 612.0, 792.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        super::scale_to_size(&mut document, page_id, (432.0, 648.0), fit).unwrap();
+        for key in [&b"MediaBox"[..], b"CropBox", b"TrimBox"] {
+            assert_eq!(
+                super::page_box(&document, page_id, key),
+                Some([0.0, 0.0, 432.0, 648.0])
+            );
+        }
+        let content = document.get_page_content(page_id).unwrap();
+        let operations = Content::decode(&content).unwrap().operations;
+        let matrix = operations
+            .iter()
+            .find(|operation| operation.operator == "cm")
+            .unwrap()
+            .operands
+            .iter()
+            .map(|operand| (operand.as_float().unwrap() * 1000.0).round() / 1000.0)
+            .collect::<Vec<_>>();
+        matrix.try_into().unwrap()
+    }
+
+    #[test]
+    fn clip_to_box() {
+        use super::PlacedBox;
+
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 300.0]; 2]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let crop_box = [20.0, 30.0, 180.0, 280.0];
+        document
+            .get_dictionary_mut(page_ids[0])
+            .unwrap()
+            .set("CropBox", crop_box.map(Object::from).to_vec());
+
+        assert_eq!(
+            super::placed_box(&document, page_ids[0], PlacedBox::Media),
+            Some([0.0, 0.0, 200.0, 300.0])
+        );
+        assert_eq!(
+            super::placed_box(&document, page_ids[0], PlacedBox::Trim),
+            Some(crop_box)
+        );
+        assert_eq!(
+            super::placed_box(&document, page_ids[1], PlacedBox::Crop),
+            Some([0.0, 0.0, 200.0, 300.0])
+        );
+
+        super::clip_to_box(&mut document, page_ids[0], PlacedBox::Crop).unwrap();
+        assert_eq!(
+            super::page_box(&document, page_ids[0], b"MediaBox"),
+            Some(crop_box)
+        );
+    }
+
+    #[test]
+    fn trim_page() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 200.0]; 2]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let trim = super::Trim {
+            head: 10.0,
+            foot: 20.0,
+            fore_edge: 5.0,
+        };
+        super::trim_page(&mut document, page_ids[0], trim, true).unwrap();
+        assert_eq!(
+            super::page_box(&document, page_ids[0], b"TrimBox"),
+            Some([0.0, 20.0, 95.0, 190.0])
+        );
+        document
+            .get_dictionary_mut(page_ids[1])
+            .unwrap()
+            .set("Rotate", 90);
+        super::trim_page(&mut document, page_ids[1], trim, false).unwrap();
+        // displayed rotated clockwise, the top of the page is the left of the media box
+        assert_eq!(
+            super::page_box(&document, page_ids[1], b"CropBox"),
+            Some([10.0, 5.0, 80.0, 200.0])
+        );
+    }
+
+    #[test]
+    fn normalize_origin() {
+        let mut document =
+            testing::document_with_boxes(&[[100.0, 100.0, 712.0, 892.0], [0.0, 0.0, 612.0, 792.0]]);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        document.get_dictionary_mut(page_ids[0]).unwrap().set(
+            "TrimBox",
+            vec![110.into(), 110.into(), 702.into(), 882.into()],
+        );
+        for &page_id in &page_ids {
+            super::normalize_origin(&mut document, page_id).unwrap();
+        }
+        assert_eq!(
+            super::page_box(&document, page_ids[0], b"MediaBox"),
+            Some([0.0, 0.0, 612.0, 792.0])
+        );
+        assert_eq!(
+            super::page_box(&document, page_ids[0], b"TrimBox"),
+            Some([10.0, 10.0, 602.0, 782.0])
+        );
+        let content = document.get_page_content(page_ids[0]).unwrap();
+        let operations = Content::decode(&content).unwrap().operations;
+        let matrix = operations[1]
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matrix, [1.0, 0.0, 0.0, 1.0, -100.0, -100.0]);
+        // a page which is already at the origin is left as it is
+        assert!(matches!(
+            document
+                .get_dictionary(page_ids[1])
+                .unwrap()
+                .get(b"Contents"),
+            Ok(Object::Reference(_))
+        ));
+    }
+
+    #[test]
+    fn bake_rotation() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Rotate", 90);
+        super::bake_rotation(&mut document, page_id).unwrap();
+
+        assert_eq!(super::rotation(&document, page_id), 0);
+        assert_eq!(
+            super::page_box(&document, page_id, b"MediaBox"),
+            Some([0.0, 0.0, 100.0, 200.0])
+        );
+        let content = document.get_page_content(page_id).unwrap();
+        let operations = Content::decode(&content).unwrap().operations;
+        assert_eq!(operations[0].operator, "q");
+        assert_eq!(operations[1].operator, "cm");
+        assert_eq!(operations.last().unwrap().operator, "Q");
+        // displayed with `/Rotate 90`, the bottom left corner of the page is at the top left, and
+        // the top right corner is at the bottom right
+        let matrix = operations[1]
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect::<Vec<_>>();
+        let apply = |x: f32, y: f32| {
+            (
+                matrix[0] * x + matrix[2] * y + matrix[4],
+                matrix[1] * x + matrix[3] * y + matrix[5],
+            )
+        };
+        assert_eq!(apply(0.0, 0.0), (0.0, 200.0));
+        assert_eq!(apply(200.0, 100.0), (100.0, 0.0));
+        // the original content is kept in between
+        assert!(operations.iter().any(|op| op.operator == "S"));
+        assert!(matches!(
+            document.get_dictionary(page_id).unwrap().get(b"Contents"),
+            Ok(Object::Array(contents)) if contents.len() == 3
+        ));
+    }
+
+    #[test]
+    fn add_pdfx() {
+        let mut document = super::text_document(&["text".to_owned()], super::LETTER).unwrap();
+        super::add_pdfx(&mut document, super::PdfX::X4, "D:20261015").unwrap();
+
+        assert_eq!(document.version, "1.6");
+        let catalog = document.catalog().unwrap();
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = document
+            .get_dictionary(intents[0].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFX");
+        let info_id = document
+            .trailer
+            .get(b"Info")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let info = document.get_dictionary(info_id).unwrap();
+        assert_eq!(
+            info.get(b"GTS_PDFXVersion").unwrap().as_str().unwrap(),
+            b"PDF/X-4"
+        );
+        let page_id = document.page_iter().next().unwrap();
+        assert_eq!(
+            super::page_box(&document, page_id, b"TrimBox"),
+            Some([0.0, 0.0, 612.0, 792.0])
+        );
+        assert_eq!(super::unembedded_fonts(&document), ["Helvetica"]);
+    }
+
+    #[test]
+    fn drop_structure() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let struct_tree_id = document.add_object(Object::Dictionary(
+            [("Type", Object::from("StructTreeRoot"))]
+                .into_iter()
+                .collect(),
+        ));
+        let catalog = document.catalog_mut().unwrap();
+        catalog.set("StructTreeRoot", struct_tree_id);
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("StructParents", 0);
+        assert!(super::is_tagged(&document));
+
+        super::drop_structure(&mut document).unwrap();
+        assert!(!super::is_tagged(&document));
+        assert!(!document
+            .get_dictionary(page_id)
+            .unwrap()
+            .has(b"StructParents"));
+        document.prune_objects();
+        assert!(document.get_object(struct_tree_id).is_err());
+    }
+
+    #[test_case(super::BlankStyle::Empty => None)]
+    #[test_case(super::BlankStyle::Note => Some((1, 0, true)))]
+    #[test_case(super::BlankStyle::Grid => Some((0, 15 + 20, false)))]
+    #[test_case(super::BlankStyle::Dots => Some((0, 15 * 20, false)))]
+    fn blank_contents(style: super::BlankStyle) -> Option<(usize, usize, bool)> {
+        let mut document = lopdf::Document::with_version("1.5");
+        // 14 by 19 squares of 5 mm fit inside the margins
+        let (content_id, resources) =
+            super::blank_contents(&mut document, style, [0.0, 0.0, 272.0, 348.0]).unwrap()?;
+        let content = document
+            .get_object(content_id)
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        let operations = Content::decode(&content.content).unwrap().operations;
+        let count = |operator: &str| {
+            operations
+                .iter()
+                .filter(|op| op.operator == operator)
+                .count()
+        };
+        Some((count("Tj"), count("m"), resources.has(b"Font")))
+    }
+
+    #[test]
+    fn text_document() {
+        let lines = ["first line".to_owned(), "second line".to_owned()];
+        let document = super::text_document(&lines, super::LETTER).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 1);
+        let content = document.get_page_content(pages[&1]).unwrap();
+        let shown = Content::decode(&content)
+            .unwrap()
+            .operations
+            .into_iter()
+            .filter(|op| op.operator == "Tj")
+            .map(|op| op.operands[0].as_str().unwrap().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(shown, [b"first line".to_vec(), b"second line".to_vec()]);
+    }
+
+    #[test]
+    fn embed_fonts() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 100.0, 100.0]; 3]);
+        // the input's own Helvetica, the same as the tool's but with another name, is kept, and
+        // the text drawn with it doesn't go into the subset
+        let helvetica_id = document.add_object(super::helvetica());
+        let content_id = document.add_object(Stream::new(
+            Dictionary::new(),
+            b"BT /F1 12 Tf (z) Tj ET".to_vec(),
+        ));
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        let page = document.get_dictionary_mut(page_ids[0]).unwrap();
+        page.set("Contents", content_id);
+        let fonts = Object::Dictionary([("F1", Object::from(helvetica_id))].into_iter().collect());
+        page.set(
+            "Resources",
+            Object::Dictionary([("Font", fonts)].into_iter().collect()),
+        );
+        // a page number on each page, as with `--stamp-signature-info`
+        for (index, &page_id) in page_ids.iter().enumerate() {
+            let text = format!("Page {} \u{2013} draft", index + 1);
+            super::stamp_text(&mut document, page_id, &text).unwrap();
+        }
+        // a font of the input, which is not replaced
+        let times = Object::Dictionary(
+            [
+                ("Type", Object::from("Font")),
+                ("Subtype", Object::from("Type1")),
+                ("BaseFont", Object::from("Times-Roman")),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        document.add_object(times);
+        assert_eq!(super::embed_fonts(&mut document).unwrap(), 3);
+        assert_eq!(
+            super::unembedded_fonts(&document),
+            ["Helvetica", "Times-Roman"]
+        );
+
+        let page_id = document.page_iter().next().unwrap();
+        let fonts = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Resources")
+            .and_then(|resources| resources.as_dict()?.get(b"Font")?.as_dict())
+            .unwrap();
+        let font_id = fonts
+            .get(super::FONT.as_bytes())
+            .and_then(Object::as_reference)
+            .unwrap();
+        let font = document.get_dictionary(font_id).unwrap();
+        assert_eq!(
+            font.get(b"Subtype").unwrap().as_name().unwrap(),
+            b"TrueType"
+        );
+        // the widths start from the space, and `P` is 1000 * 1235 / 2048 wide
+        assert_eq!(font.get(b"FirstChar").unwrap().as_i64().unwrap(), 32);
+        let widths = font.get(b"Widths").unwrap().as_array().unwrap();
+        assert_eq!(widths[usize::from(b'P' - 32)].as_i64().unwrap(), 603);
+        // the en dash is code 150 in `WinAnsiEncoding`
+        assert_eq!(font.get(b"LastChar").unwrap().as_i64().unwrap(), 150);
+        assert!(widths[150 - 32].as_i64().unwrap() > 0);
+        let descriptor_id = font.get(b"FontDescriptor").unwrap().as_reference().unwrap();
+        let file_id = document
+            .get_dictionary(descriptor_id)
+            .unwrap()
+            .get(b"FontFile2")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let file = document.get_object(file_id).unwrap().as_stream().unwrap();
+        let subset = crate::font::Font::parse(&file.content).unwrap();
+        // only the characters of the page numbers are in the subset
+        for (c, expected) in [
+            ('P', true),
+            ('3', true),
+            (' ', true),
+            ('\u{2013}', true),
+            ('4', false),
+            ('z', false),
+        ] {
+            assert_eq!(subset.glyph_id(c).unwrap() != 0, expected, "{c:?}");
+        }
+    }
+}
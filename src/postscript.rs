@@ -0,0 +1,618 @@
+//! Writing the imposed document as PostScript, for RIPs which do not accept PDF.
+//!
+//! PDF content streams use the same syntax and imaging model as PostScript, so each page's content
+//! is translated operator by operator, with a prolog which defines the PDF operators as PostScript
+//! procedures. This only covers simple pages: vector graphics in the device colour spaces (and
+//! separations, which are printed in grey), text in the standard 14 fonts, and form XObjects,
+//! which is what the imposition itself adds. Pages which use anything else, such as images,
+//! shadings or embedded fonts, are rejected with an error rather than printed wrongly; those
+//! documents need a full PDF interpreter, such as Ghostscript's `ps2write`.
+
+use std::fmt::Write;
+
+use color_eyre::eyre::{bail, eyre};
+use lopdf::{content::Operation, Content, Dictionary, Document, Object};
+
+use crate::pdf::{self, PlacedBox};
+
+/// Defines the PDF operators as PostScript procedures, in the `PDFOps` dictionary.
+///
+/// PostScript has a single current colour, so it holds the fill colour, and the stroke colour is
+/// kept as a procedure which sets it, saved and restored by `q` and `Q`.
+const PROLOG: &str = r#"/PDFOps 128 dict def
+PDFOps begin
+/QStack 256 array def /QDepth 0 def
+/strokec { 0 setgray } def
+/Fonts 0 dict def /XObjects 0 dict def
+/q { gsave QStack QDepth /strokec load put /QDepth QDepth 1 add def } bind def
+/Q { grestore /QDepth QDepth 1 sub def /strokec QStack QDepth get def } bind def
+/cm { 6 array astore concat } bind def
+/w { setlinewidth } bind def
+/J { setlinecap } bind def
+/j { setlinejoin } bind def
+/M { setmiterlimit } bind def
+/d { setdash } bind def
+/ri { pop } bind def
+/i { setflat } bind def
+/m { moveto } bind def
+/l { lineto } bind def
+/c { curveto } bind def
+/v { currentpoint 6 2 roll curveto } bind def
+/y { 2 copy curveto } bind def
+/h { closepath } bind def
+/re { 4 2 roll moveto exch dup 0 rlineto exch 0 exch rlineto neg 0 rlineto closepath } bind def
+/S { gsave strokec stroke grestore newpath } bind def
+/s { closepath S } bind def
+/f { fill } bind def
+/F { fill } bind def
+/f* { eofill } bind def
+/B { gsave fill grestore S } bind def
+/B* { gsave eofill grestore S } bind def
+/b { closepath B } bind def
+/b* { closepath B* } bind def
+/n { newpath } bind def
+/W { clip } bind def
+/W* { eoclip } bind def
+/g { setgray } bind def
+/rg { setrgbcolor } bind def
+/k { setcmykcolor } bind def
+/G { /setgray load 2 array astore cvx /strokec exch def } bind def
+/RG { /setrgbcolor load 4 array astore cvx /strokec exch def } bind def
+/K { /setcmykcolor load 5 array astore cvx /strokec exch def } bind def
+/Tc_ 0 def /Tw_ 0 def /Tz_ 100 def /TL_ 0 def /Ts_ 0 def /Tr_ 0 def
+/Tfs 1 def /Tfont /Helvetica findfont def
+/Tmx matrix def /Tlmx matrix def
+/BT { /Tmx matrix def /Tlmx matrix def } bind def
+/ET { } bind def
+/Tc { /Tc_ exch def } bind def
+/Tw { /Tw_ exch def } bind def
+/Tz { /Tz_ exch def } bind def
+/TL { /TL_ exch def } bind def
+/Ts { /Ts_ exch def } bind def
+/Tr { /Tr_ exch def } bind def
+/Tf { /Tfs exch def Fonts exch get /Tfont exch def } bind def
+/Td { matrix translate Tlmx matrix concatmatrix dup /Tlmx exch def /Tmx exch def } bind def
+/TD { dup neg /TL_ exch def Td } bind def
+/Tm { 6 array astore dup /Tlmx exch def /Tmx exch def } bind def
+/T* { 0 TL_ neg Td } bind def
+/Tadvance { 0 matrix translate Tmx matrix concatmatrix /Tmx exch def } bind def
+/Tj {
+  gsave Tmx concat
+  Tfont [Tfs Tz_ 100 div mul 0 0 Tfs 0 Ts_] makefont setfont
+  0 0 moveto
+  Tr_ 3 eq { stringwidth pop } {
+    Tw_ Tz_ 100 div mul 0 32 Tc_ Tz_ 100 div mul 0 6 -1 roll awidthshow currentpoint pop
+  } ifelse
+  grestore Tadvance
+} bind def
+/TJ {
+  { dup type /stringtype eq { Tj } { neg 1000 div Tfs mul Tz_ 100 div mul Tadvance } ifelse } forall
+} bind def
+/' { T* Tj } bind def
+/" { 3 1 roll /Tc_ exch def /Tw_ exch def ' } bind def
+/Do { XObjects exch get exec } bind def
+/WinAnsi {
+  dup length dict begin { 1 index /FID ne { def } { pop pop } ifelse } forall
+  /Encoding ISOLatin1Encoding def currentdict end /PDFFont exch definefont
+} bind def
+end
+"#;
+
+/// The standard 14 fonts, which every PostScript printer has.
+const STANDARD_FONTS: [&str; 14] = [
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+/// The operators which are passed through to the procedures in the prolog unchanged.
+const OPERATORS: [&str; 53] = [
+    "w", "J", "j", "M", "d", "ri", "i", "q", "Q", "cm", "m", "l", "c", "v", "y", "h", "re", "S",
+    "s", "f", "F", "f*", "B", "B*", "b", "b*", "n", "W", "W*", "g", "rg", "k", "G", "RG", "K",
+    "BT", "ET", "Tc", "Tw", "Tz", "TL", "Tf", "Tr", "Ts", "Td", "TD", "Tm", "T*", "Tj", "TJ", "'",
+    "\"", "Do",
+];
+
+/// The operators which have no effect on the printed page, and are left out.
+const IGNORED: [&str; 9] = ["BMC", "BDC", "EMC", "MP", "DP", "BX", "EX", "d0", "d1"];
+
+/// How deeply form XObjects can be nested, which also stops a form which draws itself.
+const MAX_FORM_DEPTH: usize = 16;
+
+/// Translates the document to a PostScript document with one page for each of its pages.
+pub fn to_postscript(document: &Document) -> color_eyre::Result<String> {
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut pages = String::new();
+    let (mut max_width, mut max_height) = (0.0_f32, 0.0_f32);
+    for (index, &page_id) in page_ids.iter().enumerate() {
+        let number = index + 1;
+        let [x0, y0, x1, y1] = pdf::page_box(document, page_id, b"MediaBox")
+            .ok_or_else(|| eyre!("page {number} has no media box"))?;
+        let [cx0, cy0, cx1, cy1] =
+            pdf::placed_box(document, page_id, PlacedBox::Crop).unwrap_or([x0, y0, x1, y1]);
+        if pdf::rotation(document, page_id) != 0 {
+            bail!(
+                "page {number} is rotated, which PostScript output does not support; use \
+                 --normalize-rotation"
+            );
+        }
+        let (width, height) = (x1 - x0, y1 - y0);
+        (max_width, max_height) = (max_width.max(width), max_height.max(height));
+        let resources = pdf::inherited(document, page_id, b"Resources");
+        let content = pdf::concatenate_contents(document, page_id)?;
+        let translator = Translator { document };
+        let body = translator
+            .scope(resources, &content, 0)
+            .map_err(|err| eyre!("page {number} cannot be written as PostScript: {err}"))?;
+        let (width, height) = (width.ceil(), height.ceil());
+        write!(
+            pages,
+            "%%Page: {number} {number}\n\
+             %%PageBoundingBox: 0 0 {width} {height}\n\
+             %%BeginPageSetup\n\
+             << /PageSize [{width} {height}] >> setpagedevice\n\
+             %%EndPageSetup\n\
+             save PDFOps begin\n\
+             {} {} translate\n\
+             {cx0} {cy0} {} {} re W n\n\
+             {body}\
+             end restore showpage\n",
+            -x0,
+            -y0,
+            cx1 - cx0,
+            cy1 - cy0,
+        )?;
+    }
+    let (max_width, max_height) = (max_width.ceil(), max_height.ceil());
+    Ok(format!(
+        "%!PS-Adobe-3.0\n\
+         %%Creator: bookbinding\n\
+         %%Pages: {}\n\
+         %%BoundingBox: 0 0 {max_width} {max_height}\n\
+         %%LanguageLevel: 2\n\
+         %%EndComments\n\
+         %%BeginProlog\n\
+         {PROLOG}\
+         %%EndProlog\n\
+         {pages}\
+         %%Trailer\n\
+         %%EOF\n",
+        page_ids.len(),
+    ))
+}
+
+/// The colour spaces which can be printed. PDF sets separate spaces for stroking and filling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// A single colorant, printed as grey.
+    Separation,
+}
+
+impl ColorSpace {
+    /// The operator which sets a colour in this space, for filling or stroking, and the colour's
+    /// operands.
+    fn set_color(self, stroke: bool, components: &[f32]) -> (&'static str, Vec<f32>) {
+        let operator = match (self, stroke) {
+            (ColorSpace::Gray | ColorSpace::Separation, false) => "g",
+            (ColorSpace::Gray | ColorSpace::Separation, true) => "G",
+            (ColorSpace::Rgb, false) => "rg",
+            (ColorSpace::Rgb, true) => "RG",
+            (ColorSpace::Cmyk, false) => "k",
+            (ColorSpace::Cmyk, true) => "K",
+        };
+        let components = match self {
+            // full tint is the darkest
+            ColorSpace::Separation => components.iter().map(|tint| 1.0 - tint).collect(),
+            _ => components.to_vec(),
+        };
+        (operator, components)
+    }
+
+    /// The initial colour in this space, which is black or full tint.
+    fn initial_color(self) -> &'static [f32] {
+        match self {
+            ColorSpace::Gray => &[0.0],
+            ColorSpace::Rgb => &[0.0, 0.0, 0.0],
+            ColorSpace::Cmyk => &[0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Separation => &[1.0],
+        }
+    }
+}
+
+/// Translates the content of one page.
+struct Translator<'a> {
+    document: &'a Document,
+}
+
+impl Translator<'_> {
+    /// Translates content along with the resources it uses, which are defined before it.
+    fn scope(
+        &self,
+        resources: Option<&Object>,
+        content: &[u8],
+        depth: usize,
+    ) -> Result<String, String> {
+        let resources = match resources {
+            Some(resources) => self.dictionary(resources)?,
+            None => Dictionary::new(),
+        };
+        let mut out = String::new();
+        let fonts = self.entries(&resources, b"Font")?;
+        if !fonts.is_empty() {
+            out.push_str("/Fonts <<");
+            for (name, font) in fonts {
+                write!(out, " {} {}", name_token(&name), self.font(&font)?).unwrap();
+            }
+            out.push_str(" >> def\n");
+        }
+        let xobjects = self.entries(&resources, b"XObject")?;
+        if !xobjects.is_empty() {
+            out.push_str("/XObjects <<\n");
+            for (name, xobject) in xobjects {
+                let form = self.form(&xobject, depth)?;
+                writeln!(out, "{} {form}", name_token(&name)).unwrap();
+            }
+            out.push_str(">> def\n");
+        }
+        out.push_str(&self.content(&resources, content)?);
+        Ok(out)
+    }
+
+    /// Follows a reference to a dictionary.
+    fn dictionary(&self, object: &Object) -> Result<Dictionary, String> {
+        let (_, object) = self
+            .document
+            .dereference(object)
+            .map_err(|err| err.to_string())?;
+        match object {
+            Object::Dictionary(dictionary) => Ok(dictionary.clone()),
+            Object::Stream(stream) => Ok(stream.dict.clone()),
+            _ => Err("a resource is not a dictionary".to_owned()),
+        }
+    }
+
+    /// The entries of a resource category, such as `/Font`.
+    fn entries(
+        &self,
+        resources: &Dictionary,
+        category: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Object)>, String> {
+        match resources.get(category) {
+            Ok(entries) => Ok(self
+                .dictionary(entries)?
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect()),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// A PostScript expression for a font, which must be one of the standard 14 fonts.
+    fn font(&self, font: &Object) -> Result<String, String> {
+        let font = self.dictionary(font)?;
+        let name = font
+            .get(b"BaseFont")
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .unwrap_or_default();
+        let subtype = font
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or(b"");
+        if subtype != b"Type1" || !STANDARD_FONTS.contains(&name.as_str()) {
+            return Err(format!(
+                "it uses the font {name:?}, but only the standard 14 fonts are supported"
+            ));
+        }
+        match font.get(b"Encoding") {
+            Err(_) => Ok(format!("/{name} findfont")),
+            Ok(Object::Name(encoding)) if encoding == b"StandardEncoding" => {
+                Ok(format!("/{name} findfont"))
+            }
+            // Latin-1 differs from WinAnsi only in characters which are rarely used
+            Ok(Object::Name(encoding)) if encoding == b"WinAnsiEncoding" => {
+                Ok(format!("/{name} findfont WinAnsi"))
+            }
+            Ok(_) => Err(format!("the font {name:?} has an unsupported encoding")),
+        }
+    }
+
+    /// A procedure which draws a form XObject.
+    fn form(&self, xobject: &Object, depth: usize) -> Result<String, String> {
+        if depth >= MAX_FORM_DEPTH {
+            return Err("its form XObjects are nested too deeply".to_owned());
+        }
+        let (_, object) = self
+            .document
+            .dereference(xobject)
+            .map_err(|err| err.to_string())?;
+        let stream = object
+            .as_stream()
+            .map_err(|_| "an XObject is not a stream")?;
+        match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Form") => {}
+            Ok(b"Image") => return Err("it draws an image, which is not supported".to_owned()),
+            _ => return Err("it draws an unsupported XObject".to_owned()),
+        }
+        let numbers = |key: &[u8]| -> Option<Vec<f32>> {
+            let array = stream.dict.get(key).ok()?.as_array().ok()?;
+            array.iter().map(|value| value.as_float().ok()).collect()
+        };
+        let matrix = numbers(b"Matrix").unwrap_or_else(|| vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let [x0, y0, x1, y1] = numbers(b"BBox")
+            .and_then(|bbox| <[f32; 4]>::try_from(bbox).ok())
+            .ok_or("a form XObject has no bounding box")?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        let body = self.scope(stream.dict.get(b"Resources").ok(), &content, depth + 1)?;
+        let matrix = matrix
+            .iter()
+            .map(f32::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        // the form's resources replace the current ones while it is drawn
+        Ok(format!(
+            "{{ Fonts XObjects q {matrix} cm {x0} {y0} {} {} re W n\n{body}Q /XObjects exch def /Fonts exch def }}",
+            x1 - x0,
+            y1 - y0,
+        ))
+    }
+
+    /// Translates a content stream, whose resources have already been defined.
+    fn content(&self, resources: &Dictionary, content: &[u8]) -> Result<String, String> {
+        let operations = Content::decode(content)
+            .map_err(|err| format!("its content could not be read: {err}"))?
+            .operations;
+        let mut out = String::new();
+        // the fill and stroke colour spaces, saved by `q`
+        let mut spaces = (ColorSpace::Gray, ColorSpace::Gray);
+        let mut saved = Vec::new();
+        for Operation { operator, operands } in operations {
+            let operator = operator.as_str();
+            match operator {
+                "q" => saved.push(spaces),
+                "Q" => spaces = saved.pop().unwrap_or(spaces),
+                "g" => spaces.0 = ColorSpace::Gray,
+                "rg" => spaces.0 = ColorSpace::Rgb,
+                "k" => spaces.0 = ColorSpace::Cmyk,
+                "G" => spaces.1 = ColorSpace::Gray,
+                "RG" => spaces.1 = ColorSpace::Rgb,
+                "K" => spaces.1 = ColorSpace::Cmyk,
+                "cs" | "CS" => {
+                    let stroke = operator == "CS";
+                    let name = operands.first().and_then(|name| name.as_name().ok());
+                    let space = self.color_space(resources, name.unwrap_or(b""))?;
+                    let (operator, components) = space.set_color(stroke, space.initial_color());
+                    write_operation(&mut out, operator, &color_operands(components))?;
+                    if stroke {
+                        spaces.1 = space;
+                    } else {
+                        spaces.0 = space;
+                    }
+                    continue;
+                }
+                "sc" | "scn" | "SC" | "SCN" => {
+                    let stroke = operator.starts_with('S');
+                    let components = operands
+                        .iter()
+                        .map(|operand| operand.as_float().ok())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or("it uses a pattern, which is not supported")?;
+                    let space = if stroke { spaces.1 } else { spaces.0 };
+                    let (operator, components) = space.set_color(stroke, &components);
+                    write_operation(&mut out, operator, &color_operands(components))?;
+                    continue;
+                }
+                "sh" => return Err("it uses a shading, which is not supported".to_owned()),
+                "BI" | "ID" | "EI" => {
+                    return Err("it draws an inline image, which is not supported".to_owned())
+                }
+                // the graphics state parameters (mostly transparency) are ignored
+                "gs" => continue,
+                operator if IGNORED.contains(&operator) => continue,
+                operator if OPERATORS.contains(&operator) => {}
+                operator => return Err(format!("it uses the unsupported operator {operator:?}")),
+            }
+            write_operation(&mut out, operator, &operands)?;
+        }
+        Ok(out)
+    }
+
+    /// Looks up a colour space by the name given to `cs` or `CS`.
+    fn color_space(&self, resources: &Dictionary, name: &[u8]) -> Result<ColorSpace, String> {
+        let unsupported = || {
+            format!(
+                "it uses the colour space {:?}, which is not supported",
+                String::from_utf8_lossy(name)
+            )
+        };
+        let space = match name {
+            b"DeviceGray" | b"G" => return Ok(ColorSpace::Gray),
+            b"DeviceRGB" | b"RGB" => return Ok(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => return Ok(ColorSpace::Cmyk),
+            _ => {
+                self.entries(resources, b"ColorSpace")?
+                    .into_iter()
+                    .find(|(key, _)| key == name)
+                    .ok_or_else(unsupported)?
+                    .1
+            }
+        };
+        let (_, space) = self
+            .document
+            .dereference(&space)
+            .map_err(|err| err.to_string())?;
+        let array = space.as_array().map_err(|_| unsupported())?;
+        match array.first().and_then(|family| family.as_name().ok()) {
+            Some(b"CalGray") => Ok(ColorSpace::Gray),
+            Some(b"CalRGB") => Ok(ColorSpace::Rgb),
+            Some(b"Separation") => Ok(ColorSpace::Separation),
+            Some(b"ICCBased") => {
+                let profile = array.get(1).ok_or_else(unsupported)?;
+                let (_, profile) = self
+                    .document
+                    .dereference(profile)
+                    .map_err(|err| err.to_string())?;
+                let components = profile
+                    .as_stream()
+                    .ok()
+                    .and_then(|profile| profile.dict.get(b"N").ok()?.as_i64().ok());
+                match components {
+                    Some(1) => Ok(ColorSpace::Gray),
+                    Some(3) => Ok(ColorSpace::Rgb),
+                    Some(4) => Ok(ColorSpace::Cmyk),
+                    _ => Err(unsupported()),
+                }
+            }
+            _ => Err(unsupported()),
+        }
+    }
+}
+
+/// Colour components as operands.
+fn color_operands(components: Vec<f32>) -> Vec<Object> {
+    components.into_iter().map(Object::Real).collect()
+}
+
+/// Writes an operation as PostScript, with the operands followed by the operator.
+fn write_operation(out: &mut String, operator: &str, operands: &[Object]) -> Result<(), String> {
+    for operand in operands {
+        write_object(out, operand)?;
+        out.push(' ');
+    }
+    out.push_str(operator);
+    out.push('\n');
+    Ok(())
+}
+
+/// Writes an operand as a PostScript object.
+fn write_object(out: &mut String, object: &Object) -> Result<(), String> {
+    match object {
+        Object::Null => out.push_str("null"),
+        Object::Boolean(value) => write!(out, "{value}").unwrap(),
+        Object::Integer(value) => write!(out, "{value}").unwrap(),
+        Object::Real(value) => write!(out, "{value}").unwrap(),
+        Object::Name(name) => out.push_str(&name_token(name)),
+        Object::String(bytes, _) => out.push_str(&string_literal(bytes)),
+        Object::Array(array) => {
+            out.push('[');
+            for (index, value) in array.iter().enumerate() {
+                if index > 0 {
+                    out.push(' ');
+                }
+                write_object(out, value)?;
+            }
+            out.push(']');
+        }
+        _ => return Err("it has an unsupported operand".to_owned()),
+    }
+    Ok(())
+}
+
+/// A literal name, which is written as a string converted to a name if it contains characters
+/// which cannot appear in a PostScript name.
+fn name_token(name: &[u8]) -> String {
+    let regular = |byte: &u8| byte.is_ascii_graphic() && !b"()<>[]{}/%".contains(byte);
+    if !name.is_empty() && name.iter().all(regular) {
+        format!("/{}", String::from_utf8_lossy(name))
+    } else {
+        format!("{} cvn", string_literal(name))
+    }
+}
+
+/// A string literal, with any characters other than printable ASCII escaped.
+fn string_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("(");
+    for &byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            b' '..=b'~' => literal.push(byte as char),
+            _ => write!(literal, "\\{byte:03o}").unwrap(),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{Dictionary, Object, Stream};
+
+    use crate::{pdf, testing};
+
+    #[test]
+    fn to_postscript() {
+        let document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 300.0]; 2]);
+        let postscript = super::to_postscript(&document).unwrap();
+        assert!(postscript.starts_with("%!PS-Adobe-3.0\n"));
+        assert!(postscript.contains("%%Pages: 2\n"));
+        assert!(postscript.contains("%%Page: 2 2\n"));
+        assert!(postscript.contains("<< /PageSize [200 300] >> setpagedevice\n"));
+        assert!(postscript.contains("0 0 m\n10 10 l\nS\n"));
+        assert_eq!(postscript.matches("showpage").count(), 2);
+    }
+
+    #[test]
+    fn text() {
+        let lines = ["(brackets) and \\".to_owned()];
+        let document = pdf::text_document(&lines, pdf::LETTER).unwrap();
+        let postscript = super::to_postscript(&document).unwrap();
+        assert!(postscript.contains("/Fonts << /FBookbinding /Helvetica findfont WinAnsi >> def\n"));
+        assert!(postscript.contains("(\\(brackets\\) and \\\\) Tj\n"));
+    }
+
+    #[test]
+    fn form() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 300.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let form_id = pdf::page_xobject(&mut document, page_id).unwrap();
+        let content = b"q 0.5 0 0 0.5 0 0 cm /P0 Do Q".to_vec();
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+        let xobjects = Object::Dictionary([("P0", Object::from(form_id))].into_iter().collect());
+        let page = document.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", content_id);
+        page.set(
+            "Resources",
+            Object::Dictionary([("XObject", xobjects)].into_iter().collect()),
+        );
+        let postscript = super::to_postscript(&document).unwrap();
+        assert!(postscript
+            .contains("/XObjects <<\n/P0 { Fonts XObjects q 1 0 0 1 0 0 cm 0 0 200 300 re W n\n"));
+        assert!(postscript.contains("/P0 Do\n"));
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 300.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let content = b"/Sh0 sh".to_vec();
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Contents", content_id);
+        let err = super::to_postscript(&document).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "page 1 cannot be written as PostScript: it uses a shading, which is not supported"
+        );
+    }
+}
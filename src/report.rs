@@ -0,0 +1,144 @@
+//! Machine-readable reports of the imposition, which can be read back to reuse the same plan.
+
+use std::path::Path;
+
+use bookbinding::{arrange_pages_with, SignaturePlan};
+use color_eyre::eyre::{bail, WrapErr};
+
+/// A report of how the document was imposed.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// The plan the document was imposed with.
+    pub plan: SignaturePlan,
+    /// The number (starting from 1) of the page of the padded document printed at each output
+    /// page, in output order.
+    pub order: Vec<usize>,
+}
+
+impl Report {
+    /// The report for a plan.
+    pub fn new(plan: &SignaturePlan) -> Self {
+        let mut order = vec![0; plan.num_pages().0];
+        arrange_pages_with(plan, |src, dest| order[dest] = src + 1);
+        Report {
+            plan: plan.clone(),
+            order,
+        }
+    }
+}
+
+/// Writes the report for a plan to a file, as JSON.
+pub fn write(path: &Path, plan: &SignaturePlan) -> color_eyre::Result<()> {
+    let json = serde_json::to_string_pretty(&Report::new(plan))?;
+    std::fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+/// Reads the plan from a report written by [`write`], checking that it is for a document with
+/// `num_pages` pages once padded, and that its order is the one the plan arranges the pages in.
+pub fn read_plan(path: &Path, num_pages: usize) -> color_eyre::Result<SignaturePlan> {
+    let json = std::fs::read_to_string(path)?;
+    let report: Report = serde_json::from_str(&json)
+        .wrap_err_with(|| format!("invalid report {}", path.display()))?;
+    check(&report, num_pages).wrap_err_with(|| format!("invalid plan in {}", path.display()))?;
+    Ok(report.plan)
+}
+
+/// Checks that a report which was read back can be imposed on a document with `num_pages` pages.
+fn check(report: &Report, num_pages: usize) -> color_eyre::Result<()> {
+    let plan_pages = report.plan.num_pages().0;
+    if plan_pages != num_pages {
+        bail!(
+            "the plan is for {plan_pages} pages, but the document has {num_pages} pages once \
+             padded"
+        );
+    }
+    if report.order != Report::new(&report.plan).order {
+        bail!("the order does not match the plan; use --mapping-file to impose in another order");
+    }
+    Ok(())
+}
+
+/// A single line summarising the imposition for automated checks, as space-separated `key=value`
+/// pairs, always with the same keys in the same order:
+///
+/// ```text
+/// bookbinding-qa version=1 input_pages=10 blank_pages=2 output_pages=12 sheets=3 signatures=1 permutation=ok
+/// ```
+///
+/// - `version`: the version of this format, which changes if any key changes meaning.
+/// - `input_pages`: the pages from the input which are imposed.
+/// - `blank_pages`: the pages added by padding, including end pages and endpapers.
+/// - `output_pages`: the pages in the output, including any cover sheet.
+/// - `sheets` and `signatures`: as planned.
+/// - `permutation`: `ok` if each page of the input was found on the output page it was imposed
+///   to, or `invalid` otherwise.
+pub fn qa_summary(
+    input_pages: usize,
+    blank_pages: usize,
+    output_pages: usize,
+    plan: &SignaturePlan,
+    permutation: bool,
+) -> String {
+    let permutation = if permutation { "ok" } else { "invalid" };
+    format!(
+        "bookbinding-qa version=1 input_pages={input_pages} blank_pages={blank_pages} \
+         output_pages={output_pages} sheets={} signatures={} permutation={permutation}",
+        plan.num_sheets().0,
+        plan.signatures.len(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use bookbinding::{plan_signatures, Binding, Sheets, SignatureParams, WritingMode};
+    use test_case::test_case;
+
+    use super::Report;
+
+    #[test]
+    fn round_trip() {
+        let params = SignatureParams {
+            signature_size: 2,
+            minimum_remainder_size: 1,
+            writing_mode: WritingMode::VerticalRtl,
+            ..Default::default()
+        };
+        let report = Report::new(&plan_signatures(Sheets(3), params));
+        assert_eq!(report.order, [1, 8, 7, 2, 3, 6, 5, 4, 9, 12, 11, 10]);
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert!(json.contains(r#""writing_mode": "vertical-rtl""#));
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn check() {
+        let report = || Report::new(&plan_signatures(Sheets(3), SignatureParams::default()));
+        assert!(super::check(&report(), 12).is_ok());
+        assert!(super::check(&report(), 16).is_err());
+        let mut swapped = report();
+        swapped.order.swap(0, 1);
+        assert!(super::check(&swapped, 12).is_err());
+    }
+
+    #[test_case(10, 2, 12, SignatureParams::default(), true => "bookbinding-qa version=1 input_pages=10 blank_pages=2 output_pages=12 sheets=3 signatures=1 permutation=ok")]
+    #[test_case(97, 3, 100, SignatureParams::default(), true => "bookbinding-qa version=1 input_pages=97 blank_pages=3 output_pages=100 sheets=25 signatures=4 permutation=ok")]
+    #[test_case(40, 0, 44, SignatureParams { binding: Binding::Saddle, ..Default::default() }, true => "bookbinding-qa version=1 input_pages=40 blank_pages=0 output_pages=44 sheets=10 signatures=1 permutation=ok")]
+    #[test_case(10, 2, 12, SignatureParams::default(), false => "bookbinding-qa version=1 input_pages=10 blank_pages=2 output_pages=12 sheets=3 signatures=1 permutation=invalid")]
+    fn qa_summary(
+        input_pages: usize,
+        blank_pages: usize,
+        output_pages: usize,
+        params: SignatureParams,
+        permutation: bool,
+    ) -> String {
+        let num_sheets = Sheets((input_pages + blank_pages).div_ceil(4));
+        super::qa_summary(
+            input_pages,
+            blank_pages,
+            output_pages,
+            &plan_signatures(num_sheets, params),
+            permutation,
+        )
+    }
+}
@@ -0,0 +1,130 @@
+//! The imposition schemes, which are the ways of placing the pages on the sheets, and the
+//! `schemes` command, which lists them.
+
+use log::info;
+
+/// A way of placing the pages on the sheets. The options select one (see [`crate::scheme`]), and
+/// the imposition dispatches on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// Folded into signatures, with each sheet folded once.
+    Folded,
+    /// Four pages to one side of a sheet, folded into a card.
+    FrenchFold,
+    /// Pages in a grid on each sheet, cut apart rather than folded.
+    Grid,
+}
+
+/// An entry in [`SCHEMES`].
+#[derive(Clone, Copy, Debug)]
+pub struct SchemeInfo {
+    pub scheme: Scheme,
+    /// The short name of the scheme.
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The number of pages on each sheet, counting both sides.
+    pub pages_per_sheet: &'static str,
+    /// The options to impose with the scheme, after the input and output.
+    pub options: &'static str,
+}
+
+/// Every imposition scheme, in the order they are listed.
+pub static SCHEMES: [SchemeInfo; 3] = [
+    SchemeInfo {
+        scheme: Scheme::Folded,
+        name: "folio",
+        description: "each sheet folded once, and nested into signatures (the default)",
+        pages_per_sheet: "4",
+        options: "--signature-size 6",
+    },
+    SchemeInfo {
+        scheme: Scheme::FrenchFold,
+        name: "french-fold",
+        description: "four pages to one side of each sheet, folded twice into a card",
+        pages_per_sheet: "4",
+        options: "--french-fold",
+    },
+    SchemeInfo {
+        scheme: Scheme::Grid,
+        name: "grid",
+        description: "pages in a grid on one side of each sheet, cut apart into cards",
+        pages_per_sheet: "columns x rows",
+        options: "--grid 3x4",
+    },
+];
+
+/// The entry for a scheme in [`SCHEMES`].
+pub fn info(scheme: Scheme) -> &'static SchemeInfo {
+    SCHEMES
+        .iter()
+        .find(|info| info.scheme == scheme)
+        .expect("every scheme is in the registry")
+}
+
+/// Prints the list of schemes.
+pub fn run() -> color_eyre::Result<()> {
+    for line in lines() {
+        info!("{line}");
+    }
+    Ok(())
+}
+
+/// The lines describing each scheme, with an example of imposing with it.
+fn lines() -> Vec<String> {
+    let width = SCHEMES
+        .iter()
+        .map(|info| info.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut lines = Vec::new();
+    for info in SCHEMES {
+        lines.push(format!("{:width$}  {}", info.name, info.description));
+        lines.push(format!(
+            "{:width$}  pages per sheet: {}",
+            "", info.pages_per_sheet
+        ));
+        lines.push(format!(
+            "{:width$}  example: bookbinding in.pdf out.pdf {}",
+            "", info.options
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Scheme, SCHEMES};
+
+    #[test]
+    fn lines() {
+        let lines = super::lines();
+        assert_eq!(lines.len(), 3 * SCHEMES.len());
+        assert_eq!(
+            lines[0],
+            "folio        each sheet folded once, and nested into signatures (the default)"
+        );
+        assert_eq!(lines[1], "             pages per sheet: 4");
+        assert_eq!(
+            lines[2],
+            "             example: bookbinding in.pdf out.pdf --signature-size 6"
+        );
+    }
+
+    #[test]
+    fn every_scheme() {
+        let schemes = [Scheme::Folded, Scheme::FrenchFold, Scheme::Grid];
+        for scheme in schemes {
+            // a new scheme must be added both here and to the registry
+            match scheme {
+                Scheme::Folded | Scheme::FrenchFold | Scheme::Grid => {}
+            }
+            assert_eq!(
+                SCHEMES.iter().filter(|info| info.scheme == scheme).count(),
+                1,
+                "{scheme:?}"
+            );
+            assert_eq!(super::info(scheme).scheme, scheme);
+        }
+        assert_eq!(SCHEMES.len(), schemes.len());
+    }
+}
@@ -0,0 +1,942 @@
+//! Rendering of imposed sheets one at a time, for previews.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use color_eyre::eyre::eyre;
+use lopdf::{
+    content::{Content, Operation},
+    Dictionary, Document, Object, ObjectId, Stream,
+};
+
+use crate::{
+    arrange_pages_with, edge_marks, mark_operations,
+    page::{copy_object, page_form, placed_box, rotation, PlacedBox},
+    plan_signatures, Marks, Pages, SignatureParams, SignaturePlan, MARK_MARGIN,
+};
+
+/// The number of columns and rows of pages on each side of a sheet shown by [`sheet_document`] or
+/// [`nup_document`], written as `<columns>x<rows>`: `2x1` for two pages side by side, `1x2` for
+/// two pages one above the other, head to head, and `2x2` or `4x1` for four pages, such as both
+/// sides of a sheet at once. A layout must hold either one side of a sheet or the whole sheet (see
+/// [`NupLayout::check`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NupLayout {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Default for NupLayout {
+    fn default() -> Self {
+        NupLayout {
+            columns: 2,
+            rows: 1,
+        }
+    }
+}
+
+impl FromStr for NupLayout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid n-up layout {value:?}, expected `<columns>x<rows>`");
+        let (columns, rows) = value.split_once('x').ok_or_else(error)?;
+        let columns = columns.trim().parse().map_err(|_| error())?;
+        let rows = rows.trim().parse().map_err(|_| error())?;
+        if columns == 0 || rows == 0 {
+            return Err(format!(
+                "n-up layout {value:?} must have at least one column and row"
+            ));
+        }
+        Ok(NupLayout { columns, rows })
+    }
+}
+
+impl NupLayout {
+    /// The number of pages on each side.
+    pub fn pages_per_side(self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// The width and height of a side with pages of the given size.
+    pub fn side_size(self, (width, height): (f32, f32)) -> (f32, f32) {
+        (self.columns as f32 * width, self.rows as f32 * height)
+    }
+
+    /// The rectangle of each slot on a side with pages of the given size. See [`nup_slots`].
+    pub fn slots(self, cell: (f32, f32)) -> Vec<[f32; 4]> {
+        nup_slots(self.columns, self.rows, cell, 0.0, 0.0)
+    }
+
+    /// How far the page in a slot is turned, in degrees clockwise. With one page above the other,
+    /// the top page is upside down, so that the heads of the pages meet at the fold between them.
+    pub fn rotation(self, slot: usize) -> i64 {
+        if self.columns == 1 && self.rows == 2 && slot == 0 {
+            180
+        } else {
+            0
+        }
+    }
+
+    /// The edges of the given slots which are marked: the x coordinates of the vertical edges and
+    /// the y coordinates of the horizontal edges, from left to right and from top to bottom. An edge
+    /// between two slots is a fold if the slots on either side of it are the two pages of one side
+    /// of a sheet, and a cut otherwise.
+    pub fn marked_edges(self, slots: &[[f32; 4]], marks: Marks) -> (Vec<f32>, Vec<f32>) {
+        let index = |row: usize, column: usize| row * self.columns + column;
+        let folded = |a: usize, b: usize| a.is_multiple_of(2) && b == a + 1;
+        let marked = |fold: bool| match marks {
+            Marks::All => true,
+            Marks::Outer => false,
+            Marks::Cut => !fold,
+        };
+        let mut xs = vec![slots[0][0]];
+        for column in 1..self.columns {
+            let fold = (0..self.rows).all(|row| folded(index(row, column - 1), index(row, column)));
+            if marked(fold) {
+                xs.push(slots[index(0, column)][0]);
+            }
+        }
+        xs.push(slots[self.columns - 1][2]);
+        let mut ys = vec![slots[0][3]];
+        for row in 1..self.rows {
+            let fold =
+                (0..self.columns).all(|column| folded(index(row - 1, column), index(row, column)));
+            if marked(fold) {
+                ys.push(slots[index(row, 0)][3]);
+            }
+        }
+        ys.push(slots[index(self.rows - 1, 0)][1]);
+        (xs, ys)
+    }
+
+    /// Checks that the layout holds the pages of one side of a sheet, or of the whole sheet, so
+    /// that no side is split between two pages of the output.
+    pub fn check(self) -> Result<(), String> {
+        let pages_per_side = self.pages_per_side();
+        if pages_per_side == 2 || pages_per_side == 4 {
+            Ok(())
+        } else {
+            Err(format!(
+                "n-up layout {}x{} holds {pages_per_side} pages, but each sheet has 2 pages on \
+                 each side and 4 in all",
+                self.columns, self.rows,
+            ))
+        }
+    }
+}
+
+/// The rectangles `[x0, y0, x1, y1]` of the slots of a grid of `columns` by `rows` cells of the
+/// given size, with `gap` between neighbouring cells and `margin` around the grid, on a sheet
+/// which fits them exactly. The slots fill the rows from left to right, starting with the top
+/// row, which is the order pages are placed in by every n-up imposition.
+pub fn nup_slots(
+    columns: usize,
+    rows: usize,
+    (width, height): (f32, f32),
+    gap: f32,
+    margin: f32,
+) -> Vec<[f32; 4]> {
+    let top = margin + rows as f32 * height + rows.saturating_sub(1) as f32 * gap;
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            let x0 = margin + column as f32 * (width + gap);
+            let y1 = top - row as f32 * (height + gap);
+            [x0, y1 - height, x0 + width, y1]
+        })
+        .collect()
+}
+
+/// How the sides are laid out on the pages of the output by [`nup_document`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SheetSettings {
+    /// The width and height of the pages of the output, in points. The pages of each side are
+    /// centered on it, and shrunk to fit inside the margin if they are too large. Without a size,
+    /// each page of the output fits the pages of its side and the margins around them.
+    pub size: Option<(f32, f32)>,
+    /// The space to leave clear at the edges of each page of the output, in points.
+    pub margin: f32,
+    /// The marks to draw around the pages of each side, if any. The marks are lined up with the
+    /// edges of the pages, so with a fixed size they go in the margin, unless `reserve_marks` is
+    /// set; otherwise, the pages are surrounded by a further margin of [`MARK_MARGIN`] to hold
+    /// them.
+    pub marks: Option<Marks>,
+    /// With a fixed size, keep a further margin of [`MARK_MARGIN`] inside the margin for the
+    /// marks, shrinking the pages to make room if needed, so that the marks are never in the
+    /// margin.
+    pub reserve_marks: bool,
+}
+
+/// Where the pages of a side go on a page of the output. See [`fit`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fit {
+    /// The width and height of the page of the output.
+    size: (f32, f32),
+    /// The position of the bottom left corner of the pages of the side.
+    origin: (f32, f32),
+    /// How much the pages of the side are scaled, which is never more than 1.
+    scale: f32,
+}
+
+/// Fits a side with the given width and height onto a page of the output with the given settings.
+fn fit((width, height): (f32, f32), settings: SheetSettings) -> Result<Fit, String> {
+    let Some((sheet_width, sheet_height)) = settings.size else {
+        let marks = if settings.marks.is_some() {
+            MARK_MARGIN
+        } else {
+            0.0
+        };
+        let border = settings.margin + marks;
+        return Ok(Fit {
+            size: (width + 2.0 * border, height + 2.0 * border),
+            origin: (border, border),
+            scale: 1.0,
+        });
+    };
+    let border = if settings.reserve_marks && settings.marks.is_some() {
+        settings.margin + MARK_MARGIN
+    } else {
+        settings.margin
+    };
+    let available = (sheet_width - 2.0 * border, sheet_height - 2.0 * border);
+    if available.0 <= 0.0 || available.1 <= 0.0 {
+        return Err(format!(
+            "a sheet margin of {border}pt, with any room for the marks, leaves no room for the \
+             pages on a sheet of {sheet_width}x{sheet_height}pt",
+        ));
+    }
+    let scale = (available.0 / width).min(available.1 / height).min(1.0);
+    Ok(Fit {
+        size: (sheet_width, sheet_height),
+        origin: (
+            (sheet_width - width * scale) / 2.0,
+            (sheet_height - height * scale) / 2.0,
+        ),
+        scale,
+    })
+}
+
+/// The marks around the pages of a side with pages of the given size, placed as given. See
+/// [`NupLayout::marked_edges`] and [`edge_marks`].
+fn side_marks(layout: NupLayout, cell: (f32, f32), fit: Fit, marks: Marks) -> Vec<[f32; 4]> {
+    let Fit {
+        origin: (ox, oy),
+        scale,
+        ..
+    } = fit;
+    let place = |[x0, y0, x1, y1]: [f32; 4]| {
+        [
+            x0 * scale + ox,
+            y0 * scale + oy,
+            x1 * scale + ox,
+            y1 * scale + oy,
+        ]
+    };
+    let slots = layout
+        .slots(cell)
+        .into_iter()
+        .map(place)
+        .collect::<Vec<_>>();
+    let (width, height) = layout.side_size(cell);
+    let (xs, ys) = layout.marked_edges(&slots, marks);
+    edge_marks(place([0.0, 0.0, width, height]), &xs, &ys)
+}
+
+/// Calls `with` for each physical sheet of the imposed document, in printing order, with the
+/// index of the sheet and a document showing it with the given layout. See [`sheet_document`].
+///
+/// Each sheet is built only when it is reached, so a consumer can stop early or show each sheet as
+/// soon as it is ready.
+pub fn for_each_sheet(
+    document: &Document,
+    params: SignatureParams,
+    layout: NupLayout,
+    mut with: impl FnMut(usize, Document),
+) -> color_eyre::Result<()> {
+    let num_sheets = Pages(document.get_pages().len()).sheets_needed();
+    let plan = plan_signatures(num_sheets, params);
+    for sheet in 0..num_sheets.0 {
+        with(sheet, sheet_document(document, &plan, sheet, layout)?);
+    }
+    Ok(())
+}
+
+/// Builds a document showing one sheet of the imposed document, with its pages placed in order in
+/// the slots of the layout, with blank slots where the document was padded. With the default
+/// layout of `2x1`, there is a page for the front of the sheet and one for the back, each with its
+/// two pages side by side.
+///
+/// The document is self-contained: everything the placed pages use is copied into it. Pages are
+/// placed by their crop box, turned by their `/Rotate` and by the layout's rotation of their slot.
+/// It is an error if the layout does not fit the sheets of the plan (see [`NupLayout::check`]).
+pub fn sheet_document(
+    document: &Document,
+    plan: &SignaturePlan,
+    sheet: usize,
+    layout: NupLayout,
+) -> color_eyre::Result<Document> {
+    layout.check().map_err(|err| eyre!("{err}"))?;
+    let page_ids = document.page_iter().collect::<Vec<_>>();
+    let mut slots = [None; 4];
+    let sheet_pages = sheet * 4..sheet * 4 + 4;
+    arrange_pages_with(plan, |src, dest| {
+        if sheet_pages.contains(&dest) {
+            slots[dest - sheet_pages.start] = page_ids.get(src).copied();
+        }
+    });
+    place_sides(document, &slots, layout, |_| SheetSettings::default())
+}
+
+/// Builds a document with the pages of an imposed document placed together on the sides of its
+/// sheets, as they are printed: each run of [`layout.pages_per_side()`](NupLayout::pages_per_side)
+/// pages, in order, is placed in the slots of the layout on one page of the output. The pages are
+/// placed as by [`sheet_document`], with the settings given for each side by its index, and the
+/// document information is kept.
+///
+/// The layout should be checked against the sheets the document was imposed for with
+/// [`NupLayout::check`], since otherwise the sides of the sheets are split between the output
+/// pages.
+pub fn nup_document(
+    document: &Document,
+    layout: NupLayout,
+    settings: impl Fn(usize) -> SheetSettings,
+) -> color_eyre::Result<Document> {
+    let page_ids = document.page_iter().map(Some).collect::<Vec<_>>();
+    place_sides(document, &page_ids, layout, settings)
+}
+
+/// Builds a self-contained document with a page for each run of the layout's number of pages,
+/// with each page placed in its slot and `None` leaving a slot blank. Every slot is the size of
+/// the largest page placed, after turning it.
+fn place_sides(
+    document: &Document,
+    pages: &[Option<ObjectId>],
+    layout: NupLayout,
+    settings: impl Fn(usize) -> SheetSettings,
+) -> color_eyre::Result<Document> {
+    let mut output = Document::with_version(document.version.as_str());
+    let mut copied = BTreeMap::new();
+    let mut placed = Vec::new();
+    for (index, &page_id) in pages.iter().enumerate() {
+        placed.push(match page_id {
+            Some(page_id) => {
+                let (xobject_id, bbox) = page_xobject(document, page_id, &mut output, &mut copied)?;
+                let slot = index % layout.pages_per_side();
+                let rotation = (rotation(document, page_id) + layout.rotation(slot)) % 360;
+                Some((xobject_id, bbox, rotation))
+            }
+            None => None,
+        });
+    }
+    // the width and height of a placed page, after turning it
+    let size = |[x0, y0, x1, y1]: [f32; 4], rotation: i64| match rotation {
+        90 | 270 => (y1 - y0, x1 - x0),
+        _ => (x1 - x0, y1 - y0),
+    };
+    // every slot is the size of the largest page on the sheet
+    let cell =
+        placed
+            .iter()
+            .flatten()
+            .fold((0.0f32, 0.0f32), |(width, height), &(_, bbox, rotation)| {
+                let (page_width, page_height) = size(bbox, rotation);
+                (width.max(page_width), height.max(page_height))
+            });
+    let side_size = layout.side_size(cell);
+    let slots = layout.slots(cell);
+
+    let pages_id = output.new_object_id();
+    let mut kids = Vec::new();
+    for (index, side) in placed.chunks(layout.pages_per_side()).enumerate() {
+        let settings = settings(index);
+        let fit = self::fit(side_size, settings).map_err(|err| eyre!("{err}"))?;
+        let Fit {
+            size: (sheet_width, sheet_height),
+            origin: (ox, oy),
+            scale,
+        } = fit;
+        let mut operations = settings
+            .marks
+            .map(|marks| mark_operations(&side_marks(layout, cell, fit, marks)))
+            .unwrap_or_default();
+        let mut xobjects = Dictionary::new();
+        for (slot, (xobject_id, bbox, rotation)) in side
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, placed)| Some((slot, (*placed)?)))
+        {
+            let name = format!("P{slot}");
+            let [x0, y0, ..] = bbox;
+            let (width, height) = size(bbox, rotation);
+            // turn the page clockwise about the origin, keeping it above and to the right of it
+            let ([a, b, c, d], [e, f]) = match rotation {
+                90 => ([0, -1, 1, 0], [0.0, bbox[2] - x0]),
+                180 => ([-1, 0, 0, -1], [bbox[2] - x0, bbox[3] - y0]),
+                270 => ([0, 1, -1, 0], [bbox[3] - y0, 0.0]),
+                _ => ([1, 0, 0, 1], [0.0, 0.0]),
+            };
+            // and center it in its slot
+            let [sx0, sy0, sx1, sy1] = slots[slot];
+            let dx = sx0 + ((sx1 - sx0) - width) / 2.0;
+            let dy = sy0 + ((sy1 - sy0) - height) / 2.0;
+            let e = e - x0 * a as f32 - y0 * c as f32 + dx;
+            let f = f - x0 * b as f32 - y0 * d as f32 + dy;
+            // then scale the side to fit the sheet, and move it into place
+            let matrix = [
+                a as f32 * scale,
+                b as f32 * scale,
+                c as f32 * scale,
+                d as f32 * scale,
+                e * scale + ox,
+                f * scale + oy,
+            ];
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.map(Object::from).to_vec()),
+                Operation::new("Do", vec![Object::Name(name.clone().into_bytes())]),
+                Operation::new("Q", vec![]),
+            ]);
+            xobjects.set(name, xobject_id);
+        }
+        let content_id = output.add_object(Stream::new(
+            Dictionary::new(),
+            Content { operations }.encode()?,
+        ));
+        let page = Object::Dictionary(
+            [
+                ("Type", Object::from("Page")),
+                ("Parent", Object::from(pages_id)),
+                ("Contents", Object::from(content_id)),
+                (
+                    "Resources",
+                    Object::Dictionary(
+                        [("XObject", Object::Dictionary(xobjects))]
+                            .into_iter()
+                            .collect(),
+                    ),
+                ),
+                (
+                    "MediaBox",
+                    Object::from(vec![
+                        0.into(),
+                        0.into(),
+                        sheet_width.into(),
+                        sheet_height.into(),
+                    ]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        kids.push(Object::from(output.add_object(page)));
+    }
+    output.set_object(
+        pages_id,
+        Object::Dictionary(
+            [
+                ("Type", Object::from("Pages")),
+                ("Count", Object::from(kids.len() as i64)),
+                ("Kids", Object::from(kids)),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    // keep how the document is shown, such as the direction of a right-to-left book
+    let preferences = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"ViewerPreferences"))
+        .ok()
+        .map(|preferences| copy_object(document, &mut output, &mut copied, preferences));
+    let catalog_id = output.add_object(Object::Dictionary(
+        [
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::from(pages_id)),
+        ]
+        .into_iter()
+        .chain(preferences.map(|preferences| ("ViewerPreferences", preferences)))
+        .collect(),
+    ));
+    output.trailer.set("Root", catalog_id);
+    if let Ok(info) = document.trailer.get(b"Info") {
+        let info = copy_object(document, &mut output, &mut copied, info);
+        output.trailer.set("Info", info);
+    }
+    Ok(output)
+}
+
+/// Adds a form XObject to `output` which draws the content of a page of `document`, copying
+/// everything it uses along with it. Returns the id of the XObject and its bounding box, which is
+/// the page's crop box. See [`page_form`].
+fn page_xobject(
+    document: &Document,
+    page_id: ObjectId,
+    output: &mut Document,
+    copied: &mut BTreeMap<ObjectId, ObjectId>,
+) -> lopdf::Result<(ObjectId, [f32; 4])> {
+    // the default media box is US Letter
+    let bbox = placed_box(document, page_id, PlacedBox::Crop).unwrap_or([0.0, 0.0, 612.0, 792.0]);
+    let form = Object::Stream(page_form(document, page_id, bbox)?);
+    let xobject = copy_object(document, output, copied, &form);
+    Ok((output.add_object(xobject), bbox))
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{Dictionary, Document, Object};
+    use test_case::test_case;
+
+    use super::{Fit, NupLayout, SheetSettings};
+    use crate::{plan_signatures, testing, Marks, Sheets, SignatureParams, MARK_MARGIN};
+
+    /// A document with the given number of pages (see [`testing::document_with_boxes`]), which
+    /// inherit their media box and a shared font resource from the page tree.
+    fn document(num_pages: usize) -> Document {
+        let mut document = testing::document_with_boxes(&vec![[0.0, 0.0, 100.0, 200.0]; num_pages]);
+        let font_id = document.add_object(Object::Dictionary(
+            [
+                ("Type", Object::from("Font")),
+                ("Subtype", Object::from("Type1")),
+                ("BaseFont", Object::from("Helvetica")),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+        let resources_id = document.add_object(Object::Dictionary(
+            [(
+                "Font",
+                Object::Dictionary([("F1", Object::from(font_id))].into_iter().collect()),
+            )]
+            .into_iter()
+            .collect(),
+        ));
+        for page_id in document.page_iter().collect::<Vec<_>>() {
+            let page = document.get_dictionary_mut(page_id).unwrap();
+            page.remove(b"Resources");
+            page.remove(b"MediaBox");
+        }
+        let pages_id = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .unwrap();
+        let pages = document.get_dictionary_mut(pages_id).unwrap();
+        pages.set("Resources", resources_id);
+        pages.set("MediaBox", vec![0.into(), 0.into(), 100.into(), 200.into()]);
+        document
+    }
+
+    /// The label of each page placed on each side of the sheet, in order, or `None` for blank
+    /// slots.
+    fn labels(sheet: &Document) -> Vec<Vec<Option<String>>> {
+        sheet
+            .page_iter()
+            .map(|page_id| {
+                let xobjects = sheet
+                    .get_dictionary(page_id)
+                    .and_then(|page| page.get(b"Resources"))
+                    .and_then(Object::as_dict)
+                    .and_then(|resources| resources.get(b"XObject"))
+                    .and_then(Object::as_dict)
+                    .unwrap();
+                ["P0", "P1"]
+                    .map(|name| {
+                        let id = xobjects.get(name.as_bytes()).ok()?.as_reference().ok()?;
+                        let stream = sheet.get_object(id).ok()?.as_stream().ok()?;
+                        let content = String::from_utf8(stream.content.clone()).ok()?;
+                        Some(content.lines().next()?.to_owned())
+                    })
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn for_each_sheet() {
+        let params = SignatureParams {
+            signature_size: 2,
+            ..Default::default()
+        };
+        let mut sheets = Vec::new();
+        super::for_each_sheet(&document(6), params, Default::default(), |index, sheet| {
+            sheets.push((index, sheet))
+        })
+        .unwrap();
+        assert_eq!(sheets.len(), 2);
+        let label = |index: usize| Some(format!("% page {index}"));
+        let (index, sheet) = &sheets[0];
+        assert_eq!(*index, 0);
+        assert_eq!(
+            labels(sheet),
+            [[None, label(0)], [label(1), None]].map(Vec::from)
+        );
+        let (index, sheet) = &sheets[1];
+        assert_eq!(*index, 1);
+        assert_eq!(
+            labels(sheet),
+            [[label(5), label(2)], [label(3), label(4)]].map(Vec::from)
+        );
+        // each sheet is self-contained, with its own copy of the shared font
+        for (_, sheet) in &sheets {
+            for object in sheet.objects.values() {
+                let references = match object {
+                    Object::Dictionary(dict) => dict.iter().map(|(_, value)| value).collect(),
+                    Object::Stream(stream) => stream.dict.iter().map(|(_, value)| value).collect(),
+                    _ => Vec::new(),
+                };
+                for id in references
+                    .into_iter()
+                    .filter_map(|value| value.as_reference().ok())
+                {
+                    assert!(sheet.objects.contains_key(&id), "missing object {id:?}");
+                }
+            }
+            let fonts = sheet
+                .objects
+                .values()
+                .filter_map(|object| object.as_dict().ok())
+                .filter(|dict| dict.type_is(b"Font"))
+                .count();
+            assert_eq!(fonts, 1);
+        }
+    }
+
+    #[test_case("2x1" => vec![[0.0, 0.0, 100.0, 200.0], [100.0, 0.0, 200.0, 200.0]])]
+    #[test_case("1x2" => vec![[0.0, 200.0, 100.0, 400.0], [0.0, 0.0, 100.0, 200.0]])]
+    #[test_case("2x2" => vec![
+        [0.0, 200.0, 100.0, 400.0],
+        [100.0, 200.0, 200.0, 400.0],
+        [0.0, 0.0, 100.0, 200.0],
+        [100.0, 0.0, 200.0, 200.0],
+    ])]
+    #[test_case("4x1" => vec![
+        [0.0, 0.0, 100.0, 200.0],
+        [100.0, 0.0, 200.0, 200.0],
+        [200.0, 0.0, 300.0, 200.0],
+        [300.0, 0.0, 400.0, 200.0],
+    ])]
+    fn slots(layout: &str) -> Vec<[f32; 4]> {
+        let layout = layout.parse::<NupLayout>().unwrap();
+        let slots = layout.slots((100.0, 200.0));
+        assert_eq!(slots.len(), layout.pages_per_side());
+        slots
+    }
+
+    /// The fold between the two pages of each side is only marked with `all`, and the other edges
+    /// between pages, which are cut, are marked with `cut` too.
+    #[test_case("2x1", Marks::All => (vec![0.0, 100.0, 200.0], vec![200.0, 0.0]))]
+    #[test_case("2x1", Marks::Outer => (vec![0.0, 200.0], vec![200.0, 0.0]))]
+    #[test_case("2x1", Marks::Cut => (vec![0.0, 200.0], vec![200.0, 0.0]))]
+    #[test_case("1x2", Marks::All => (vec![0.0, 100.0], vec![400.0, 200.0, 0.0]))]
+    #[test_case("1x2", Marks::Cut => (vec![0.0, 100.0], vec![400.0, 0.0]))]
+    #[test_case("2x2", Marks::Outer => (vec![0.0, 200.0], vec![400.0, 0.0]))]
+    #[test_case("2x2", Marks::Cut => (vec![0.0, 200.0], vec![400.0, 200.0, 0.0]))]
+    #[test_case("4x1", Marks::Outer => (vec![0.0, 400.0], vec![200.0, 0.0]))]
+    #[test_case("4x1", Marks::Cut => (vec![0.0, 200.0, 400.0], vec![200.0, 0.0]))]
+    #[test_case("4x1", Marks::All => (vec![0.0, 100.0, 200.0, 300.0, 400.0], vec![200.0, 0.0]))]
+    fn marked_edges(layout: &str, marks: Marks) -> (Vec<f32>, Vec<f32>) {
+        let layout = layout.parse::<NupLayout>().unwrap();
+        layout.marked_edges(&layout.slots((100.0, 200.0)), marks)
+    }
+
+    #[test_case("2x1" => Some((2, 1)))]
+    #[test_case(" 1 x 2 " => Some((1, 2)))]
+    #[test_case("0x2" => None)]
+    #[test_case("2" => None)]
+    fn parse_layout(value: &str) -> Option<(usize, usize)> {
+        let NupLayout { columns, rows } = value.parse().ok()?;
+        Some((columns, rows))
+    }
+
+    /// A layout which splits the sides of the sheets is an error rather than a panic.
+    #[test]
+    fn sheet_document_invalid_layout() {
+        let plan = plan_signatures(Sheets(1), SignatureParams::default());
+        let layout = NupLayout {
+            columns: 3,
+            rows: 1,
+        };
+        assert!(super::sheet_document(&document(4), &plan, 0, layout).is_err());
+    }
+
+    #[test]
+    fn stacked_layout() {
+        let plan = plan_signatures(Sheets(1), SignatureParams::default());
+        let layout = NupLayout {
+            columns: 1,
+            rows: 2,
+        };
+        let sheet = super::sheet_document(&document(4), &plan, 0, layout).unwrap();
+        let label = |index: usize| Some(format!("% page {index}"));
+        assert_eq!(
+            labels(&sheet),
+            [[label(3), label(0)], [label(1), label(2)]].map(Vec::from)
+        );
+        let page_id = sheet.page_iter().next().unwrap();
+        let page = sheet.get_dictionary(page_id).unwrap();
+        let media_box = page
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(media_box, [0.0, 0.0, 100.0, 400.0]);
+        // the first page of the side is at the top, upside down, with its head against the
+        // second page's
+        let content = sheet.get_page_content(page_id).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert!(
+            content.contains("-1 0 0 -1 100 400 cm\n/P0 Do"),
+            "{content}"
+        );
+        assert!(content.contains("1 0 0 1 0 0 cm\n/P1 Do"), "{content}");
+    }
+
+    #[test_case("2x1" => true)]
+    #[test_case("1x2" => true)]
+    #[test_case("2x2" => true)]
+    #[test_case("4x1" => true)]
+    #[test_case("1x1" => false)]
+    #[test_case("3x1" => false)]
+    #[test_case("4x2" => false)]
+    fn check(layout: &str) -> bool {
+        let layout = layout.parse::<NupLayout>().unwrap();
+        layout.check().is_ok()
+    }
+
+    #[test]
+    fn nup_document() {
+        let mut document = document(4);
+        let page_ids = document.page_iter().collect::<Vec<_>>();
+        // a page turned a quarter turn, as with `--head-binding`
+        document
+            .get_dictionary_mut(page_ids[1])
+            .unwrap()
+            .set("Rotate", 270);
+        let sheet =
+            super::nup_document(&document, Default::default(), |_| Default::default()).unwrap();
+        let label = |index: usize| Some(format!("% page {index}"));
+        assert_eq!(
+            labels(&sheet),
+            [[label(0), label(1)], [label(2), label(3)]].map(Vec::from)
+        );
+        // every slot is as wide as the turned page is
+        let page_id = sheet.page_iter().next().unwrap();
+        let media_box = sheet
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(media_box, [0.0, 0.0, 400.0, 200.0]);
+        let content = sheet.get_page_content(page_id).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.contains("0 1 -1 0 400 50 cm\n/P1 Do"), "{content}");
+    }
+
+    /// The marks are drawn in a margin around the pages, which moves them in from the edges.
+    #[test]
+    fn nup_document_marks() {
+        let settings = SheetSettings {
+            marks: Some(Marks::Outer),
+            ..Default::default()
+        };
+        let sheet = super::nup_document(&document(4), Default::default(), |_| settings).unwrap();
+        let page_id = sheet.page_iter().next().unwrap();
+        let media_box = sheet
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<_>>();
+        let margin = MARK_MARGIN;
+        assert_eq!(
+            media_box,
+            [0.0, 0.0, 200.0 + 2.0 * margin, 200.0 + 2.0 * margin]
+        );
+        let content = sheet.get_page_content(page_id).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        // a mark above and below each of the outer edges, and to each side of the top and bottom
+        assert_eq!(content.matches(" l\nS").count(), 8, "{content}");
+        assert!(content.contains(&format!("1 0 0 1 {margin} {margin} cm\n/P0 Do")));
+    }
+
+    #[test_case(None, 10.0 => Fit { size: (220.0, 120.0), origin: (10.0, 10.0), scale: 1.0 })]
+    #[test_case(Some((400.0, 300.0)), 10.0 => Fit { size: (400.0, 300.0), origin: (100.0, 100.0), scale: 1.0 }; "centered")]
+    #[test_case(Some((120.0, 300.0)), 10.0 => Fit { size: (120.0, 300.0), origin: (10.0, 125.0), scale: 0.5 }; "shrunk")]
+    fn fit(size: Option<(f32, f32)>, margin: f32) -> Fit {
+        let settings = SheetSettings {
+            size,
+            margin,
+            ..Default::default()
+        };
+        super::fit((200.0, 100.0), settings).unwrap()
+    }
+
+    #[test]
+    fn fit_no_room() {
+        let settings = SheetSettings {
+            size: Some((100.0, 100.0)),
+            margin: 50.0,
+            ..Default::default()
+        };
+        assert!(super::fit((200.0, 100.0), settings).is_err());
+    }
+
+    /// With a fixed size, the pages are shrunk to fit inside the margin, and the marks are drawn
+    /// around them, in the margin.
+    #[test]
+    fn nup_document_sheet_size() {
+        let settings = SheetSettings {
+            size: Some((150.0, 400.0)),
+            margin: 25.0,
+            marks: Some(Marks::Outer),
+            ..Default::default()
+        };
+        let sheet = super::nup_document(&document(4), Default::default(), |_| settings).unwrap();
+        let page_id = sheet.page_iter().next().unwrap();
+        let content = sheet.get_page_content(page_id).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        // the side is 200 points wide, and shrunk to half of that to fit between the margins
+        assert!(
+            content.contains("0.5 0 0 0.5 25 150 cm\n/P0 Do"),
+            "{content}"
+        );
+        assert!(
+            content.contains("0.5 0 0 0.5 75 150 cm\n/P1 Do"),
+            "{content}"
+        );
+        assert!(content.contains("25 253 m\n25 265 l"), "{content}");
+    }
+
+    /// Each side can be placed on a sheet of its own size.
+    #[test]
+    fn nup_document_side_sizes() {
+        let settings = |side| SheetSettings {
+            size: (side == 1).then_some((300.0, 300.0)),
+            ..Default::default()
+        };
+        let sheet = super::nup_document(&document(4), Default::default(), settings).unwrap();
+        let media_boxes = sheet
+            .page_iter()
+            .map(|page_id| {
+                sheet
+                    .get_dictionary(page_id)
+                    .unwrap()
+                    .get(b"MediaBox")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|value| value.as_float().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            media_boxes,
+            [[0.0, 0.0, 200.0, 200.0], [0.0, 0.0, 300.0, 300.0]].map(Vec::from)
+        );
+    }
+
+    /// With room reserved for them, the marks are inside the margin, and clear of every page,
+    /// however tight the margin is.
+    #[test_case("2x1", Marks::All, 0.0)]
+    #[test_case("2x2", Marks::Cut, 5.0)]
+    #[test_case("1x2", Marks::Outer, 30.0)]
+    fn reserve_marks(layout: &str, marks: Marks, margin: f32) {
+        let layout = layout.parse::<NupLayout>().unwrap();
+        let cell = (100.0, 200.0);
+        let settings = SheetSettings {
+            size: Some((200.0, 200.0)),
+            margin,
+            marks: Some(marks),
+            reserve_marks: true,
+        };
+        let fit = super::fit(layout.side_size(cell), settings).unwrap();
+        assert!(fit.scale < 1.0);
+        let place = |[x0, y0, x1, y1]: [f32; 4]| {
+            let (ox, oy) = fit.origin;
+            [
+                x0 * fit.scale + ox,
+                y0 * fit.scale + oy,
+                x1 * fit.scale + ox,
+                y1 * fit.scale + oy,
+            ]
+        };
+        let slots = layout
+            .slots(cell)
+            .into_iter()
+            .map(place)
+            .collect::<Vec<_>>();
+        let half_width = crate::MARK_WIDTH / 2.0;
+        for [x0, y0, x1, y1] in super::side_marks(layout, cell, fit, marks) {
+            let mark = [
+                x0.min(x1) - half_width,
+                y0.min(y1) - half_width,
+                x0.max(x1) + half_width,
+                y0.max(y1) + half_width,
+            ];
+            assert!(mark[0] >= margin && mark[1] >= margin, "{mark:?}");
+            assert!(
+                mark[2] <= 200.0 - margin && mark[3] <= 200.0 - margin,
+                "{mark:?}"
+            );
+            for slot in &slots {
+                let overlaps = mark[0] < slot[2]
+                    && slot[0] < mark[2]
+                    && mark[1] < slot[3]
+                    && slot[1] < mark[3];
+                assert!(!overlaps, "mark {mark:?} overlaps slot {slot:?}");
+            }
+        }
+    }
+
+    /// The viewer preferences of the document, and the private data of its pages, are kept.
+    #[test]
+    fn nup_document_keeps_metadata() {
+        let mut document = document(2);
+        let preferences =
+            Object::Dictionary([("Direction", Object::from("R2L"))].into_iter().collect());
+        document
+            .catalog_mut()
+            .unwrap()
+            .set("ViewerPreferences", preferences);
+        let page_id = document.page_iter().next().unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("PieceInfo", Dictionary::new());
+        let sheet =
+            super::nup_document(&document, Default::default(), |_| Default::default()).unwrap();
+        let catalog = sheet.catalog().unwrap();
+        let direction = catalog
+            .get(b"ViewerPreferences")
+            .and_then(Object::as_dict)
+            .and_then(|preferences| preferences.get(b"Direction"))
+            .and_then(Object::as_name)
+            .unwrap();
+        assert_eq!(direction, b"R2L");
+        let page_id = sheet.page_iter().next().unwrap();
+        let xobject_id = sheet
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Resources"))
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(Object::as_dict)
+            .and_then(|xobjects| xobjects.get(b"P0"))
+            .and_then(Object::as_reference)
+            .unwrap();
+        let xobject = sheet.get_object(xobject_id).unwrap().as_stream().unwrap();
+        assert!(xobject.dict.has(b"PieceInfo"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Write;
+
+use bookbinding::{arrange_pages_with, SignaturePlan};
+
+/// Width of one page slot in the diagram.
+const SLOT_WIDTH: usize = 40;
+/// Height of one side of a sheet in the diagram.
+const SIDE_HEIGHT: usize = 30;
+/// Space around the diagram and between sheets.
+const GAP: usize = 10;
+/// Width of the signature labels at the start of each row.
+const LABEL_WIDTH: usize = 50;
+
+/// Renders the plan as an SVG diagram, with a row for each signature and a rectangle for each
+/// sheet. The front of each sheet is drawn above the back, each slot is labelled with the number
+/// (starting from 1) of the page printed there, and the fold is drawn as a dashed line.
+///
+/// The output only uses generic font families and does not depend on anything but the plan, so it
+/// is the same on every run.
+pub fn plan_diagram(plan: &SignaturePlan) -> String {
+    let mut slots = vec![0; plan.num_pages().0];
+    arrange_pages_with(plan, |src, dest| slots[dest] = src + 1);
+
+    let sheet_width = SLOT_WIDTH * 2;
+    let sheet_height = SIDE_HEIGHT * 2;
+    let max_sheets = plan.signatures.iter().copied().max().unwrap_or(0);
+    let width = GAP + LABEL_WIDTH + max_sheets * (sheet_width + GAP);
+    let height = GAP + plan.signatures.len() * (sheet_height + GAP);
+
+    let mut svg = String::new();
+    // writing to a `String` can't fail
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="10">"#
+    );
+    let mut sheets = slots.chunks(4);
+    for (row, &num_sheets) in plan.signatures.iter().enumerate() {
+        let y = GAP + row * (sheet_height + GAP);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{GAP}" y="{}">Sig {}</text>"#,
+            y + SIDE_HEIGHT + 4,
+            row + 1
+        );
+        for (column, sheet) in sheets.by_ref().take(num_sheets).enumerate() {
+            let x = GAP + LABEL_WIDTH + column * (sheet_width + GAP);
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{sheet_width}" height="{sheet_height}" fill="none" stroke="black"/>"#
+            );
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{x}" y1="{mid}" x2="{}" y2="{mid}" stroke="gray"/>"#,
+                x + sheet_width,
+                mid = y + SIDE_HEIGHT
+            );
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{fold}" y1="{y}" x2="{fold}" y2="{}" stroke="gray" stroke-dasharray="3 2"/>"#,
+                y + sheet_height,
+                fold = x + SLOT_WIDTH
+            );
+            for (side, (marker, pages)) in ["F", "B"].iter().zip(sheet.chunks(2)).enumerate() {
+                let side_y = y + side * SIDE_HEIGHT;
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="6" fill="gray">{marker}</text>"#,
+                    x + 2,
+                    side_y + 7
+                );
+                for (i, page) in pages.iter().enumerate() {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{}" y="{}" text-anchor="middle">{page}</text>"#,
+                        x + i * SLOT_WIDTH + SLOT_WIDTH / 2,
+                        side_y + SIDE_HEIGHT / 2 + 4
+                    );
+                }
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use bookbinding::{plan_signatures, Sheets, SignatureParams};
+
+    /// The page labels in the diagram, in the order they are drawn.
+    fn labels(svg: &str) -> Vec<&str> {
+        svg.lines()
+            .filter(|line| line.contains(r#"text-anchor="middle""#))
+            .filter_map(|line| line.split('>').nth(1)?.strip_suffix("</text"))
+            .collect()
+    }
+
+    #[test]
+    fn plan_diagram() {
+        let params = SignatureParams {
+            signature_size: 2,
+            minimum_remainder_size: 1,
+            ..Default::default()
+        };
+        let plan = plan_signatures(Sheets(3), params);
+        let svg = super::plan_diagram(&plan);
+        assert_eq!(svg, super::plan_diagram(&plan));
+        assert_eq!(svg.matches("<rect ").count(), 3);
+        assert_eq!(svg.matches(">Sig ").count(), 2);
+        assert_eq!(
+            labels(&svg),
+            ["8", "1", "2", "7", "6", "3", "4", "5", "12", "9", "10", "11"]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! In-memory documents for tests.
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+/// Builds a document with one page for each of the given media boxes, all directly under the root
+/// page tree node. Each page draws a short line, and is labelled with its index by a comment at
+/// the start of its content stream, which can be read back with [`page_index`].
+pub fn document_with_boxes(media_boxes: &[[f32; 4]]) -> Document {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let kids = media_boxes
+        .iter()
+        .enumerate()
+        .map(|(index, media_box)| {
+            let content = format!("% page {index}\n0 0 m 10 10 l S\n").into_bytes();
+            let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+            let page = Object::Dictionary(
+                [
+                    ("Type", Object::from("Page")),
+                    ("Parent", Object::from(pages_id)),
+                    ("Contents", Object::from(content_id)),
+                    ("Resources", Object::Dictionary(Dictionary::new())),
+                    (
+                        "MediaBox",
+                        Object::from(media_box.map(Object::from).to_vec()),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            );
+            Object::from(document.add_object(page))
+        })
+        .collect::<Vec<_>>();
+    let pages = Object::Dictionary(
+        [
+            ("Type", Object::from("Pages")),
+            ("Count", Object::from(kids.len() as i64)),
+            ("Kids", Object::from(kids)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    document.set_object(pages_id, pages);
+    let catalog_id = document.add_object(Object::Dictionary(
+        [
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::from(pages_id)),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    document.trailer.set("Root", catalog_id);
+    document
+}
+
+/// Reads back the index of a page created by [`document_with_boxes`], or `None` if the page has
+/// no label (such as an inserted blank page).
+pub fn page_index(document: &Document, page_id: ObjectId) -> Option<usize> {
+    let content = document.get_page_content(page_id).ok()?;
+    let content = String::from_utf8(content).ok()?;
+    let label = content.strip_prefix("% page ")?.lines().next()?;
+    label.parse().ok()
+}
+
+/// The index of each page of the document, in order. See [`page_index`].
+pub fn page_indices(document: &Document) -> Vec<Option<usize>> {
+    document
+        .page_iter()
+        .map(|id| page_index(document, id))
+        .collect()
+}
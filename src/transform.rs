@@ -0,0 +1,171 @@
+//! Affine transforms of page content.
+
+use lopdf::{content::Operation, Content, Document, Object, ObjectId};
+
+use crate::pdf;
+
+/// A transform of a page's content and boxes, made up of an affine matrix and an optional clip
+/// rectangle. Features which move or clip page content should add to a single `PageTransform`
+/// with [`then`](PageTransform::then), and apply it once with
+/// [`apply`](PageTransform::apply), so that each page's content is only wrapped once and the
+/// transforms are combined in a well-defined order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageTransform {
+    /// The matrix, in the order used by the `cm` operator.
+    pub matrix: [f32; 6],
+    /// The region that content is clipped to, in the transformed coordinate space.
+    pub clip: Option<[f32; 4]>,
+}
+
+impl Default for PageTransform {
+    fn default() -> Self {
+        PageTransform::IDENTITY
+    }
+}
+
+impl PageTransform {
+    /// The transform which leaves pages unchanged.
+    pub const IDENTITY: PageTransform = PageTransform {
+        matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        clip: None,
+    };
+
+    /// The transform which rotates a rectangle clockwise by a multiple of 90 degrees, keeping its
+    /// lower left corner at its original position.
+    pub fn rotation(degrees: i64, [x0, y0, x1, y1]: [f32; 4]) -> Self {
+        let (width, height) = (x1 - x0, y1 - y0);
+        // a matrix which rotates the rectangle clockwise, with its origin at (0, 0)
+        let rotate = match degrees.rem_euclid(360) {
+            0 => return PageTransform::IDENTITY,
+            90 => [0.0, -1.0, 1.0, 0.0, 0.0, width],
+            180 => [-1.0, 0.0, 0.0, -1.0, width, height],
+            _ => [0.0, 1.0, -1.0, 0.0, height, 0.0],
+        };
+        PageTransform::IDENTITY
+            .then([1.0, 0.0, 0.0, 1.0, -x0, -y0])
+            .then(rotate)
+            .then([1.0, 0.0, 0.0, 1.0, x0, y0])
+    }
+
+    /// Follows this transform with another matrix. Any clip rectangle is transformed along with
+    /// the content, as its bounding box.
+    pub fn then(self, [a2, b2, c2, d2, e2, f2]: [f32; 6]) -> Self {
+        let [a1, b1, c1, d1, e1, f1] = self.matrix;
+        let matrix = [
+            a1 * a2 + b1 * c2,
+            a1 * b2 + b1 * d2,
+            c1 * a2 + d1 * c2,
+            c1 * b2 + d1 * d2,
+            e1 * a2 + f1 * c2 + e2,
+            e1 * b2 + f1 * d2 + f2,
+        ];
+        let clip = self
+            .clip
+            .map(|clip| transform_rect([a2, b2, c2, d2, e2, f2], clip));
+        PageTransform { matrix, clip }
+    }
+
+    /// Transforms a rectangle, returning the bounding box of the result.
+    pub fn transform_rect(&self, rect: [f32; 4]) -> [f32; 4] {
+        transform_rect(self.matrix, rect)
+    }
+
+    /// Applies the transform to a page, wrapping its content in a single pair of content streams
+    /// and transforming its boxes to match. Does nothing if the transform is the identity.
+    pub fn apply(&self, document: &mut Document, page_id: ObjectId) -> color_eyre::Result<()> {
+        if *self == PageTransform::IDENTITY {
+            return Ok(());
+        }
+        for key in pdf::BOXES {
+            if let Some(rect) = pdf::page_box(document, page_id, key) {
+                let rect = self.transform_rect(rect);
+                document
+                    .get_dictionary_mut(page_id)?
+                    .set(key, rect.map(Object::from).to_vec());
+            }
+        }
+        let mut operations = vec![Operation::new("q", vec![])];
+        if let Some([x0, y0, x1, y1]) = self.clip {
+            // the clip is in the transformed space, so it goes before the matrix
+            operations.extend([
+                Operation::new("re", [x0, y0, x1 - x0, y1 - y0].map(Object::from).to_vec()),
+                Operation::new("W", vec![]),
+                Operation::new("n", vec![]),
+            ]);
+        }
+        operations.push(Operation::new("cm", self.matrix.map(Object::from).to_vec()));
+        let before = Content { operations };
+        pdf::wrap_contents(document, page_id, before.encode()?, b"Q".to_vec())?;
+        Ok(())
+    }
+}
+
+/// Transforms a rectangle by an affine matrix, returning the bounding box of the result.
+fn transform_rect([a, b, c, d, e, f]: [f32; 6], [x0, y0, x1, y1]: [f32; 4]) -> [f32; 4] {
+    let (xa, ya) = (a * x0 + c * y0 + e, b * x0 + d * y0 + f);
+    let (xb, yb) = (a * x1 + c * y1 + e, b * x1 + d * y1 + f);
+    [xa.min(xb), ya.min(yb), xa.max(xb), ya.max(yb)]
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{Content, Object};
+
+    use super::PageTransform;
+    use crate::{pdf, testing};
+
+    #[test]
+    fn then() {
+        let rect = [0.0, 0.0, 200.0, 100.0];
+        let quarter = PageTransform::rotation(90, rect);
+        let half = quarter.then(PageTransform::rotation(90, quarter.transform_rect(rect)).matrix);
+        assert_eq!(half, PageTransform::rotation(180, rect));
+        let clipped = PageTransform {
+            clip: Some([0.0, 0.0, 100.0, 50.0]),
+            ..PageTransform::IDENTITY
+        }
+        .then(quarter.matrix);
+        assert_eq!(clipped.clip, Some([0.0, 100.0, 50.0, 200.0]));
+    }
+
+    #[test]
+    fn apply() {
+        let mut document = testing::document_with_boxes(&[[0.0, 0.0, 200.0, 100.0]]);
+        let page_id = document.page_iter().next().unwrap();
+        let transform = PageTransform {
+            matrix: [2.0, 0.0, 0.0, 2.0, 0.0, 0.0],
+            clip: Some([10.0, 10.0, 390.0, 190.0]),
+        }
+        .then([1.0, 0.0, 0.0, 1.0, 5.0, 5.0]);
+        transform.apply(&mut document, page_id).unwrap();
+
+        assert_eq!(
+            pdf::page_box(&document, page_id, b"MediaBox"),
+            Some([5.0, 5.0, 405.0, 205.0])
+        );
+        let content = document.get_page_content(page_id).unwrap();
+        let operations = Content::decode(&content).unwrap().operations;
+        let operators = operations
+            .iter()
+            .map(|op| op.operator.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(operators[..5], ["q", "re", "W", "n", "cm"]);
+        let matrix = operations[4]
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matrix, [2.0, 0.0, 0.0, 2.0, 5.0, 5.0]);
+        let clip = operations[1]
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(clip, [15.0, 15.0, 380.0, 180.0]);
+        // the content is only wrapped once
+        assert!(matches!(
+            document.get_dictionary(page_id).unwrap().get(b"Contents"),
+            Ok(Object::Array(contents)) if contents.len() == 3
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+//! Counts of sheets, leaves, and pages, which are easy to confuse.
+//!
+//! Each sheet of paper is folded once, making 2 leaves, each of which has a page on each side, so
+//! a sheet holds 4 pages. Signature sizes are always counted in sheets.
+
+use std::ops::Add;
+
+/// A number of sheets of paper, each folded into 2 leaves with 4 pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sheets(pub usize);
+
+/// A number of leaves, each with 2 pages, one on each side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Leaves(pub usize);
+
+/// A number of pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pages(pub usize);
+
+impl Sheets {
+    /// The number of leaves in this many sheets.
+    pub fn leaves(self) -> Leaves {
+        Leaves(self.0 * 2)
+    }
+
+    /// The number of pages in this many sheets.
+    pub fn pages(self) -> Pages {
+        Pages(self.0 * 4)
+    }
+}
+
+impl Leaves {
+    /// The number of pages in this many leaves.
+    pub fn pages(self) -> Pages {
+        Pages(self.0 * 2)
+    }
+
+    /// The number of sheets needed to hold this many leaves, rounding up.
+    pub fn sheets_needed(self) -> Sheets {
+        Sheets(self.0.div_ceil(2))
+    }
+}
+
+impl Pages {
+    /// The number of leaves needed to hold this many pages, rounding up.
+    pub fn leaves_needed(self) -> Leaves {
+        Leaves(self.0.div_ceil(2))
+    }
+
+    /// The number of sheets needed to hold this many pages, rounding up.
+    pub fn sheets_needed(self) -> Sheets {
+        Sheets(self.0.div_ceil(4))
+    }
+}
+
+impl From<Sheets> for Leaves {
+    fn from(sheets: Sheets) -> Self {
+        sheets.leaves()
+    }
+}
+
+impl From<Sheets> for Pages {
+    fn from(sheets: Sheets) -> Self {
+        sheets.pages()
+    }
+}
+
+impl From<Leaves> for Pages {
+    fn from(leaves: Leaves) -> Self {
+        leaves.pages()
+    }
+}
+
+impl Add for Sheets {
+    type Output = Sheets;
+
+    fn add(self, other: Sheets) -> Sheets {
+        Sheets(self.0 + other.0)
+    }
+}
+
+impl std::iter::Sum for Sheets {
+    fn sum<I: Iterator<Item = Sheets>>(iter: I) -> Sheets {
+        Sheets(iter.map(|sheets| sheets.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{Leaves, Pages, Sheets};
+
+    #[test]
+    fn sheets() {
+        assert_eq!(Sheets(3).leaves(), Leaves(6));
+        assert_eq!(Sheets(3).pages(), Pages(12));
+        assert_eq!(Pages::from(Leaves::from(Sheets(3))), Sheets(3).pages());
+    }
+
+    #[test_case(0 => (Leaves(0), Sheets(0)))]
+    #[test_case(1 => (Leaves(1), Sheets(1)))]
+    #[test_case(4 => (Leaves(2), Sheets(1)))]
+    #[test_case(5 => (Leaves(3), Sheets(2)))]
+    #[test_case(26 => (Leaves(13), Sheets(7)))]
+    fn pages_needed(num_pages: usize) -> (Leaves, Sheets) {
+        let pages = Pages(num_pages);
+        assert_eq!(pages.leaves_needed().sheets_needed(), pages.sheets_needed());
+        (pages.leaves_needed(), pages.sheets_needed())
+    }
+}